@@ -1,14 +1,22 @@
-use std::fs::{File, read_dir};
+use std::fs::{File, read_dir, read_to_string};
 use std::io::{Result, Write};
+use std::process::Command;
 
 fn main() {
     println!("cargo:rerun-if-changed=../user/src/");
     println!("cargo:rerun-if-changed={TARGET_PATH}");
+    println!("cargo:rerun-if-env-changed={KERNEL_SYMS_ENV}");
     insert_app_data().unwrap();
+    insert_kernel_symbols().unwrap();
+    set_version_env();
+    set_bootargs_env();
 }
 
 static TARGET_PATH: &str = "../user/target/riscv64gc-unknown-none-elf/release/";
 
+/// Names the `nm` output of the previous kernel build, see `make kernel`.
+static KERNEL_SYMS_ENV: &str = "KERNEL_SYMS";
+
 fn insert_app_data() -> Result<()> {
     let mut f = File::create("src/link_app.S").unwrap();
     let mut apps: Vec<_> = read_dir("../user/src/bin")
@@ -22,6 +30,22 @@ fn insert_app_data() -> Result<()> {
             name_with_ext
         })
         .collect();
+    // flat binaries keep a suffix for the kernel to tell them apart, see `loader`
+    if let Ok(dir) = read_dir("../user/src/flat") {
+        for dir_entry in dir {
+            let name = dir_entry.unwrap().file_name().into_string().unwrap();
+            let Some(stem) = name.strip_suffix(".S") else {
+                continue;
+            };
+            // assembling them needs `FLAT_CC`, see user/Makefile
+            let app = format!("{stem}.flat");
+            if std::fs::exists(format!("{TARGET_PATH}{app}"))? {
+                apps.push(app);
+            } else {
+                println!("cargo:warning=flat binary {app} was not built, leaving it out");
+            }
+        }
+    }
     apps.sort();
 
     writeln!(
@@ -51,6 +75,19 @@ _app_names:"#
         writeln!(f, r#"    .string "{app}""#)?;
     }
 
+    writeln!(
+        f,
+        r#"
+    .align 2
+    .global _app_crcs
+_app_crcs:"#
+    )?;
+
+    for app in apps.iter() {
+        let data = std::fs::read(format!("{TARGET_PATH}{app}"))?;
+        writeln!(f, r#"    .word {:#010x}"#, crc32(&data))?;
+    }
+
     for (idx, app) in apps.iter().enumerate() {
         println!("app_{idx}: {app}");
         writeln!(
@@ -67,3 +104,113 @@ app_{idx}_end:"#,
     }
     Ok(())
 }
+
+/// CRC-32 (IEEE) of `data`, the same as the kernel's `crc32::crc32`.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// Parse `nm --numeric-sort --demangle` output into sorted `(address, name)` pairs of the
+/// text symbols.
+fn parse_kernel_symbols(nm_output: &str) -> Vec<(usize, String)> {
+    let mut symbols: Vec<_> = nm_output
+        .lines()
+        .filter_map(|line| {
+            // "<address> <type> <name>", demangled names may contain spaces
+            let mut fields = line.splitn(3, ' ');
+            let addr = usize::from_str_radix(fields.next()?, 16).ok()?;
+            let ty = fields.next()?;
+            let mut name = fields.next()?.to_string();
+            if ty != "t" && ty != "T" {
+                return None;
+            }
+            // drop the legacy mangling hash, e.g. "::h0123456789abcdef"
+            let hash_len = "::h0123456789abcdef".len();
+            if name.len() > hash_len && name[name.len() - hash_len..].starts_with("::h") {
+                name.truncate(name.len() - hash_len);
+            }
+            Some((addr, name))
+        })
+        .collect();
+    symbols.sort_by_key(|&(addr, _)| addr);
+    symbols
+}
+
+/// Write `src/ksyms.S`, the symbol table used by `stack_trace` to name return addresses.
+///
+/// The table comes from the previous build, so it is empty on the first build. Only
+/// `.rodata` grows when it is filled in, code addresses stay the same.
+fn insert_kernel_symbols() -> Result<()> {
+    let symbols = match std::env::var(KERNEL_SYMS_ENV) {
+        Ok(path) => {
+            println!("cargo:rerun-if-changed={path}");
+            read_to_string(&path)
+                .map(|nm_output| parse_kernel_symbols(&nm_output))
+                .unwrap_or_default()
+        }
+        Err(_) => Vec::new(),
+    };
+
+    let mut f = File::create("src/ksyms.S").unwrap();
+    writeln!(
+        f,
+        r#"
+    .section .rodata.ksyms
+    .align 3
+    .global _ksyms
+_ksyms:
+    .quad {}"#,
+        symbols.len()
+    )?;
+    for (addr, _) in symbols.iter() {
+        writeln!(f, r#"    .quad {addr:#x}"#)?;
+    }
+    for idx in 0..symbols.len() {
+        writeln!(f, r#"    .quad ksym_name_{idx}"#)?;
+    }
+    for (idx, (_, name)) in symbols.iter().enumerate() {
+        let name = name.replace('\\', "\\\\").replace('"', "\\\"");
+        writeln!(f, r#"ksym_name_{idx}:"#)?;
+        writeln!(f, r#"    .string "{name}""#)?;
+    }
+    Ok(())
+}
+
+/// Run `program` with `args` and return its trimmed stdout, or `None` if it failed.
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8(output.stdout).ok()?.trim().to_string())
+}
+
+/// Export the kernel version (`git describe`) and build time to the crate, see `version.rs`.
+fn set_version_env() {
+    println!("cargo:rerun-if-changed=../.git/HEAD");
+    println!("cargo:rerun-if-changed=../.git/refs/");
+    let version = command_output("git", &["describe", "--always", "--tags", "--dirty"])
+        .unwrap_or_else(|| env!("CARGO_PKG_VERSION").to_string());
+    let build_time = command_output("date", &["-u", "+%Y-%m-%d %H:%M:%S UTC"])
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=MINI_OS_VERSION={version}");
+    println!("cargo:rustc-env=MINI_OS_BUILD_TIME={build_time}");
+}
+
+/// Pass the boot parameters in `BOOTARGS` on to the kernel, see `boot::BOOTARGS`.
+fn set_bootargs_env() {
+    println!("cargo:rerun-if-env-changed=BOOTARGS");
+    let bootargs = std::env::var("BOOTARGS").unwrap_or_default();
+    println!("cargo:rustc-env=MINI_OS_BOOTARGS={bootargs}");
+}
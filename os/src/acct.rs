@@ -0,0 +1,56 @@
+//! Process accounting, built with the `acct` feature.
+//!
+//! Every app that exits leaves an `AcctRecord` with its name, exit code, CPU time and peak
+//! memory in a ring buffer, which `sys_acct_collect` drains to user space, e.g. for
+//! `lastcomm` or a test harness grading the apps of a run. Kthreads are not recorded.
+//! When the ring is full the oldest records are overwritten.
+
+use crate::sync::UPSafeCell;
+use crate::task::TASK_NAME_LEN;
+use alloc::collections::vec_deque::VecDeque;
+use alloc::vec::Vec;
+use lazy_static::*;
+
+/// Records kept before the oldest ones are overwritten.
+const ACCT_CAPACITY: usize = 256;
+
+/// The accounting record of an exited app, layout compatible with the user library's.
+///
+/// There are no processes, so no pid or parent: `task` is the task id of the app.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct AcctRecord {
+    /// Time of the exit since boot in nanoseconds.
+    pub time_ns: u64,
+    pub task: usize,
+    pub exit_code: isize,
+    /// Timer ticks the app ran in user mode.
+    pub user_ticks: usize,
+    /// Timer ticks the kernel ran syscalls for the app.
+    pub kernel_ticks: usize,
+    /// Most frames the address space of the app owned at once.
+    pub peak_frames: usize,
+    /// NUL terminated, truncated to fit.
+    pub name: [u8; TASK_NAME_LEN],
+}
+
+lazy_static! {
+    static ref ACCT: UPSafeCell<VecDeque<AcctRecord>> =
+        unsafe { UPSafeCell::new(VecDeque::with_capacity(ACCT_CAPACITY)) };
+}
+
+/// Append the record of an app that just exited.
+pub fn record(record: AcctRecord) {
+    let mut acct = ACCT.exclusive_access();
+    if acct.len() == ACCT_CAPACITY {
+        acct.pop_front();
+    }
+    acct.push_back(record);
+}
+
+/// Remove and return up to `max` of the oldest records.
+pub fn collect(max: usize) -> Vec<AcctRecord> {
+    let mut acct = ACCT.exclusive_access();
+    let len = acct.len().min(max);
+    acct.drain(..len).collect()
+}
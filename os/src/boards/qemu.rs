@@ -2,8 +2,44 @@
 /// Check `make device-tree` timebase-frequency
 pub const CLOCK_FREQ: u64 = 10_000_000;
 
+/// Name of the board, reported by `sys_uname`.
+pub const BOARD_NAME: &str = "qemu";
+
+/// Number of harts the kernel runs on, only the boot hart is brought up.
+pub const NUM_HARTS: usize = 1;
+
 /// The end address of the physical memory available to the QEMU board.
 /// This constant defines the upper boundary of usable RAM.
 /// 0x8800_0000 = 0x8000_0000 + 0x0800_0000 (128MB)
 pub const MEMORY_END: usize = 0x8800_0000;
+
+/// Memory at the top of RAM holding the kernel log across warm reboots, see `pstore` (64 KiB).
+pub const PSTORE_SIZE: usize = 0x1_0000;
+pub const PSTORE_BASE: usize = MEMORY_END - PSTORE_SIZE;
+
+/// Physical memory ranges `(start, end)` the frame allocator must never hand out, e.g. memory
+/// firmware keeps using. RustSBI lives below the kernel image, so on QEMU only the pstore
+/// region is reserved.
+pub const RESERVED_MEMORY: &[(usize, usize)] = &[(PSTORE_BASE, MEMORY_END)];
+
+/// The sifive_test device of the `virt` machine, writing it ends QEMU with an exit status.
+pub const VIRT_TEST: usize = 0x10_0000;
+
+/// The goldfish RTC of the `virt` machine, the wall clock time at boot.
+pub const GOLDFISH_RTC: usize = 0x10_1000;
+
+/// The 16550 UART of the `virt` machine, QEMU's serial port.
+pub const UART0: usize = 0x1000_0000;
+
+/// The virtio-mmio transports of the `virt` machine, `VIRTIO_MMIO_COUNT` slots of 4 KiB.
+pub const VIRTIO_MMIO: usize = 0x1000_1000;
+pub const VIRTIO_MMIO_COUNT: usize = 8;
+
+/// MMIO regions `(start, size)` the kernel maps into its address space.
+pub const MMIO: &[(usize, usize)] = &[
+    (VIRT_TEST, 0x1000),
+    (GOLDFISH_RTC, 0x1000),
+    (UART0, 0x1000),
+    (VIRTIO_MMIO, VIRTIO_MMIO_COUNT * 0x1000),
+];
    
@@ -0,0 +1,48 @@
+//! Boot stages.
+//!
+//! Until `logging::init` runs, the `log` macros print nothing, and until memory management
+//! is up, nothing may touch the heap or the lazy statics built on it (frame allocator, kernel
+//! space, task manager). Code that can run that early, above all the panic handler, checks the
+//! stage first or prints with `emergency_println!`, which formats straight to the console
+//! device without allocating.
+
+use core::sync::atomic::{AtomicU8, Ordering};
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[repr(u8)]
+pub enum BootStage {
+    /// Only the boot stack and the SBI console can be relied on.
+    Entry = 1,
+    /// The logger is installed.
+    Logging,
+    /// Heap, frame allocator and kernel space are up.
+    Memory,
+    /// Tasks are about to run or running.
+    Tasks,
+}
+
+/// The current `BootStage`; starting non-zero keeps it out of .bss, so it is valid before
+/// `clear_bss`.
+static STAGE: AtomicU8 = AtomicU8::new(BootStage::Entry as u8);
+
+/// Record that boot reached `stage`.
+pub fn set_stage(stage: BootStage) {
+    STAGE.store(stage as u8, Ordering::Release);
+}
+
+/// Returns `true` once boot reached `stage`.
+pub fn reached(stage: BootStage) -> bool {
+    STAGE.load(Ordering::Acquire) >= stage as u8
+}
+
+/// Boot parameters, space-separated `name=value` pairs given as `BOOTARGS` at build time,
+/// e.g. `make run BOOTARGS="console=uart"`. The loader passes no command line to the kernel.
+pub const BOOTARGS: &str = env!("MINI_OS_BOOTARGS");
+
+/// Returns the value of boot parameter `name`, `None` if it is not given.
+pub fn param(name: &str) -> Option<&'static str> {
+    BOOTARGS.split_whitespace().find_map(|arg| {
+        let (key, value) = arg.split_once('=')?;
+        (key == name).then_some(value)
+    })
+}
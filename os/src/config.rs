@@ -1,18 +1,58 @@
 /// User stack size in bytes (8 KiB).
 pub const USER_STACK_SIZE: usize = 4096 * 2;
 
+/// Maximum size the user stack may grow to on page faults (1 MiB).
+pub const USER_STACK_LIMIT: usize = 1024 * 1024;
+
+/// Most frames one user address space may own, page table nodes included (16 MiB).
+///
+/// Faults needing a frame beyond it fail as if memory ran out, so a single runaway app
+/// cannot starve the others.
+pub const USER_FRAME_LIMIT: usize = 4096;
+
+/// Where flat (non-ELF) binaries are loaded and entered, the base address of
+/// `user/src/linker.ld` too.
+pub const FLAT_BASE_ADDR: usize = 0x10000;
+
+/// Whether user stacks start a random number of pages above the ELF image.
+///
+/// Turn it off for the same user addresses on every boot when debugging.
+pub const ASLR: bool = true;
+
+/// Most pages left unmapped by ASLR between the ELF image and the user stack (64 MiB).
+pub const ASLR_STACK_PAGES: usize = 16 * 1024;
+
 /// Kernel stack size in bytes (8 KiB).
 pub const KERNEL_STACK_SIZE: usize = 4096 * 2;
 
 /// Kernel heap size in bytes (3 MiB).
 pub const KERNEL_HEAP_SIZE: usize = 3 * 1024 * 1024; // 0x30_0000
 
+/// Timer ticks a task may run in user mode without any syscall before the watchdog fires (5 s).
+pub const WATCHDOG_TIMEOUT_TICKS: usize = 500;
+
+/// Whether the watchdog kills the offending task or only reports it.
+pub const WATCHDOG_KILL: bool = true;
+
+/// Messages a rate-limited log call site may print per interval, see `log_ratelimited!`.
+pub const RATELIMIT_BURST: usize = 10;
+
+/// Length of a rate limiting interval in milliseconds.
+pub const RATELIMIT_INTERVAL_MS: u64 = 1000;
+
 /// Page offset bits for SV39
 pub const PAGE_OFFSET_BITS: usize = 12;
 
 /// Page size in bytes (4 KiB).
 pub const PAGE_SIZE: usize = 1 << PAGE_OFFSET_BITS;
 
+/// Offset of the kernel's direct map of physical memory: physical address `pa` is accessed
+/// at `pa + PHYS_MEM_OFFSET`, see `mm::phys_to_virt`.
+///
+/// This is the start of the upper half of the SV39 address space, and the kernel image runs
+/// in the direct map too. `linker.ld` and the boot page table in `entry.asm` must agree.
+pub const PHYS_MEM_OFFSET: usize = 0xffff_ffc0_0000_0000;
+
 /// Address of the trampoline code (top of virtual address space).
 ///
 /// This address is set to the highest possible value in the virtual address space (`usize::MAX - PAGE_SIZE + 1`).
@@ -26,6 +66,10 @@ pub const TRAMPOLINE_ADDR: usize = usize::MAX - PAGE_SIZE + 1;
 /// Address for the trap context (just below the trampoline).
 pub const TRAP_CONTEXT_ADDR: usize = TRAMPOLINE_ADDR - PAGE_SIZE;
 
+/// End of the user part of an address space, the lower half of SV39 (256 GiB). Above it a
+/// user address space only maps the trap context and the trampoline.
+pub const USER_SPACE_END: usize = 1 << 38;
+
 /// Returns the bottom and top addresses of the kernel stack for a given app.
 ///
 /// # Arguments
@@ -41,4 +85,4 @@ pub fn kernel_stack_pos(app_id: usize) -> (usize, usize) {
     (bottom, top)
 }
 
-pub use crate::board::{CLOCK_FREQ, MEMORY_END};
+pub use crate::board::{BOARD_NAME, CLOCK_FREQ, MEMORY_END, MMIO, NUM_HARTS};
@@ -1,21 +1,229 @@
+//! Kernel console.
+//!
+//! Output goes to one of several interchangeable `ConsoleBackend`s: the SBI console, which
+//! works from the first instruction on, the 16550 UART or a virtio console. `init` switches
+//! to the backend the `console=` boot parameter names once memory management is up.
+//!
+//! Kernel log lines may go to a backend of their own, named by the `log=` boot parameter,
+//! so they do not interleave with what tasks print: QEMU's `virt` machine has a single
+//! UART, but e.g. `console=uart log=virtio` leaves the serial port to the tasks and puts
+//! the log into `virtio-console.log`.
+
+use alloc::vec::Vec;
 use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use log::warn;
 
 use crate::sbi::console_putchar;
+use crate::trap::InterruptGuard;
+use crate::{boot, uart, virtio_console};
+
+/// Size at which a task's line buffer is flushed even without a newline.
+const LINE_BUFFER_SIZE: usize = 256;
+
+/// Held while a line or a formatted print goes out, so writers never interleave.
+///
+/// S-mode interrupts stay disabled while it is held, so with one hart the lock can only be
+/// found held when a print re-enters, e.g. a fault while printing. That print is dropped
+/// rather than deadlock or interleave; the panic handler goes around the lock, see
+/// `emergency_print`.
+static CONSOLE_LOCK: AtomicBool = AtomicBool::new(false);
+
+/// Run `f` with the console lock held and interrupts disabled, or drop the output if the
+/// lock is already taken.
+fn with_console_lock(f: impl FnOnce()) {
+    let _interrupts = InterruptGuard::disable();
+    if CONSOLE_LOCK
+        .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+        .is_err()
+    {
+        return;
+    }
+    f();
+    CONSOLE_LOCK.store(false, Ordering::Release);
+}
+
+/// A device the console writes to.
+pub trait ConsoleBackend: Sync {
+    /// Write `bytes` out, waiting until the device took them.
+    fn write_bytes(&self, bytes: &[u8]);
+}
+
+/// The legacy SBI console, one SBI call per byte.
+struct SbiConsole;
+
+impl ConsoleBackend for SbiConsole {
+    fn write_bytes(&self, bytes: &[u8]) {
+        for &byte in bytes {
+            console_putchar(byte as usize);
+        }
+    }
+}
+
+/// The 16550 UART, driven directly.
+struct UartConsole;
+
+impl ConsoleBackend for UartConsole {
+    fn write_bytes(&self, bytes: &[u8]) {
+        for &byte in bytes {
+            uart::putchar(byte);
+        }
+    }
+}
+
+/// A virtio console, falling back to SBI when a panic interrupted a write to it.
+struct VirtioConsole;
+
+impl ConsoleBackend for VirtioConsole {
+    fn write_bytes(&self, bytes: &[u8]) {
+        if !virtio_console::write_bytes(bytes) {
+            SbiConsole.write_bytes(bytes);
+        }
+    }
+}
+
+const BACKEND_SBI: u8 = 0;
+const BACKEND_UART: u8 = 1;
+const BACKEND_VIRTIO: u8 = 2;
+
+/// The backend in use, one of `BACKEND_*`.
+static BACKEND: AtomicU8 = AtomicU8::new(BACKEND_SBI);
+/// The backend kernel log lines go to, see `log_print`.
+static LOG_BACKEND: AtomicU8 = AtomicU8::new(BACKEND_SBI);
+
+fn backend_of(backend: &AtomicU8) -> &'static dyn ConsoleBackend {
+    match backend.load(Ordering::Acquire) {
+        BACKEND_UART => &UartConsole,
+        BACKEND_VIRTIO => &VirtioConsole,
+        _ => &SbiConsole,
+    }
+}
+
+fn backend() -> &'static dyn ConsoleBackend {
+    backend_of(&BACKEND)
+}
+
+/// Set up the backend called `name` by boot parameter `param`: `sbi` (also for `None`),
+/// `uart` or `virtio`.
+///
+/// # Returns
+/// The backend, SBI if the device is missing.
+fn open_backend(param: &str, name: Option<&str>) -> u8 {
+    match name {
+        None | Some("sbi") => BACKEND_SBI,
+        Some("uart") => {
+            uart::init();
+            BACKEND_UART
+        }
+        Some("virtio") if virtio_console::init() => BACKEND_VIRTIO,
+        Some(name) => {
+            warn!("[kernel] {param} {name} is not available, using sbi");
+            BACKEND_SBI
+        }
+    }
+}
+
+/// Switch to the backend named by the `console=` boot parameter: `sbi` (the default), `uart`
+/// or `virtio`, and the kernel log to the one named by `log=`, by default the same. Stays
+/// on SBI if the device is missing.
+pub fn init() {
+    let console = boot::param("console");
+    let backend = open_backend("console", console);
+    BACKEND.store(backend, Ordering::Release);
+    let log_backend = match boot::param("log") {
+        // a device is set up once, the virtio console would be reset
+        None => backend,
+        log if log == console => backend,
+        log => open_backend("log", log),
+    };
+    LOG_BACKEND.store(log_backend, Ordering::Release);
+}
 
 struct Stdout;
 
 impl Write for Stdout {
     fn write_str(&mut self, s: &str) -> core::fmt::Result {
-        for c in s.chars() {
-            console_putchar(c as usize);
-        }
+        backend().write_bytes(s.as_bytes());
+        Ok(())
+    }
+}
+
+/// Output of `log_print`.
+struct LogStdout;
+
+impl Write for LogStdout {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        backend_of(&LOG_BACKEND).write_bytes(s.as_bytes());
+        Ok(())
+    }
+}
 
+/// Output of `emergency_print`, the UART if it is the backend in use and SBI otherwise.
+struct EmergencyStdout;
+
+impl Write for EmergencyStdout {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        match BACKEND.load(Ordering::Acquire) {
+            BACKEND_UART => UartConsole.write_bytes(s.as_bytes()),
+            _ => SbiConsole.write_bytes(s.as_bytes()),
+        }
         Ok(())
     }
 }
 
+/// Print diagnostics from places the regular path may fail in, the panic handler above all.
+///
+/// It takes no lock, allocates nothing and goes around the logger and the virtio console,
+/// whose driver state a panic may have left borrowed, straight to a polled device. The text
+/// also goes to the kernel log kept across warm reboots, see `pstore`, so it survives even
+/// when the console does not.
+pub fn emergency_print(args: fmt::Arguments) {
+    let _ = EmergencyStdout.write_fmt(args);
+    crate::pstore::log(args);
+}
+
 pub fn print(args: fmt::Arguments) {
-    Stdout.write_fmt(args).unwrap();
+    with_console_lock(|| Stdout.write_fmt(args).unwrap());
+}
+
+/// Print a kernel log line, to the backend of the `log=` boot parameter.
+pub fn log_print(args: fmt::Arguments) {
+    with_console_lock(|| LogStdout.write_fmt(args).unwrap());
+}
+
+/// Write raw bytes to the console in one go.
+pub fn write_bytes(bytes: &[u8]) {
+    with_console_lock(|| backend().write_bytes(bytes));
+}
+
+/// Per-task buffer of console output, written out a whole line at a time so output of
+/// concurrent tasks interleaves by lines, not by characters.
+///
+/// The buffer is flushed on newline, when it reaches `LINE_BUFFER_SIZE`, before the task
+/// writes to stderr and when the task exits.
+#[derive(Default)]
+pub struct LineBuffer {
+    buf: Vec<u8>,
+}
+
+impl LineBuffer {
+    /// Buffer `bytes`, writing out every line completed by them.
+    pub fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.buf.push(byte);
+            if byte == b'\n' || self.buf.len() >= LINE_BUFFER_SIZE {
+                self.flush();
+            }
+        }
+    }
+
+    /// Write out the buffered partial line.
+    pub fn flush(&mut self) {
+        if !self.buf.is_empty() {
+            write_bytes(&self.buf);
+            self.buf.clear();
+        }
+    }
 }
 
 #[macro_export]
@@ -31,3 +239,11 @@ macro_rules! println {
         $crate::console::print(format_args!(concat!($fmt, "\n") $(, $($arg)+)?))
     }
 }
+
+/// `println!` through `emergency_print`, for the panic handler and what it calls.
+#[macro_export]
+macro_rules! emergency_println {
+    ($fmt: literal $(, $($arg: tt)+)?) => {
+        $crate::console::emergency_print(format_args!(concat!($fmt, "\n") $(, $($arg)+)?))
+    }
+}
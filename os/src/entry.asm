@@ -3,8 +3,39 @@
   .section .text.entry
   .globl _start
 _start:
-  la sp, boot_stack_top
-  call rust_main
+  # still running at the physical load address: turn on paging with boot_page_table,
+  # which maps RAM both where we are and at its direct map address in the upper half
+  lla t0, boot_page_table
+  srli t0, t0, 12
+  li t1, 8 << 60 # SV39
+  or t0, t0, t1
+  csrw satp, t0
+  sfence.vma
+  # continue in the upper half, at the link addresses
+  ld sp, boot_stack_top_addr
+  ld t0, rust_main_addr
+  jr t0
+
+  .section .rodata
+  .align 3
+boot_stack_top_addr:
+  .quad boot_stack_top
+rust_main_addr:
+  .quad rust_main
+
+  # used until `mm::init_late` switches to KERNEL_SPACE; 1 GiB pages, V|R|W|X|A|D
+  .section .data
+  .align 12
+boot_page_table:
+  # 0x0000_0000 -> 0x0000_0000, MMIO (e.g. the finisher of an early panic)
+  .quad (0x00000 << 10) | 0xcf
+  .quad 0
+  # 0x8000_0000 -> 0x8000_0000, RAM at its physical address for the jump above
+  .quad (0x80000 << 10) | 0xcf
+  .zero 8 * 255
+  # 0xffff_ffc0_8000_0000 -> 0x8000_0000, RAM in the direct map (PHYS_MEM_OFFSET)
+  .quad (0x80000 << 10) | 0xcf
+  .zero 8 * 253
 
   .section .bss.stack
   .globl boot_stack_lower_bound
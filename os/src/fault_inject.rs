@@ -0,0 +1,60 @@
+//! Fault injection, built with the `fault_injection` feature.
+//!
+//! A task picks failure probabilities for frame and heap allocations with `sys_fault_inject`.
+//! They only apply while the kernel services that task's syscalls, up to the first task
+//! switch, so boot, trap handling and other tasks allocate as usual. Failed frame allocations
+//! surface as `None` from `frame_alloc`, failed heap allocations as an error from fallible
+//! ones like `Vec::try_reserve` or end in the alloc error handler; both exercise the error
+//! and oops paths instead of only the happy path.
+//!
+//! Heap allocations only fail while no `UPSafeCell` is borrowed. With one borrowed the alloc
+//! error handler's panic cannot be recovered from, see `oops`, and would halt the kernel.
+
+use crate::random;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// Task the probabilities apply to, `usize::MAX` for none.
+static TARGET_TASK: AtomicUsize = AtomicUsize::new(usize::MAX);
+static FRAME_FAIL_PERMILLE: AtomicUsize = AtomicUsize::new(0);
+static HEAP_FAIL_PERMILLE: AtomicUsize = AtomicUsize::new(0);
+/// Whether a syscall of the target task is being serviced.
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Fail allocations in the syscalls of task `task_id` with the given probabilities.
+pub fn configure(task_id: usize, frame_permille: usize, heap_permille: usize) {
+    let enabled = frame_permille > 0 || heap_permille > 0;
+    TARGET_TASK.store(
+        if enabled { task_id } else { usize::MAX },
+        Ordering::Relaxed,
+    );
+    FRAME_FAIL_PERMILLE.store(frame_permille, Ordering::Relaxed);
+    HEAP_FAIL_PERMILLE.store(heap_permille, Ordering::Relaxed);
+}
+
+/// Called on syscall entry of task `task_id`.
+pub fn enter(task_id: usize) {
+    ACTIVE.store(
+        TARGET_TASK.load(Ordering::Relaxed) == task_id,
+        Ordering::Relaxed,
+    );
+}
+
+/// Called on syscall return and task switch.
+pub fn exit() {
+    ACTIVE.store(false, Ordering::Relaxed);
+}
+
+fn roll(permille: &AtomicUsize) -> bool {
+    ACTIVE.load(Ordering::Relaxed)
+        && (random::next_u64() % 1000) < permille.load(Ordering::Relaxed) as u64
+}
+
+/// Returns `true` if the frame allocation about to happen has to fail.
+pub fn should_fail_frame() -> bool {
+    roll(&FRAME_FAIL_PERMILLE)
+}
+
+/// Returns `true` if the heap allocation about to happen has to fail.
+pub fn should_fail_heap() -> bool {
+    !crate::sync::any_borrowed() && roll(&HEAP_FAIL_PERMILLE)
+}
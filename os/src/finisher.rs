@@ -0,0 +1,38 @@
+//! Exit QEMU with an exit status through the `virt` machine's sifive_test device.
+//!
+//! SBI system reset only tells success from failure, but scripted runs want to know more,
+//! e.g. how many user apps failed. Writing `FINISHER_FAIL | code << 16` to the device makes
+//! QEMU exit with status `code`, writing `FINISHER_PASS` with status 0. Writing
+//! `FINISHER_RESET` resets the machine instead.
+
+use crate::board::VIRT_TEST;
+use crate::sbi::{shutdown, warm_reboot};
+
+const FINISHER_PASS: u32 = 0x5555;
+const FINISHER_FAIL: u32 = 0x3333;
+const FINISHER_RESET: u32 = 0x7777;
+
+/// Exit status of a kernel panic.
+pub const EXIT_PANIC: u16 = 255;
+
+/// Power off the machine, QEMU exits with status `code`.
+///
+/// Works with or without paging, the device is identity-mapped in the kernel space.
+pub fn exit(code: u16) -> ! {
+    let value = if code == 0 {
+        FINISHER_PASS
+    } else {
+        FINISHER_FAIL | (code as u32) << 16
+    };
+    unsafe { (VIRT_TEST as *mut u32).write_volatile(value) };
+    // no test finisher on this machine, at least tell success from failure
+    shutdown(code != 0)
+}
+
+/// Reset the machine without clearing RAM, through the SBI or else the test device.
+pub fn reboot() -> ! {
+    warm_reboot();
+    unsafe { (VIRT_TEST as *mut u32).write_volatile(FINISHER_RESET) };
+    // neither can reset, leave it to whoever watches the machine
+    shutdown(true)
+}
@@ -0,0 +1,98 @@
+//! kcov-style coverage collection, built with the `kcov` feature.
+//!
+//! `make FEATURES=kcov` compiles the kernel crate with LLVM's sanitizer coverage in trace-pc
+//! mode, so every basic block calls `__sanitizer_cov_trace_pc`. The hook appends its return
+//! address to the active area, which belongs to the task whose syscall is being serviced. A
+//! fuzzer enables coverage for itself, issues syscalls and collects the pcs they reached.
+//!
+//! The hook is written in assembly so it is not instrumented itself. Coverage is only active
+//! between syscall entry and return; a syscall that yields loses the rest of its coverage.
+
+use crate::sync::UPSafeCell;
+use alloc::collections::btree_map::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::arch::global_asm;
+use lazy_static::*;
+
+/// The active area: a pointer to `[count, pc...]` and its pc capacity, or `[0, 0]`.
+static mut KCOV_AREA: [usize; 2] = [0, 0];
+
+global_asm!(
+    r#"
+    .section .text
+    .globl __sanitizer_cov_trace_pc
+__sanitizer_cov_trace_pc:
+    la t0, {area}
+    ld t1, 0(t0)        # area
+    beqz t1, 1f
+    ld t2, 8(t0)        # capacity
+    ld t3, 0(t1)        # count
+    bgeu t3, t2, 1f
+    addi t3, t3, 1
+    sd t3, 0(t1)
+    slli t3, t3, 3
+    add t3, t3, t1
+    sd ra, 0(t3)        # area[count] = caller
+1:
+    ret
+"#,
+    area = sym KCOV_AREA,
+);
+
+lazy_static! {
+    /// Coverage areas keyed by task id, `area[0]` is the number of recorded pcs.
+    static ref AREAS: UPSafeCell<BTreeMap<usize, Vec<usize>>> =
+        unsafe { UPSafeCell::new(BTreeMap::new()) };
+}
+
+fn set_active(area: *mut usize, capacity: usize) {
+    unsafe {
+        let active = &raw mut KCOV_AREA;
+        (*active)[0] = 0;
+        (*active)[1] = capacity;
+        (*active)[0] = area as usize;
+    }
+}
+
+/// Give task `task_id` an area of `capacity` pcs and start recording into it.
+pub fn enable(task_id: usize, capacity: usize) {
+    exit();
+    AREAS
+        .exclusive_access()
+        .insert(task_id, vec![0; capacity + 1]);
+    enter(task_id);
+}
+
+/// Stop recording for task `task_id` and free its area.
+pub fn disable(task_id: usize) {
+    exit();
+    AREAS.exclusive_access().remove(&task_id);
+}
+
+/// Record into the area of task `task_id`, if it has one, until `exit`.
+pub fn enter(task_id: usize) {
+    let mut areas = AREAS.exclusive_access();
+    if let Some(area) = areas.get_mut(&task_id) {
+        set_active(area.as_mut_ptr(), area.len() - 1);
+    }
+}
+
+/// Stop recording.
+pub fn exit() {
+    set_active(core::ptr::null_mut(), 0);
+}
+
+/// Take the pcs recorded for task `task_id` so far and reset its area.
+pub fn collect(task_id: usize) -> Vec<usize> {
+    exit();
+    let pcs = match AREAS.exclusive_access().get_mut(&task_id) {
+        Some(area) => {
+            let count = core::mem::take(&mut area[0]);
+            area[1..=count].to_vec()
+        }
+        None => Vec::new(),
+    };
+    enter(task_id);
+    pcs
+}
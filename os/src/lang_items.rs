@@ -1,18 +1,31 @@
-use crate::sbi::shutdown;
+use crate::boot::{self, BootStage};
+use crate::finisher::{self, EXIT_PANIC};
+use crate::oops;
+use crate::stack_trace::print_stack_trace;
 use core::panic::PanicInfo;
-use log::*;
 
+/// Report the panic and kill the task or halt, see `oops`.
+///
+/// Everything is printed through `emergency_println!`, so the report gets out even if the
+/// panic interrupted the console, the logger or the memory a print would allocate from.
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
-    if let Some(location) = info.location() {
-        error!(
+    if !boot::reached(BootStage::Logging) {
+        emergency_println!("[kernel] Panicked during early boot: {}", info);
+    } else if let Some(location) = info.location() {
+        emergency_println!(
             "[kernel] Panicked at {}:{} {}",
             location.file(),
             location.line(),
             info.message()
         );
     } else {
-        error!("[kernel] Panicked: {}", info.message());
+        emergency_println!("[kernel] Panicked: {}", info.message());
     }
-    shutdown(true)
+    unsafe { print_stack_trace() };
+    #[cfg(test)]
+    emergency_println!("[kernel] test failed");
+    #[cfg(not(test))]
+    oops::try_recover();
+    finisher::exit(EXIT_PANIC)
 }
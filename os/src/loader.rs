@@ -1,3 +1,11 @@
+use crate::crc32::crc32;
+use crate::mm::LoadError;
+use log::error;
+
+/// Name suffix of flat (non-ELF) apps, raw binaries assembled from `user/src/flat`, see
+/// `MemorySet::from_flat`.
+pub const FLAT_APP_SUFFIX: &str = ".flat";
+
 /// Returns the number of applications to load.
 ///
 /// This function reads the number of applications from a symbol provided by the linker.
@@ -44,3 +52,71 @@ pub fn get_app_data(app_id: usize) -> &'static [u8] {
         )
     }
 }
+
+/// Returns the name of the application with the given app ID, its file name in `user/src/bin`,
+/// or in `user/src/flat` with `FLAT_APP_SUFFIX` instead of `.S`.
+///
+/// # Panics
+/// Panics if `app_id` is out of bounds.
+pub fn get_app_name(app_id: usize) -> &'static str {
+    // SAFETY: `_app_names` is generated by build.rs: the NUL terminated names of the apps,
+    // back to back in app order.
+    unsafe extern "C" {
+        fn _app_names();
+    }
+
+    assert!(app_id < get_num_app());
+    let mut name = _app_names as usize as *const core::ffi::c_char;
+    for _ in 0..app_id {
+        name = unsafe { name.add(core::ffi::CStr::from_ptr(name).count_bytes() + 1) };
+    }
+    unsafe { core::ffi::CStr::from_ptr(name) }
+        .to_str()
+        .unwrap_or("?")
+}
+
+/// Returns the CRC-32 of the application with the given app ID, taken by build.rs.
+pub fn get_app_crc(app_id: usize) -> u32 {
+    // SAFETY: `_app_crcs` is generated by build.rs: one aligned `u32` per app, in app order.
+    unsafe extern "C" {
+        fn _app_crcs();
+    }
+
+    assert!(app_id < get_num_app());
+    unsafe {
+        (_app_crcs as usize as *const u32)
+            .add(app_id)
+            .read_volatile()
+    }
+}
+
+/// Returns the application data for the given app ID after checking it against its CRC-32.
+///
+/// # Errors
+/// `LoadError::Corrupted` if the image changed since the kernel image was built.
+pub fn get_verified_app_data(app_id: usize) -> Result<&'static [u8], LoadError> {
+    let data = get_app_data(app_id);
+    let crc = crc32(data);
+    if crc != get_app_crc(app_id) {
+        error!(
+            "[kernel] app '{}' has CRC-32 {:#010x}, expected {:#010x}",
+            get_app_name(app_id),
+            crc,
+            get_app_crc(app_id)
+        );
+        return Err(LoadError::Corrupted);
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn embedded_apps_match_their_checksums() {
+        for app_id in 0..get_num_app() {
+            assert!(get_verified_app_data(app_id).is_ok());
+        }
+    }
+}
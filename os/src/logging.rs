@@ -28,12 +28,13 @@ impl Log for SimpleLogger {
             Level::Trace => "TRC", // BrightBlack
         };
 
-        println!(
-            "\u{1B}[{}m[{}] {}\u{1B}[0m",
+        crate::console::log_print(format_args!(
+            "\u{1B}[{}m[{}] {}\u{1B}[0m\n",
             color,
             display_level,
             record.args()
-        );
+        ));
+        crate::pstore::log(format_args!("[{}] {}\n", display_level, record.args()));
     }
 
     fn flush(&self) {}
@@ -1,6 +1,9 @@
 #![no_std]
 #![no_main]
 #![feature(alloc_error_handler)]
+#![cfg_attr(test, feature(custom_test_frameworks))]
+#![cfg_attr(test, test_runner(crate::testing::run_tests))]
+#![cfg_attr(test, reexport_test_harness_main = "test_main")]
 #![allow(unused)]
 
 extern crate alloc;
@@ -11,27 +14,57 @@ extern crate bitflags;
 use alloc::fmt::format;
 use alloc::string::String;
 use alloc::vec::{self, Vec};
+use boot::BootStage;
 use log::*;
 
 #[path = "boards/qemu.rs"]
 mod board;
 
+#[cfg(feature = "acct")]
+mod acct;
+mod boot;
 #[macro_use]
 mod console;
+#[macro_use]
+mod ratelimit;
 mod config;
+mod crc32;
+#[cfg(feature = "fault_injection")]
+mod fault_inject;
+mod finisher;
+#[cfg(feature = "kcov")]
+mod kcov;
 mod lang_items;
 mod loader;
 mod logging;
 mod mm;
+mod oops;
+#[cfg(feature = "profiler")]
+mod profiler;
+mod pstore;
+mod random;
+mod rtc;
 mod sbi;
+mod stack_trace;
 mod sync;
 pub mod syscall;
 pub mod task;
+mod tasklet;
+#[cfg(test)]
+mod testing;
 mod timer;
+mod timer_queue;
+#[cfg(feature = "sched_trace")]
+mod trace;
 pub mod trap;
+mod uart;
+mod version;
+mod virtio_console;
+mod watchdog;
 
 core::arch::global_asm!(include_str!("entry.asm"));
 core::arch::global_asm!(include_str!("link_app.S"));
+core::arch::global_asm!(include_str!("ksyms.S"));
 
 unsafe extern "C" {
     pub(crate) safe fn stext();
@@ -60,11 +93,20 @@ fn clear_bss() {
 pub fn rust_main() -> ! {
     clear_bss();
     logging::init();
-    info!("[kernel] Hello, world!");
-    mm::init();
+    boot::set_stage(BootStage::Logging);
+    version::print_banner();
+    mm::init_early();
+    mm::init_late();
+    boot::set_stage(BootStage::Memory);
+    console::init();
+    pstore::init();
+    timer::init_realtime();
+    #[cfg(test)]
+    test_main();
     info!("[kernel] back to world!");
     trap::init();
     trap::enable_timer_interrupt();
+    boot::set_stage(BootStage::Tasks);
     task::run_first_task();
     panic!("Unreachable in rust_main!");
 }
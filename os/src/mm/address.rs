@@ -1,5 +1,5 @@
 use super::PageTableEntry;
-use crate::config::{PAGE_OFFSET_BITS, PAGE_SIZE};
+use crate::config::{PAGE_OFFSET_BITS, PAGE_SIZE, PHYS_MEM_OFFSET};
 use core::fmt::{self, Debug, Formatter};
 
 /// Physical address width for SV39 (in bits)
@@ -69,6 +69,24 @@ impl From<usize> for VirtPageNum {
     }
 }
 
+/// Returns the kernel virtual address through which physical address `pa` is reached.
+///
+/// All of physical memory is mapped into the kernel at `PHYS_MEM_OFFSET` (the direct map);
+/// every kernel access to an arbitrary frame must go through this instead of using the
+/// physical address as a pointer.
+pub fn phys_to_virt(pa: PhysAddr) -> VirtAddr {
+    VirtAddr(pa.0 + PHYS_MEM_OFFSET)
+}
+
+/// Returns the physical address behind kernel virtual address `va` of the direct map, the
+/// inverse of `phys_to_virt`.
+///
+/// The kernel image lives in the direct map, so this also works for kernel symbols. `va` may
+/// be sign extended or truncated to SV39 (as `VirtAddr::from` does).
+pub fn virt_to_phys(va: VirtAddr) -> PhysAddr {
+    PhysAddr(va.0.wrapping_sub(PHYS_MEM_OFFSET) & ((1 << VA_WIDTH_SV39) - 1))
+}
+
 impl VirtAddr {
     pub fn bits(&self) -> usize {
         self.0
@@ -143,6 +161,22 @@ impl PhysAddr {
     pub fn aligned(&self) -> bool {
         self.page_offset() == 0
     }
+
+    /// Returns a reference to a value of type `T` at this address.
+    ///
+    /// # Safety
+    /// The caller must ensure the type and alignment are correct.
+    pub fn get_ref<T>(&self) -> &'static T {
+        unsafe { (phys_to_virt(*self).0 as *const T).as_ref().unwrap() }
+    }
+
+    /// Returns a mutable reference to a value of type `T` at this address.
+    ///
+    /// # Safety
+    /// The caller must ensure the type and alignment are correct.
+    pub fn get_mut<T>(&self) -> &'static mut T {
+        unsafe { (phys_to_virt(*self).0 as *mut T).as_mut().unwrap() }
+    }
 }
 
 impl PhysPageNum {
@@ -161,14 +195,14 @@ impl PhysPageNum {
 
     /// Returns a mutable byte slice representing the page's memory.
     pub fn get_bytes_array_mut(&self) -> &'static mut [u8] {
-        let pa: PhysAddr = self.get_first_addr();
-        unsafe { core::slice::from_raw_parts_mut(pa.0 as *mut u8, PAGE_SIZE) }
+        let va = phys_to_virt(self.get_first_addr());
+        unsafe { core::slice::from_raw_parts_mut(va.0 as *mut u8, PAGE_SIZE) }
     }
 
     /// Returns an immutable byte slice representing the page's memory.
     pub fn get_bytes_array(&self) -> &'static [u8] {
-        let pa: PhysAddr = self.get_first_addr();
-        unsafe { core::slice::from_raw_parts(pa.0 as *mut u8, PAGE_SIZE) }
+        let va = phys_to_virt(self.get_first_addr());
+        unsafe { core::slice::from_raw_parts(va.0 as *const u8, PAGE_SIZE) }
     }
 
     /// Returns a mutable slice of page table entries for this page.
@@ -176,11 +210,11 @@ impl PhysPageNum {
     /// # Safety
     /// The page must be used as a page table.
     pub fn get_pte_array_mut(&self) -> &'static mut [PageTableEntry] {
-        let pa: PhysAddr = self.get_first_addr();
+        let va = phys_to_virt(self.get_first_addr());
         // PAGE_SIZE / sizeof(PageTableEntry) = 512; one page can store 512 PTEs.
         unsafe {
             core::slice::from_raw_parts_mut(
-                pa.0 as *mut PageTableEntry,
+                va.0 as *mut PageTableEntry,
                 PAGE_SIZE / size_of::<PageTableEntry>(),
             )
         }
@@ -191,7 +225,58 @@ impl PhysPageNum {
     /// # Safety
     /// The caller must ensure the type and alignment are correct.
     pub fn get_mut<T>(&self) -> &'static mut T {
-        let pa: PhysAddr = (*self).get_first_addr();
-        unsafe { (pa.0 as *mut T).as_mut().unwrap() }
+        self.get_first_addr().get_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn virt_addr_page_rounding() {
+        let va = VirtAddr::from(0x1234_5678);
+        assert_eq!(va.floor(), VirtPageNum(0x12345));
+        assert_eq!(va.ceil(), VirtPageNum(0x12346));
+        assert_eq!(va.page_offset(), 0x678);
+        assert!(!va.aligned());
+
+        let aligned = va.floor().get_first_addr();
+        assert_eq!(aligned, VirtAddr(0x1234_5000));
+        assert!(aligned.aligned());
+        assert_eq!(aligned.floor(), aligned.ceil());
+    }
+
+    #[test_case]
+    fn addresses_are_truncated_to_sv39() {
+        assert_eq!(VirtAddr::from(usize::MAX).0, (1 << VA_WIDTH_SV39) - 1);
+        assert_eq!(PhysAddr::from(usize::MAX).0, (1 << PA_WIDTH_SV39) - 1);
+        assert_eq!(VirtPageNum::from(usize::MAX).0, (1 << VPN_WIDTH_SV39) - 1);
+        assert_eq!(PhysPageNum::from(usize::MAX).0, (1 << PPN_WIDTH_SV39) - 1);
+    }
+
+    #[test_case]
+    fn phys_addr_page_rounding() {
+        let pa = PhysAddr::from(0x8020_0001);
+        assert_eq!(pa.floor(), PhysPageNum(0x80200));
+        assert_eq!(pa.ceil(), PhysPageNum(0x80201));
+        assert_eq!(pa.floor().get_first_addr(), PhysAddr(0x8020_0000));
+    }
+
+    #[test_case]
+    fn direct_map_round_trip() {
+        let pa = PhysAddr(0x8040_1234);
+        assert_eq!(phys_to_virt(pa).0, 0x8040_1234 + PHYS_MEM_OFFSET);
+        assert_eq!(virt_to_phys(phys_to_virt(pa)), pa);
+        assert_eq!(phys_to_virt(pa).page_offset(), pa.page_offset());
+        // page table code sees virtual addresses truncated to SV39
+        assert_eq!(virt_to_phys(VirtAddr::from(phys_to_virt(pa).0)), pa);
+    }
+
+    #[test_case]
+    fn vpn_indexes() {
+        let vpn = VirtPageNum((3 << 18) | (2 << 9) | 1);
+        assert_eq!(vpn.indexes(), [3, 2, 1]);
+        assert_eq!(VirtPageNum::from(usize::MAX).indexes(), [511, 511, 511]);
     }
 }
@@ -1,10 +1,11 @@
 /// Frame allocator module for managing physical memory frames.
 use super::address::PhysPageNum;
-use crate::board::MEMORY_END;
+use crate::board::{MEMORY_END, RESERVED_MEMORY};
 use crate::config::PAGE_SIZE;
-use crate::mm::address::PhysAddr;
+use crate::mm::address::{PhysAddr, VirtAddr, virt_to_phys};
 use crate::sync::UPSafeCell;
 use crate::*;
+use alloc::vec;
 use alloc::vec::Vec;
 use core::fmt::{self, Debug, Formatter};
 use lazy_static::*;
@@ -12,17 +13,25 @@ use lazy_static::*;
 /// Initialize the global frame allocator.
 ///
 /// This function sets up the frame allocator to manage all physical memory frames
-/// between the end of the kernel and the end of physical memory.
+/// between the end of the kernel and the end of physical memory, except the board's
+/// `RESERVED_MEMORY` and, with the `memtest` feature, frames failing the memory test.
 pub fn init_frame_allocator() {
-    let start_pa = PhysAddr::from(ekernel as usize);
-    let end_pa = PhysAddr::from(MEMORY_END);
-
     // NOTE: frame allocator init by PPN not PA
     // Use ceil/floor to convert addr to corresponding page number
-    FRAME_ALLOCATOR.exclusive_access().init(
-        PhysAddr::from(ekernel as usize).ceil(),
-        PhysAddr::from(MEMORY_END).floor(),
-    );
+    let start = virt_to_phys(VirtAddr(ekernel as usize)).ceil();
+    let end = PhysAddr::from(MEMORY_END).floor();
+    let mut allocator = FRAME_ALLOCATOR.exclusive_access();
+    allocator.init(start, end);
+    for &(reserved_start, reserved_end) in RESERVED_MEMORY {
+        allocator.reserve(
+            PhysAddr::from(reserved_start).floor(),
+            PhysAddr::from(reserved_end).ceil(),
+        );
+    }
+    #[cfg(feature = "memtest")]
+    for bad in super::memtest::bad_frames(start, end) {
+        allocator.reserve(bad, PhysPageNum(bad.0 + 1));
+    }
 }
 
 /// Allocate a physical frame and return a `FrameTracker` if successful.
@@ -31,6 +40,10 @@ pub fn init_frame_allocator() {
 /// - `Some(FrameTracker)` if a frame is available.
 /// - `None` if no frames are available.
 pub fn frame_alloc() -> Option<FrameTracker> {
+    #[cfg(feature = "fault_injection")]
+    if crate::fault_inject::should_fail_frame() {
+        return None;
+    }
     FRAME_ALLOCATOR
         .exclusive_access()
         .alloc()
@@ -45,9 +58,32 @@ pub fn frame_dealloc(ppn: PhysPageNum) {
     FRAME_ALLOCATOR.exclusive_access().dealloc(ppn);
 }
 
+/// Returns `true` if the frame allocator is borrowed, e.g. by code a panic interrupted.
+pub fn is_busy() -> bool {
+    FRAME_ALLOCATOR.is_borrowed()
+}
+
+/// Returns the number of managed frames and how many of them are free.
+pub fn frame_stats() -> (usize, usize) {
+    let allocator = FRAME_ALLOCATOR.exclusive_access();
+    (allocator.total_frames(), allocator.free_frames())
+}
+
+/// Drop one reference to a physical frame, deallocating it with the last one.
+///
+/// # Arguments
+/// - `ppn`: The physical page number to release.
+pub fn frame_release(ppn: PhysPageNum) {
+    let mut allocator = FRAME_ALLOCATOR.exclusive_access();
+    if allocator.dec_ref(ppn) == 0 {
+        allocator.dealloc(ppn);
+    }
+}
+
 /// Tracks the allocation of a physical frame.
 ///
-/// When dropped, the frame is automatically deallocated.
+/// When dropped, the frame is automatically deallocated. A `FrameTracker` is the only
+/// owner of its frame; convert it into a `SharedFrame` to share the frame.
 pub struct FrameTracker {
     /// The physical page number of the tracked frame.
     pub ppn: PhysPageNum,
@@ -67,7 +103,7 @@ impl FrameTracker {
 impl Drop for FrameTracker {
     /// Automatically deallocate the frame when the tracker is dropped.
     fn drop(&mut self) {
-        frame_dealloc(self.ppn)
+        frame_release(self.ppn)
     }
 }
 
@@ -77,6 +113,54 @@ impl Debug for FrameTracker {
     }
 }
 
+/// A reference-counted handle to a physical frame, what `Arc` is to `FrameTracker`'s `Box`.
+///
+/// Cloning takes another reference to the same frame, which is deallocated when the last
+/// handle is dropped. Used for frames mapped in several places (zero page, COW, dedup).
+pub struct SharedFrame {
+    ppn: PhysPageNum,
+}
+
+impl SharedFrame {
+    /// Returns the physical page number of the shared frame.
+    pub fn ppn(&self) -> PhysPageNum {
+        self.ppn
+    }
+
+    /// Returns the number of handles referring to this frame.
+    pub fn ref_count(&self) -> usize {
+        FRAME_ALLOCATOR.exclusive_access().ref_count(self.ppn)
+    }
+}
+
+impl From<FrameTracker> for SharedFrame {
+    /// Take over the reference held by `frame`.
+    fn from(frame: FrameTracker) -> Self {
+        let ppn = frame.ppn;
+        core::mem::forget(frame);
+        Self { ppn }
+    }
+}
+
+impl Clone for SharedFrame {
+    fn clone(&self) -> Self {
+        FRAME_ALLOCATOR.exclusive_access().inc_ref(self.ppn);
+        Self { ppn: self.ppn }
+    }
+}
+
+impl Drop for SharedFrame {
+    fn drop(&mut self) {
+        frame_release(self.ppn)
+    }
+}
+
+impl Debug for SharedFrame {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_fmt(format_args!("SharedFrame:PPN={:#x}", self.ppn.0))
+    }
+}
+
 lazy_static! {
     /// Global frame allocator instance, protected by a lock.
     static ref FRAME_ALLOCATOR: UPSafeCell<StackFrameAllocator> =
@@ -95,12 +179,18 @@ pub trait FrameAllocator {
 
 /// Stack-based frame allocator implementation.
 pub struct StackFrameAllocator {
+    /// Start of the managed physical page range.
+    start: usize,
     /// Next free physical page number.
     current: usize,
     /// End of the managed physical page range (exclusive).
     end: usize,
     /// Stack of recycled (freed) physical page numbers.
     recycled: Vec<usize>, // store recycled ppn
+    /// Reference count of every managed frame, indexed by `ppn - start`.
+    refcounts: Vec<u16>,
+    /// Sorted, disjoint ranges `[start, end)` of physical page numbers never handed out.
+    reserved: Vec<(usize, usize)>,
 }
 
 impl StackFrameAllocator {
@@ -110,42 +200,128 @@ impl StackFrameAllocator {
     /// - `start`: The first physical page number to manage.
     /// - `end`: The last physical page number to manage (exclusive).
     pub fn init(&mut self, start: PhysPageNum, end: PhysPageNum) {
+        self.start = start.0;
         self.current = start.0;
         self.end = end.0;
+        self.refcounts = vec![0; end.0 - start.0];
+        self.reserved.clear();
+    }
+
+    /// Never hand out the frames `[start, end)`, must be called before any allocation.
+    pub fn reserve(&mut self, start: PhysPageNum, end: PhysPageNum) {
+        debug_assert_eq!(self.current, self.start, "frames reserved after allocation");
+        let start = start.0.max(self.start);
+        let end = end.0.min(self.end);
+        if start >= end {
+            return;
+        }
+        // merge with every range it overlaps or touches
+        let (mut start, mut end) = (start, end);
+        self.reserved.retain(|&(s, e)| {
+            let apart = e < start || end < s;
+            if !apart {
+                start = start.min(s);
+                end = end.max(e);
+            }
+            apart
+        });
+        let pos = self.reserved.partition_point(|&(s, _)| s < start);
+        self.reserved.insert(pos, (start, end));
+    }
+
+    /// Returns `true` if `ppn` is reserved.
+    fn is_reserved(&self, ppn: usize) -> bool {
+        self.reserved.iter().any(|&(s, e)| s <= ppn && ppn < e)
+    }
+
+    /// Number of reserved frames at or above `ppn`.
+    fn reserved_from(&self, ppn: usize) -> usize {
+        self.reserved
+            .iter()
+            .map(|&(s, e)| e.saturating_sub(s.max(ppn)))
+            .sum()
+    }
+
+    /// Returns the number of managed frames.
+    pub fn total_frames(&self) -> usize {
+        self.end - self.start - self.reserved_from(self.start)
+    }
+
+    /// Returns the number of frames that can still be allocated.
+    pub fn free_frames(&self) -> usize {
+        self.end - self.current - self.reserved_from(self.current) + self.recycled.len()
+    }
+
+    /// Returns the reference count of an allocated frame.
+    pub fn ref_count(&self, ppn: PhysPageNum) -> usize {
+        self.refcounts[ppn.0 - self.start] as usize
+    }
+
+    /// Take one more reference to an allocated frame.
+    pub fn inc_ref(&mut self, ppn: PhysPageNum) {
+        let count = &mut self.refcounts[ppn.0 - self.start];
+        debug_assert!(*count > 0, "frame ppn={:#x} shared after release", ppn.0);
+        *count += 1;
+    }
+
+    /// Drop one reference to an allocated frame and return the remaining count.
+    pub fn dec_ref(&mut self, ppn: PhysPageNum) -> usize {
+        let count = &mut self.refcounts[ppn.0 - self.start];
+        debug_assert!(*count > 0, "frame ppn={:#x} released twice", ppn.0);
+        *count -= 1;
+        *count as usize
     }
 }
 
 impl FrameAllocator for StackFrameAllocator {
     fn new() -> Self {
         Self {
+            start: 0,
             current: 0,
             end: 0,
             recycled: Vec::new(),
+            refcounts: Vec::new(),
+            reserved: Vec::new(),
         }
     }
 
     fn alloc(&mut self) -> Option<PhysPageNum> {
-        if let Some(ppn) = self.recycled.pop() {
-            Some(ppn.into())
+        // step over reserved frames, the ranges are sorted
+        for &(s, e) in self.reserved.iter() {
+            if s <= self.current && self.current < e {
+                self.current = e;
+            }
+        }
+        let ppn = if let Some(ppn) = self.recycled.pop() {
+            ppn
         } else if self.current == self.end {
             log::warn!(
                 "Frame allocator out of memory! current={:#x}, end={:#x}",
                 self.current,
                 self.end
             );
-            None
+            return None;
         } else {
-            let ppn = self.current;
             self.current += 1;
-            Some((self.current - 1).into())
-        }
+            self.current - 1
+        };
+
+        let count = &mut self.refcounts[ppn - self.start];
+        debug_assert_eq!(*count, 0, "frame ppn={ppn:#x} allocated while in use");
+        *count = 1;
+        Some(ppn.into())
     }
 
     fn dealloc(&mut self, ppn: PhysPageNum) {
         let ppn = ppn.0;
-        if ppn >= self.current || self.recycled.contains(&ppn) {
-            panic!("Frame ppn={:#x} has not been has not been allocated!", ppn);
+        if ppn >= self.current || self.is_reserved(ppn) || self.recycled.contains(&ppn) {
+            panic!("Frame ppn={ppn:#x} has not been has not been allocated!");
         }
+        debug_assert_eq!(
+            self.refcounts[ppn - self.start],
+            0,
+            "frame ppn={ppn:#x} deallocated while still referenced"
+        );
 
         // recycle
         self.recycled.push(ppn);
@@ -170,5 +346,74 @@ pub fn frame_allocator_test() {
         v.push(frame);
     }
     drop(v);
+
+    let shared = SharedFrame::from(frame_alloc().unwrap());
+    let ppn = shared.ppn();
+    let other = shared.clone();
+    assert_eq!(shared.ref_count(), 2);
+    drop(shared);
+    assert_eq!(other.ref_count(), 1);
+    drop(other);
+    // the frame went back to the allocator with its last handle
+    assert_eq!(frame_alloc().unwrap().ppn, ppn);
     println!("frame_allocator_test passed!");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn exhaust_and_refill() {
+        let (total, free) = frame_stats();
+        assert!(free <= total);
+        let mut frames = Vec::with_capacity(free);
+        while let Some(frame) = frame_alloc() {
+            frames.push(frame);
+        }
+        assert_eq!(frames.len(), free);
+        assert_eq!(frame_stats().1, 0);
+        // every frame is handed out once
+        frames.sort_by_key(|frame| frame.ppn);
+        assert!(frames.windows(2).all(|pair| pair[0].ppn != pair[1].ppn));
+
+        frames.truncate(free / 2);
+        assert_eq!(frame_stats().1, free - free / 2);
+        drop(frames);
+        assert_eq!(frame_stats().1, free);
+    }
+
+    #[test_case]
+    fn reserved_frames_are_skipped() {
+        let mut allocator = StackFrameAllocator::new();
+        allocator.init(PhysPageNum(100), PhysPageNum(110));
+        allocator.reserve(PhysPageNum(103), PhysPageNum(105));
+        allocator.reserve(PhysPageNum(102), PhysPageNum(104));
+        allocator.reserve(PhysPageNum(108), PhysPageNum(200));
+        allocator.reserve(PhysPageNum(0), PhysPageNum(100));
+        assert_eq!(allocator.reserved, [(102, 105), (108, 110)]);
+        assert_eq!(allocator.total_frames(), 5);
+        assert_eq!(allocator.free_frames(), 5);
+
+        let mut ppns = Vec::new();
+        while let Some(ppn) = allocator.alloc() {
+            ppns.push(ppn.0);
+        }
+        assert_eq!(ppns, [100, 101, 105, 106, 107]);
+        assert_eq!(allocator.free_frames(), 0);
+        allocator.dec_ref(PhysPageNum(105));
+        allocator.dealloc(PhysPageNum(105));
+        assert_eq!(allocator.free_frames(), 1);
+    }
+
+    #[test_case]
+    fn frames_are_zeroed() {
+        let frame = frame_alloc().unwrap();
+        frame.ppn.get_bytes_array_mut().fill(0xa5);
+        let ppn = frame.ppn;
+        drop(frame);
+        let frame = frame_alloc().unwrap();
+        assert_eq!(frame.ppn, ppn);
+        assert!(frame.ppn.get_bytes_array().iter().all(|&b| b == 0));
+    }
+}
@@ -1,10 +1,70 @@
+use super::slab::{SLAB_SIZE, SlabAllocator, SlabStat};
 use crate::config::KERNEL_HEAP_SIZE;
 use crate::*;
 use buddy_system_allocator::LockedHeap;
+use core::alloc::{GlobalAlloc, Layout};
+use core::cell::UnsafeCell;
+use core::ptr::{NonNull, null_mut};
 
-#[global_allocator]
 static HEAP_ALLOCATOR: LockedHeap<32> = LockedHeap::<32>::empty();
 
+/// The slab caches, only touched with `HEAP_ALLOCATOR` locked, which guards both.
+struct Slabs(UnsafeCell<SlabAllocator>);
+
+unsafe impl Sync for Slabs {}
+
+static SLABS: Slabs = Slabs(UnsafeCell::new(SlabAllocator::new()));
+
+/// Small allocations from the slab caches, the others from the buddy heap, see `slab`.
+struct KernelHeap;
+
+#[cfg_attr(not(feature = "fault_injection"), global_allocator)]
+static KERNEL_HEAP: KernelHeap = KernelHeap;
+
+unsafe impl GlobalAlloc for KernelHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let mut heap = HEAP_ALLOCATOR.lock();
+        let Some(class) = SlabAllocator::class(layout) else {
+            return heap.alloc(layout).map_or(null_mut(), NonNull::as_ptr);
+        };
+        let slabs = unsafe { &mut *SLABS.0.get() };
+        slabs.alloc(class, || {
+            let slab = Layout::from_size_align(SLAB_SIZE, SLAB_SIZE).unwrap();
+            heap.alloc(slab).map_or(null_mut(), NonNull::as_ptr)
+        })
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let mut heap = HEAP_ALLOCATOR.lock();
+        match SlabAllocator::class(layout) {
+            Some(class) => unsafe { &mut *SLABS.0.get() }.dealloc(class, ptr),
+            None => heap.dealloc(NonNull::new(ptr).unwrap(), layout),
+        }
+    }
+}
+
+/// Forwards to `KERNEL_HEAP` unless fault injection decides the allocation fails.
+#[cfg(feature = "fault_injection")]
+struct FaultInjectingHeap;
+
+#[cfg(feature = "fault_injection")]
+#[global_allocator]
+static FAULT_INJECTING_HEAP: FaultInjectingHeap = FaultInjectingHeap;
+
+#[cfg(feature = "fault_injection")]
+unsafe impl GlobalAlloc for FaultInjectingHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if crate::fault_inject::should_fail_heap() {
+            return core::ptr::null_mut();
+        }
+        unsafe { KERNEL_HEAP.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { KERNEL_HEAP.dealloc(ptr, layout) }
+    }
+}
+
 static mut HEAP_SPACE: [u8; KERNEL_HEAP_SIZE] = [0; KERNEL_HEAP_SIZE];
 
 pub fn init_heap() {
@@ -15,9 +75,26 @@ pub fn init_heap() {
     }
 }
 
+/// Returns the size of the kernel heap and the bytes currently allocated from it.
+pub fn heap_stats() -> (usize, usize) {
+    let heap = HEAP_ALLOCATOR.lock();
+    (heap.stats_total_bytes(), heap.stats_alloc_actual())
+}
+
+/// Returns the statistics of the slab caches, smallest objects first.
+pub fn slab_stats() -> [SlabStat; super::slab::SIZE_CLASSES.len()] {
+    let _heap = HEAP_ALLOCATOR.lock();
+    unsafe { &*SLABS.0.get() }.stats()
+}
+
+/// Returns `true` if the heap is locked, e.g. by an allocation a panic interrupted.
+pub fn is_locked() -> bool {
+    HEAP_ALLOCATOR.is_locked()
+}
+
 // handler alloc error
 #[alloc_error_handler]
-pub fn handle_alloc_error(layout: core::alloc::Layout) -> ! {
+pub fn handle_alloc_error(layout: Layout) -> ! {
     panic!("Heap allocation error, layout = {:?}", layout);
 }
 
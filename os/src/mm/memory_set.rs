@@ -1,9 +1,13 @@
 use super::PageTableEntry;
-use super::address::{PhysAddr, PhysPageNum, VirtAddr, VirtPageNum};
-use super::frame_allocator::{FrameTracker, frame_alloc};
+use super::address::{PhysPageNum, VirtAddr, VirtPageNum, phys_to_virt, virt_to_phys};
+use super::frame_allocator::{FrameTracker, SharedFrame, frame_alloc, frame_stats};
 use super::page_table::{PTEFlags, PageTable};
 use crate::board::MEMORY_END;
-use crate::config::{PAGE_SIZE, TRAMPOLINE_ADDR, TRAP_CONTEXT_ADDR, USER_STACK_SIZE};
+use crate::config::{
+    ASLR, ASLR_STACK_PAGES, FLAT_BASE_ADDR, MMIO, PAGE_SIZE, TRAMPOLINE_ADDR, TRAP_CONTEXT_ADDR,
+    USER_FRAME_LIMIT, USER_SPACE_END, USER_STACK_LIMIT, USER_STACK_SIZE,
+};
+use crate::random;
 use crate::sync::*;
 use crate::*;
 use alloc::collections::btree_map::BTreeMap;
@@ -12,6 +16,7 @@ use alloc::vec;
 use alloc::vec::Vec;
 use bitflags::bitflags;
 use core::arch::asm;
+use core::fmt;
 use lazy_static::lazy_static;
 use log::*;
 use riscv::register;
@@ -30,6 +35,12 @@ lazy_static! {
         Arc::new(unsafe { UPSafeCell::new(MemorySet::init_kernel_space()) });
 }
 
+lazy_static! {
+    /// A frame of zeros, mapped read-only into every untouched page of `MapType::ZeroFill` areas.
+    static ref ZERO_PAGE: SharedFrame =
+        SharedFrame::from(frame_alloc().expect("failed to alloc the zero page"));
+}
+
 /// MemorySet represents the address space of a process.
 ///
 /// Each process has its own `MemorySet`, which contains the page table and all mapped memory areas.
@@ -38,6 +49,8 @@ pub struct MemorySet {
     pub page_table: PageTable,
     /// All memory areas mapped in this address space.
     areas: Vec<MapArea>,
+    /// The pages the user stack may grow over, `None` without a user stack.
+    stack_bounds: Option<VPNRange>,
 }
 
 impl MemorySet {
@@ -46,6 +59,7 @@ impl MemorySet {
         Self {
             page_table: PageTable::new(),
             areas: Vec::new(),
+            stack_bounds: None,
         }
     }
 
@@ -55,6 +69,14 @@ impl MemorySet {
     /// * `map_area` - The memory area to map.
     /// * `bytes` - Optional byte slice to initialize the mapped area.
     fn push(&mut self, mut map_area: MapArea, bytes: Option<&[u8]>) {
+        // W^X: user memory is never writable and executable at the same time
+        assert!(
+            !map_area
+                .map_perm
+                .contains(MapPermission::U | MapPermission::W | MapPermission::X),
+            "writable and executable user area at {:?}",
+            map_area.vpn_range.start
+        );
         map_area.map(&mut self.page_table);
 
         if let Some(bytes) = bytes {
@@ -64,10 +86,206 @@ impl MemorySet {
         self.areas.push(map_area);
     }
 
+    /// Identity map the device registers at physical `[pa, pa + len)` for a driver.
+    ///
+    /// The range is rounded out to whole pages and mapped kernel-only R|W; pages already
+    /// covered by an MMIO area are not mapped twice. SV39 without Svpbmt has no memory-type
+    /// bits in the PTE: the platform's physical memory attributes already make device
+    /// regions uncached and strongly ordered, so drivers only need volatile accesses.
+    ///
+    /// # Returns
+    /// The virtual address of `pa`.
+    pub fn map_mmio(&mut self, pa: usize, len: usize) -> VirtAddr {
+        let start_vpn = VirtAddr::from(pa).floor();
+        let end_vpn = VirtAddr::from(pa + len).ceil();
+        let mapped = self.areas.iter().any(|area| {
+            area.kind == MapKind::Mmio
+                && area.vpn_range.start <= start_vpn
+                && end_vpn <= area.vpn_range.end
+        });
+        if !mapped {
+            trace!("mapping MMIO [{:#x}, {:#x})", pa, pa + len);
+            self.push(
+                MapArea::new(
+                    pa.into(),
+                    (pa + len).into(),
+                    MapType::Identical,
+                    MapPermission::R | MapPermission::W,
+                    MapKind::Mmio,
+                ),
+                None,
+            );
+            // drop stale translations in case this address space is already active
+            unsafe { asm!("sfence.vma") };
+        }
+        VirtAddr::from(pa)
+    }
+
     pub fn translate(&self, vpn: VirtPageNum) -> Option<PageTableEntry> {
         self.page_table.translate(vpn)
     }
 
+    /// Returns the number of frames owned by this address space: its page table nodes and the
+    /// private frames of its areas. The shared zero page is not counted.
+    pub fn frame_count(&self) -> usize {
+        self.page_table.frame_count()
+            + self
+                .areas
+                .iter()
+                .map(|area| area.data_frames.len())
+                .sum::<usize>()
+    }
+
+    /// Describe every memory area of this address space, in mapping order.
+    pub fn vma_info(&self) -> Vec<VmaInfo> {
+        self.areas
+            .iter()
+            .map(|area| VmaInfo {
+                start: area.vpn_range.start.get_first_addr().0,
+                end: area.vpn_range.end.get_first_addr().0,
+                perm: area.map_perm.bits() as usize,
+                kind: area.kind as usize,
+            })
+            .collect()
+    }
+
+    /// Try to resolve a page fault at `va`.
+    ///
+    /// Faults below the user stack within `USER_STACK_LIMIT` grow the stack, then stores to
+    /// untouched zero-filled pages get their private frame (see `handle_store_fault`).
+    ///
+    /// # Returns
+    /// `Ok` if the fault was resolved and the faulting instruction can be retried.
+    pub fn handle_page_fault(&mut self, va: VirtAddr, write: bool) -> Result<(), PageFaultError> {
+        let grown = self.grow_stack(va);
+        if write {
+            self.handle_store_fault(va)
+        } else if grown {
+            Ok(())
+        } else {
+            Err(PageFaultError::BadAddress)
+        }
+    }
+
+    /// Grow the user stack down to the page containing `va`.
+    ///
+    /// # Returns
+    /// `false` if `va` is not in the stack's growth region below the current stack.
+    fn grow_stack(&mut self, va: VirtAddr) -> bool {
+        let Some(bounds) = self.stack_bounds else {
+            return false;
+        };
+        let vpn = va.floor();
+        if !bounds.contains(vpn) {
+            return false;
+        }
+        let Some(stack) = self
+            .areas
+            .iter_mut()
+            .find(|area| area.vpn_range.end == bounds.end)
+        else {
+            return false;
+        };
+        if vpn >= stack.vpn_range.start {
+            return false;
+        }
+
+        trace!(
+            "growing user stack from {:?} down to {:?}",
+            stack.vpn_range.start, vpn
+        );
+        stack.extend_down(&mut self.page_table, vpn);
+        true
+    }
+
+    /// Try to resolve a store page fault at `va`.
+    ///
+    /// Only the first write to a page of a writable `MapType::ZeroFill` area can be fixed: the
+    /// shared zero page is replaced by a private zeroed frame, unless the address space already
+    /// owns `USER_FRAME_LIMIT` frames.
+    ///
+    /// # Returns
+    /// `Ok` if the fault was resolved and the faulting instruction can be retried.
+    pub fn handle_store_fault(&mut self, va: VirtAddr) -> Result<(), PageFaultError> {
+        let vpn = va.floor();
+        let at_limit = self.frame_count() >= USER_FRAME_LIMIT;
+        match self
+            .areas
+            .iter_mut()
+            .find(|area| area.vpn_range.contains(vpn))
+        {
+            Some(area) if area.maps_zero_page(vpn) => {
+                if at_limit {
+                    return Err(PageFaultError::OutOfMemory);
+                }
+                area.fill_zero_page(&mut self.page_table, vpn)
+            }
+            _ => Err(PageFaultError::BadAddress),
+        }
+    }
+
+    /// Give every untouched zero-filled page in `[start_va, end_va)` its private frame.
+    ///
+    /// The kernel writes user memory through the physical mapping, so it must call this
+    /// before writing, or the write would land in the shared zero page.
+    ///
+    /// # Returns
+    /// `false` if a frame could not be allocated, some pages still map the zero page then.
+    pub fn fill_zero_pages(&mut self, start_va: VirtAddr, end_va: VirtAddr) -> bool {
+        let mut filled = true;
+        for vpn in VPNRange::new(start_va.floor(), end_va.ceil()) {
+            let _ = self.handle_store_fault(vpn.get_first_addr());
+            filled &= !self
+                .areas
+                .iter()
+                .any(|area| area.vpn_range.contains(vpn) && area.maps_zero_page(vpn));
+        }
+        filled
+    }
+
+    /// Drop the private frames of zero-filled pages in `[start_va, end_va)`.
+    ///
+    /// The pages map the shared zero page again and read as zeros until written.
+    pub fn discard_zero_pages(&mut self, start_va: VirtAddr, end_va: VirtAddr) {
+        let range = VPNRange::new(start_va.floor(), end_va.ceil());
+        for area in self
+            .areas
+            .iter_mut()
+            .filter(|area| area.map_type == MapType::ZeroFill)
+        {
+            for vpn in range {
+                if area.vpn_range.contains(vpn) {
+                    area.discard_page(&mut self.page_table, vpn);
+                }
+            }
+        }
+    }
+
+    /// Returns `true` if every page of `[start_va, end_va)` lies in a `MapType::ZeroFill` area.
+    pub fn is_zero_fill_range(&self, start_va: VirtAddr, end_va: VirtAddr) -> bool {
+        VPNRange::new(start_va.floor(), end_va.ceil())
+            .into_iter()
+            .all(|vpn| {
+                self.areas
+                    .iter()
+                    .any(|area| area.map_type == MapType::ZeroFill && area.vpn_range.contains(vpn))
+            })
+    }
+
+    /// Returns `true` if every page of `[start_va, end_va)` is mapped for user access, and
+    /// writable if `write`, so the kernel may access the range through this page table.
+    pub fn is_user_accessible(&self, start_va: VirtAddr, end_va: VirtAddr, write: bool) -> bool {
+        VPNRange::new(start_va.floor(), end_va.ceil())
+            .into_iter()
+            .all(|vpn| {
+                self.page_table.translate(vpn).is_some_and(|pte| {
+                    pte.is_valid()
+                        && pte.flags().contains(PTEFlags::U)
+                        && (!write || pte.writable())
+                })
+            })
+    }
+
     /// Insert a new framed memory area into the address space.
     ///
     /// # Arguments
@@ -81,7 +299,13 @@ impl MemorySet {
         permission: MapPermission,
     ) {
         self.push(
-            MapArea::new(start_va, end_va, MapType::Framed, permission),
+            MapArea::new(
+                start_va,
+                end_va,
+                MapType::Framed,
+                permission,
+                MapKind::Kernel,
+            ),
             None,
         );
     }
@@ -89,9 +313,11 @@ impl MemorySet {
     /// Create a new `MemorySet` for the kernel address space.
     ///
     /// This function constructs a `MemorySet` and maps all necessary kernel sections,
-    /// including .text, .rodata, .data, .bss, and the remaining physical memory.
-    /// All mappings use identical mapping (virtual address equals physical address)
-    /// and do not grant user permissions for safety.
+    /// including .text, .rodata, .data, .bss, the remaining physical memory and the MMIO
+    /// regions of the board.
+    /// The kernel image and physical memory live in the direct map in the upper half (see
+    /// `phys_to_virt`), away from the low half user programs are loaded into; MMIO regions
+    /// are identity mapped. No mapping grants user permissions.
     ///
     /// # Returns
     /// A fully initialized `MemorySet` representing the kernel address space.
@@ -123,7 +349,7 @@ impl MemorySet {
                 ".bss",
             ),
             (
-                (ekernel as usize, MEMORY_END),
+                (ekernel as usize, phys_to_virt(MEMORY_END.into()).0),
                 MapPermission::R | MapPermission::W,
                 "physical memory",
             ),
@@ -132,18 +358,37 @@ impl MemorySet {
         for &((start, end), perm, name) in &sections {
             trace!("mapping {name} section [{start:#x}, {end:#x})");
             memory_set.push(
-                MapArea::new(start.into(), end.into(), MapType::Identical, perm),
+                MapArea::new(
+                    start.into(),
+                    end.into(),
+                    MapType::Direct,
+                    perm,
+                    MapKind::Kernel,
+                ),
                 None,
             );
         }
 
+        for &(start, size) in MMIO {
+            memory_set.map_mmio(start, size);
+        }
+
         memory_set
     }
 
     /// Create a new `MemorySet` from an ELF binary.
     ///
     /// This function parses the ELF file, maps all loadable segments into the address space,
-    /// sets up the user stack with a guard page, reserves space for `sbrk`, and maps the trap context.
+    /// sets up the user stack with a guard page, and maps the trap context. The pages of a
+    /// segment past its file data, .bss with the user heap in it, are `MapType::ZeroFill`, so
+    /// they only take frames once written.
+    /// The stack starts at `USER_STACK_SIZE` and the `USER_STACK_LIMIT` below its top stays
+    /// free, so it can grow on page faults. With `ASLR`, up to `ASLR_STACK_PAGES` random
+    /// pages are left unmapped below that region.
+    ///
+    /// The segments are checked before anything is mapped: they must lie in the file, must
+    /// not share a page and, together with the stack above them, must fit below
+    /// `USER_SPACE_END`.
     ///
     /// # Arguments
     /// * `elf_data` - The ELF binary data as a byte slice.
@@ -153,19 +398,21 @@ impl MemorySet {
     /// - The constructed `MemorySet`
     /// - The top of the user stack (`VirtAddr`)
     /// - The entry point address (`usize`)
-    pub fn from_elf(elf_data: &[u8]) -> (Self, VirtAddr, usize) {
+    ///
+    /// or why the ELF cannot be loaded.
+    pub fn from_elf(elf_data: &[u8]) -> Result<(Self, VirtAddr, usize), LoadError> {
         let mut memory_set = Self::default();
 
         memory_set.map_trampoline();
 
-        let elf = xmas_elf::ElfFile::new(elf_data).expect("failed to parse elf data");
+        let elf = xmas_elf::ElfFile::new(elf_data).map_err(|_| LoadError::BadElf)?;
         let elf_header = elf.header;
         let magic = elf_header.pt1.magic;
-        assert_eq!(magic, [0x7f, 0x45, 0x4c, 0x46], "invalid elf!");
+        if magic != [0x7f, 0x45, 0x4c, 0x46] {
+            return Err(LoadError::BadElf);
+        }
         let ph_count = elf_header.pt2.ph_count(); // program header count
 
-        let mut max_end_vpn: VirtPageNum = (0usize).into();
-
         fn elf_segment_perm(ph_flags: xmas_elf::program::Flags) -> MapPermission {
             let mut perm = MapPermission::U;
             if ph_flags.is_read() {
@@ -180,55 +427,169 @@ impl MemorySet {
             perm
         }
 
-        (0..ph_count)
-            .filter_map(|i| {
-                let ph = elf.program_header(i).ok()?;
-                if ph.get_type().ok()? != xmas_elf::program::Type::Load {
-                    return None;
-                }
-                let start_va: VirtAddr = (ph.virtual_addr() as usize).into();
-                let end_va: VirtAddr = ((ph.virtual_addr() + ph.mem_size()) as usize).into();
-                let perm = elf_segment_perm(ph.flags());
-                // Note: mem_size >= file_size (only code and data)
-                let file_range = ph.offset() as usize..(ph.offset() + ph.file_size()) as usize;
-                Some((start_va, end_va, perm, &elf.input[file_range]))
-            })
-            .for_each(|(start_va, end_va, perm, data)| {
-                let map_area = MapArea::new(start_va, end_va, MapType::Framed, perm);
-                max_end_vpn = map_area.vpn_range.end;
+        let mut segments = Vec::new();
+        for i in 0..ph_count {
+            let ph = elf.program_header(i).map_err(|_| LoadError::BadElf)?;
+            if ph.get_type() != Ok(xmas_elf::program::Type::Load) {
+                continue;
+            }
+            // Note: mem_size >= file_size (only code and data)
+            let start = ph.virtual_addr() as usize;
+            let end = start
+                .checked_add(ph.mem_size() as usize)
+                .ok_or(LoadError::BadSegment)?;
+            let file_start = ph.offset() as usize;
+            let file_end = file_start
+                .checked_add(ph.file_size() as usize)
+                .ok_or(LoadError::BadSegment)?;
+            if ph.file_size() > ph.mem_size() || file_end > elf.input.len() {
+                return Err(LoadError::BadSegment);
+            }
+            // checked before `VirtAddr` truncates it to 39 bits
+            if end > USER_SPACE_END {
+                return Err(LoadError::OutOfUserSpace);
+            }
+            let perm = elf_segment_perm(ph.flags());
+            if perm.contains(MapPermission::W | MapPermission::X) {
+                return Err(LoadError::WritableExecutable);
+            }
+            segments.push((
+                VirtAddr::from(start),
+                VirtAddr::from(end),
+                perm,
+                &elf.input[file_start..file_end],
+            ));
+        }
+        // areas are whole pages, two segments in one page would map it twice
+        segments.sort_unstable_by_key(|(start_va, ..)| start_va.0);
+        for pair in segments.windows(2) {
+            if pair[1].0.floor() < pair[0].1.ceil() {
+                return Err(LoadError::OverlappingSegments);
+            }
+        }
+        let max_end_vpn = segments
+            .last()
+            .map_or(VirtPageNum(0), |(_, end_va, ..)| end_va.ceil());
+        let user_stack_limit = Self::user_stack_limit(max_end_vpn)?;
+
+        for (start_va, end_va, perm, data) in segments {
+            let data_end_vpn = if data.is_empty() {
+                start_va.floor()
+            } else {
+                VirtAddr::from(start_va.0 + data.len()).ceil()
+            };
+            if start_va.floor() < data_end_vpn {
+                let data_end_va = data_end_vpn.get_first_addr();
+                let map_area =
+                    MapArea::new(start_va, data_end_va, MapType::Framed, perm, MapKind::Elf);
                 memory_set.push(map_area, Some(data));
-            });
+            }
+            if data_end_vpn < end_va.ceil() {
+                let start_va = data_end_vpn.get_first_addr();
+                let map_area =
+                    MapArea::new(start_va, end_va, MapType::ZeroFill, perm, MapKind::Elf);
+                memory_set.push(map_area, None);
+            }
+        }
+
+        let user_stack_top = memory_set.map_stack_and_trap_context(user_stack_limit);
+        Ok((
+            memory_set,
+            user_stack_top,
+            elf.header.pt2.entry_point() as usize,
+        ))
+    }
+
+    /// Create a new `MemorySet` from a flat binary: a raw image without any headers, e.g. of
+    /// hand-written assembly, as the batch loader of old used to run.
+    ///
+    /// The image is mapped read-only and executable at `FLAT_BASE_ADDR` and entered at its
+    /// first byte; it has no writable memory but the stack, set up as for `from_elf`.
+    ///
+    /// # Returns
+    /// The `MemorySet`, the top of the user stack and the entry point, or why the image
+    /// cannot be loaded.
+    pub fn from_flat(data: &[u8]) -> Result<(Self, VirtAddr, usize), LoadError> {
+        if data.is_empty() {
+            return Err(LoadError::BadSegment);
+        }
+        let end = FLAT_BASE_ADDR
+            .checked_add(data.len())
+            .ok_or(LoadError::OutOfUserSpace)?;
+        if end > USER_SPACE_END {
+            return Err(LoadError::OutOfUserSpace);
+        }
+        let (start_va, end_va) = (VirtAddr::from(FLAT_BASE_ADDR), VirtAddr::from(end));
+        let user_stack_limit = Self::user_stack_limit(end_va.ceil())?;
 
-        // stack
-        let mut user_stack_bottom: VirtAddr = max_end_vpn.get_first_addr();
-        user_stack_bottom.0 += PAGE_SIZE; // guard page
-        let user_stack_top: VirtAddr = (user_stack_bottom.0 + USER_STACK_SIZE).into();
+        let mut memory_set = Self::default();
+        memory_set.map_trampoline();
         memory_set.push(
+            MapArea::new(
+                start_va,
+                end_va,
+                MapType::Framed,
+                MapPermission::R | MapPermission::X | MapPermission::U,
+                MapKind::Elf,
+            ),
+            Some(data),
+        );
+        let user_stack_top = memory_set.map_stack_and_trap_context(user_stack_limit);
+        Ok((memory_set, user_stack_top, FLAT_BASE_ADDR))
+    }
+
+    /// Returns the lowest address the user stack may grow down to, above an image ending at
+    /// `image_end`, a guard page and, with `ASLR`, a random gap.
+    ///
+    /// # Errors
+    /// `LoadError::OutOfUserSpace` if the stack would not fit below `USER_SPACE_END`.
+    fn user_stack_limit(image_end: VirtPageNum) -> Result<VirtAddr, LoadError> {
+        let mut user_stack_limit: VirtAddr = image_end.get_first_addr();
+        user_stack_limit.0 += PAGE_SIZE; // guard page
+        if ASLR {
+            let gap_pages = random::next_u64() as usize % (ASLR_STACK_PAGES + 1);
+            user_stack_limit.0 += gap_pages * PAGE_SIZE;
+        }
+        if user_stack_limit.0 + USER_STACK_LIMIT > USER_SPACE_END {
+            return Err(LoadError::OutOfUserSpace);
+        }
+        Ok(user_stack_limit)
+    }
+
+    /// Map the user stack, which may grow down to `user_stack_limit`, and the trap context.
+    ///
+    /// # Returns
+    /// The top of the user stack.
+    fn map_stack_and_trap_context(&mut self, user_stack_limit: VirtAddr) -> VirtAddr {
+        let user_stack_top: VirtAddr = (user_stack_limit.0 + USER_STACK_LIMIT).into();
+        let user_stack_bottom: VirtAddr = (user_stack_top.0 - USER_STACK_SIZE).into();
+        self.stack_bounds = Some(VPNRange::new(
+            user_stack_limit.floor(),
+            user_stack_top.floor(),
+        ));
+        self.push(
             MapArea::new(
                 user_stack_bottom,
                 user_stack_top,
-                MapType::Framed,
+                MapType::ZeroFill,
                 MapPermission::R | MapPermission::W | MapPermission::U,
+                MapKind::Stack,
             ),
             None,
         );
 
         // map TrapContext
-        memory_set.push(
+        self.push(
             MapArea::new(
                 VirtAddr::from(TRAP_CONTEXT_ADDR),
                 VirtAddr::from(TRAMPOLINE_ADDR),
                 MapType::Framed,
                 MapPermission::R | MapPermission::W,
+                MapKind::TrapContext,
             ),
             None,
         );
-
-        (
-            memory_set,
-            user_stack_top,
-            elf.header.pt2.entry_point() as usize,
-        )
+        user_stack_top
     }
 
     /// returns the value that should be written to the RISC-V satp
@@ -261,12 +622,35 @@ impl MemorySet {
     /// The trampoline is used for context switching and trap handling.
     fn map_trampoline(&mut self) {
         let vpn = VirtAddr::from(TRAMPOLINE_ADDR).floor();
-        let ppn = PhysAddr::from(strampoline as usize).floor();
+        let ppn = virt_to_phys(VirtAddr(strampoline as usize)).floor();
         trace!("mapping trampoline: {vpn:#?} -> {ppn:#?}");
         self.page_table.map(vpn, ppn, PTEFlags::R | PTEFlags::X);
     }
 }
 
+impl Drop for MemorySet {
+    /// Tear the address space down in a fixed order: unmap the areas, newest first, giving
+    /// their frames back, then free the page table nodes nothing maps through anymore.
+    ///
+    /// Debug builds check that exactly the frames this address space owned were freed.
+    fn drop(&mut self) {
+        #[cfg(debug_assertions)]
+        let (owned, free_before) = (self.frame_count(), frame_stats().1);
+
+        while let Some(mut area) = self.areas.pop() {
+            area.unmap(&mut self.page_table);
+        }
+        self.page_table.free_frames();
+
+        #[cfg(debug_assertions)]
+        assert_eq!(
+            frame_stats().1,
+            free_before + owned,
+            "address space leaked frames on teardown"
+        );
+    }
+}
+
 /// Describes a continuous range of virtual pages with the same mapping type and permissions.
 ///
 /// `MapArea` manages the mapping between a range of virtual page numbers and their corresponding
@@ -280,6 +664,8 @@ pub struct MapArea {
     map_type: MapType,
     /// The permissions for this memory area.
     map_perm: MapPermission,
+    /// What this memory area is used for.
+    kind: MapKind,
 }
 
 impl MapArea {
@@ -294,6 +680,7 @@ impl MapArea {
     /// * `end_va` - The end virtual address (exclusive).
     /// * `map_type` - The type of mapping (e.g., Identical, Framed).
     /// * `map_perm` - The permissions for this memory area.
+    /// * `kind` - What the memory area is used for.
     ///
     /// # Returns
     /// A new `MapArea` covering the specified virtual address range.
@@ -302,6 +689,7 @@ impl MapArea {
         end_va: VirtAddr,
         map_type: MapType,
         map_perm: MapPermission,
+        kind: MapKind,
     ) -> Self {
         let start: VirtPageNum = start_va.floor();
         let end: VirtPageNum = end_va.ceil();
@@ -310,6 +698,7 @@ impl MapArea {
             data_frames: BTreeMap::new(),
             map_type,
             map_perm,
+            kind,
         }
     }
 
@@ -333,28 +722,83 @@ impl MapArea {
 
     /// Map a single virtual page in this area using the provided page table.
     ///
-    /// Allocates a physical frame if the mapping type is `Framed`, or uses the same page number
-    /// for `Identical` mapping. Updates the page table with the mapping and permissions.
+    /// Allocates a physical frame if the mapping type is `Framed`, uses the same page number
+    /// for `Identical` mapping and the direct map's frame for `Direct` mapping. Updates the
+    /// page table with the mapping and permissions.
     ///
     /// # Arguments
     /// * `page_table` - The page table to update.
     /// * `vpn` - The virtual page number to map.
     fn map_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
+        let mut pte_flags = self.pte_flags();
         let ppn: PhysPageNum = match self.map_type {
             MapType::Identical => vpn.0.into(),
+            MapType::Direct => virt_to_phys(vpn.get_first_addr()).floor(),
             MapType::Framed => {
                 let frame = frame_alloc().expect("failed to alloc frame when using map_one");
                 let ppn = frame.ppn;
                 self.data_frames.insert(vpn, frame);
                 ppn
             }
+            MapType::ZeroFill => {
+                // writes fault and get a private frame in `fill_zero_page`
+                pte_flags.remove(PTEFlags::W);
+                ZERO_PAGE.ppn()
+            }
         };
 
-        let pte_flags =
-            PTEFlags::from_bits(self.map_perm.bits()).expect("invalid MapPermission bits");
         page_table.map(vpn, ppn, pte_flags);
     }
 
+    /// Replace the zero page mapped at `vpn` with a private zeroed frame.
+    ///
+    /// # Returns
+    /// `PageFaultError::BadAddress` if this is not an untouched page of a writable `ZeroFill`
+    /// area, `PageFaultError::OutOfMemory` if no frame is left.
+    fn fill_zero_page(
+        &mut self,
+        page_table: &mut PageTable,
+        vpn: VirtPageNum,
+    ) -> Result<(), PageFaultError> {
+        if !self.maps_zero_page(vpn) {
+            return Err(PageFaultError::BadAddress);
+        }
+        let frame = frame_alloc().ok_or(PageFaultError::OutOfMemory)?;
+
+        page_table.unmap(vpn);
+        page_table.map(vpn, frame.ppn, self.pte_flags());
+        self.data_frames.insert(vpn, frame);
+        Ok(())
+    }
+
+    /// Returns `true` if `vpn` is a writable zero-filled page still mapping the zero page.
+    fn maps_zero_page(&self, vpn: VirtPageNum) -> bool {
+        self.map_type == MapType::ZeroFill
+            && self.map_perm.contains(MapPermission::W)
+            && !self.data_frames.contains_key(&vpn)
+    }
+
+    /// Extend the area down to start at `start`, mapping the new pages.
+    fn extend_down(&mut self, page_table: &mut PageTable, start: VirtPageNum) {
+        for vpn in VPNRange::new(start, self.vpn_range.start) {
+            self.map_one(page_table, vpn);
+        }
+        self.vpn_range.start = start;
+    }
+
+    /// Give the private frame of a zero-filled page back and map the zero page again.
+    fn discard_page(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
+        if self.data_frames.remove(&vpn).is_some() {
+            page_table.unmap(vpn);
+            self.map_one(page_table, vpn);
+        }
+    }
+
+    /// Returns the page table flags matching this area's permissions.
+    fn pte_flags(&self) -> PTEFlags {
+        PTEFlags::from_bits(self.map_perm.bits()).expect("invalid MapPermission bits")
+    }
+
     /// Unmap a single virtual page in this area using the provided page table.
     ///
     /// Removes the frame from `data_frames` if the mapping type is `Framed`, and updates the page table.
@@ -364,7 +808,7 @@ impl MapArea {
     /// * `vpn` - The virtual page number to unmap.
     fn unmap_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
         match self.map_type {
-            MapType::Framed => {
+            MapType::Framed | MapType::ZeroFill => {
                 self.data_frames.remove(&vpn);
             }
             _ => {}
@@ -411,6 +855,11 @@ impl VPNRange {
     pub fn new(start: VirtPageNum, end: VirtPageNum) -> Self {
         Self { start, end }
     }
+
+    /// Returns `true` if `vpn` lies in `[start, end)`.
+    pub fn contains(&self, vpn: VirtPageNum) -> bool {
+        self.start <= vpn && vpn < self.end
+    }
 }
 
 impl IntoIterator for VPNRange {
@@ -448,11 +897,83 @@ impl Iterator for VPNRangeIterator {
 /// The type of mapping for a memory area.
 ///
 /// - `Identical`: The virtual page number is mapped to the same physical page number.
+/// - `Direct`: Kernel memory in the direct map; each virtual page maps the frame given by
+///   `virt_to_phys`.
 /// - `Framed`: Each virtual page is mapped to a newly allocated physical frame.
+/// - `ZeroFill`: Anonymous memory, i.e. the user stack and .bss; each virtual page maps the
+///   shared zero page read-only until its first write allocates a private frame.
 #[derive(Copy, Clone, PartialEq, Debug)]
 pub enum MapType {
     Identical,
+    Direct,
     Framed,
+    ZeroFill,
+}
+
+/// What a memory area is used for, reported to user space by `MemorySet::vma_info`.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum MapKind {
+    Kernel,
+    Elf,
+    Stack,
+    TrapContext,
+    /// Device registers mapped with `MemorySet::map_mmio`.
+    Mmio,
+}
+
+/// Why `MemorySet::from_elf` rejected an ELF file, or `MemorySet::from_flat` a flat binary.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum LoadError {
+    /// Not a parsable ELF file.
+    BadElf,
+    /// A segment is larger in the file than in memory, or extends past the end of the file;
+    /// or the flat binary is empty.
+    BadSegment,
+    /// Two segments share a page.
+    OverlappingSegments,
+    /// A segment, or the stack placed above the segments, reaches beyond `USER_SPACE_END`.
+    OutOfUserSpace,
+    /// A segment is both writable and executable, which W^X forbids.
+    WritableExecutable,
+    /// The image does not match the checksum taken when the kernel image was built.
+    Corrupted,
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            Self::BadElf => "not a valid ELF file",
+            Self::BadSegment => "segment exceeds the file",
+            Self::OverlappingSegments => "segments overlap",
+            Self::OutOfUserSpace => "segments or stack beyond user space",
+            Self::WritableExecutable => "writable and executable segment, W^X forbids it",
+            Self::Corrupted => "checksum mismatch, the image is corrupted",
+        };
+        f.write_str(reason)
+    }
+}
+
+/// Why `MemorySet::handle_page_fault` could not resolve a page fault.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum PageFaultError {
+    /// The address is not mapped, or not writable for a store.
+    BadAddress,
+    /// A frame was needed but none is free, or the address space reached `USER_FRAME_LIMIT`.
+    OutOfMemory,
+}
+
+/// One memory area as seen by `sys_get_vma_info`, layout compatible with user space.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct VmaInfo {
+    /// Start virtual address (inclusive).
+    pub start: usize,
+    /// End virtual address (exclusive).
+    pub end: usize,
+    /// `MapPermission` bits.
+    pub perm: usize,
+    /// `MapKind` as a number.
+    pub kind: usize,
 }
 
 bitflags! {
@@ -495,3 +1016,272 @@ pub fn remap_kernel_test() {
     );
     println!("remap_test passed!");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[test_case]
+    fn vpn_range_iteration() {
+        let range = VPNRange::new(VirtPageNum(10), VirtPageNum(13));
+        let vpns: Vec<_> = range.into_iter().collect();
+        assert_eq!(vpns, [VirtPageNum(10), VirtPageNum(11), VirtPageNum(12)]);
+        assert!(range.contains(VirtPageNum(10)));
+        assert!(range.contains(VirtPageNum(12)));
+        assert!(!range.contains(VirtPageNum(13)));
+        assert!(!range.contains(VirtPageNum(9)));
+    }
+
+    #[test_case]
+    fn mmio_is_mapped_once() {
+        let mut memory_set = MemorySet::default();
+        let va = memory_set.map_mmio(0x1000_0100, 0x10);
+        assert_eq!(va.0, 0x1000_0100);
+        memory_set.map_mmio(0x1000_0000, 0x1000);
+        assert_eq!(memory_set.areas.len(), 1);
+        let pte = memory_set.translate(VirtPageNum(0x1_0000)).unwrap();
+        assert_eq!(pte.ppn().0, 0x1_0000);
+        assert!(pte.readable() && pte.writable() && !pte.executable());
+    }
+
+    #[test_case]
+    fn address_spaces_free_their_frames() {
+        // allocated once, on first use
+        ZERO_PAGE.ppn();
+        let elf_data = crate::loader::get_app_data(0);
+        let (_, free_before) = frame_stats();
+        for _ in 0..100 {
+            let (mut memory_set, user_sp, _) = MemorySet::from_elf(elf_data).unwrap();
+            // a grown stack and a private page have to go too
+            assert!(
+                memory_set
+                    .handle_page_fault(VirtAddr::from(user_sp.0 - USER_STACK_SIZE - 1), true)
+                    .is_ok()
+            );
+            assert!(memory_set.frame_count() > 0);
+        }
+        assert_eq!(frame_stats().1, free_before);
+    }
+
+    #[test_case]
+    fn user_accessible_ranges() {
+        let elf_data = crate::loader::get_app_data(0);
+        let (mut memory_set, user_sp, entry) = MemorySet::from_elf(elf_data).unwrap();
+        let sp = user_sp.0;
+        assert!(memory_set.is_user_accessible((sp - 16).into(), sp.into(), false));
+        // untouched stack pages map the read-only zero page until filled
+        memory_set.fill_zero_pages((sp - 16).into(), sp.into());
+        assert!(memory_set.is_user_accessible((sp - 16).into(), sp.into(), true));
+        assert!(!memory_set.is_user_accessible((sp - 16).into(), (sp + PAGE_SIZE).into(), false));
+        assert!(memory_set.is_user_accessible(entry.into(), (entry + 4).into(), false));
+        assert!(!memory_set.is_user_accessible(entry.into(), (entry + 4).into(), true));
+        let trap_cx = VirtAddr::from(TRAP_CONTEXT_ADDR);
+        assert!(!memory_set.is_user_accessible(trap_cx, (TRAP_CONTEXT_ADDR + 8).into(), false));
+    }
+
+    #[test_case]
+    fn page_faults_report_why_they_fail() {
+        let elf_data = crate::loader::get_app_data(0);
+        let (mut memory_set, user_sp, _) = MemorySet::from_elf(elf_data).unwrap();
+        let below_limit = VirtAddr::from(user_sp.0 - USER_STACK_LIMIT - PAGE_SIZE);
+        assert_eq!(
+            memory_set.handle_page_fault(below_limit, true),
+            Err(PageFaultError::BadAddress)
+        );
+
+        // an unmapped area owning the address space's share of frames
+        let mut hog = MapArea::new(
+            VirtAddr::from(0x1_0000_0000),
+            VirtAddr::from(0x1_0000_0000 + USER_FRAME_LIMIT * PAGE_SIZE),
+            MapType::ZeroFill,
+            MapPermission::R | MapPermission::W | MapPermission::U,
+            MapKind::Elf,
+        );
+        for vpn in hog.vpn_range {
+            hog.data_frames.insert(vpn, frame_alloc().unwrap());
+        }
+        let stack_page = VirtAddr::from(user_sp.0 - 1);
+        memory_set.areas.push(hog);
+        assert_eq!(
+            memory_set.handle_page_fault(stack_page, true),
+            Err(PageFaultError::OutOfMemory)
+        );
+        // dropping the address space would unmap it
+        memory_set.areas.pop();
+    }
+
+    #[test_case]
+    fn user_stack_sits_above_elf() {
+        let elf_data = crate::loader::get_app_data(0);
+        for _ in 0..8 {
+            let (memory_set, user_sp, _) = MemorySet::from_elf(elf_data).unwrap();
+            let elf_end = memory_set
+                .areas
+                .iter()
+                .filter(|area| area.kind == MapKind::Elf)
+                .map(|area| area.vpn_range.end)
+                .max()
+                .unwrap();
+            let bounds = memory_set.stack_bounds.unwrap();
+            // one guard page plus the random gap
+            let gap_pages = bounds.start.0 - elf_end.0 - 1;
+            if !ASLR {
+                assert_eq!(gap_pages, 0);
+            }
+            assert!(gap_pages <= ASLR_STACK_PAGES);
+            assert_eq!(user_sp.floor(), bounds.end);
+        }
+    }
+
+    /// A minimal ELF with a loadable segment per `(p_flags, p_vaddr, p_memsz)`, the first one
+    /// backed by the ELF headers.
+    fn elf_with_segments(segments: &[(u32, u64, u64)]) -> Vec<u8> {
+        const EHDR_SIZE: u16 = 64;
+        const PHDR_SIZE: u16 = 56;
+        let headers_size = EHDR_SIZE as u64 + PHDR_SIZE as u64 * segments.len() as u64;
+        let mut elf = Vec::new();
+        elf.extend_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        elf.extend_from_slice(&2u16.to_le_bytes()); // ET_EXEC
+        elf.extend_from_slice(&243u16.to_le_bytes()); // EM_RISCV
+        elf.extend_from_slice(&1u32.to_le_bytes());
+        elf.extend_from_slice(&segments[0].1.to_le_bytes()); // entry
+        elf.extend_from_slice(&(EHDR_SIZE as u64).to_le_bytes()); // phoff
+        elf.extend_from_slice(&0u64.to_le_bytes()); // shoff
+        elf.extend_from_slice(&0u32.to_le_bytes());
+        elf.extend_from_slice(&EHDR_SIZE.to_le_bytes());
+        elf.extend_from_slice(&PHDR_SIZE.to_le_bytes());
+        elf.extend_from_slice(&(segments.len() as u16).to_le_bytes()); // phnum
+        elf.extend_from_slice(&[0; 6]); // no sections
+        for (i, &(flags, vaddr, mem_size)) in segments.iter().enumerate() {
+            let file_size = if i == 0 { headers_size } else { 0 };
+            elf.extend_from_slice(&1u32.to_le_bytes()); // PT_LOAD
+            elf.extend_from_slice(&flags.to_le_bytes());
+            elf.extend_from_slice(&0u64.to_le_bytes()); // offset
+            elf.extend_from_slice(&vaddr.to_le_bytes()); // vaddr
+            elf.extend_from_slice(&vaddr.to_le_bytes()); // paddr
+            elf.extend_from_slice(&file_size.to_le_bytes()); // filesz
+            elf.extend_from_slice(&mem_size.to_le_bytes()); // memsz
+            elf.extend_from_slice(&(PAGE_SIZE as u64).to_le_bytes()); // align
+        }
+        elf
+    }
+
+    /// A minimal ELF with one loadable segment of one page at 0x10000, `flags` as in `p_flags`.
+    fn one_segment_elf(flags: u32) -> Vec<u8> {
+        elf_with_segments(&[(flags, 0x10000, PAGE_SIZE as u64)])
+    }
+
+    #[test_case]
+    fn elf_breaking_w_xor_x_is_rejected() {
+        const PF_X: u32 = 1;
+        const PF_W: u32 = 2;
+        const PF_R: u32 = 4;
+        assert!(MemorySet::from_elf(&one_segment_elf(PF_R | PF_X)).is_ok());
+        assert!(MemorySet::from_elf(&one_segment_elf(PF_R | PF_W)).is_ok());
+        assert_eq!(
+            MemorySet::from_elf(&one_segment_elf(PF_R | PF_W | PF_X)).err(),
+            Some(LoadError::WritableExecutable)
+        );
+    }
+
+    #[test_case]
+    fn elf_with_bad_segments_is_rejected() {
+        const PF_R: u32 = 4;
+        let page = PAGE_SIZE as u64;
+        let load =
+            |segments: &[(u32, u64, u64)]| MemorySet::from_elf(&elf_with_segments(segments)).err();
+        // adjacent pages are fine, in any order
+        assert_eq!(load(&[(PF_R, 0x11000, page), (PF_R, 0x10000, page)]), None);
+        // sharing the page at 0x10000
+        assert_eq!(
+            load(&[(PF_R, 0x10000, page / 2), (PF_R, 0x10800, page)]),
+            Some(LoadError::OverlappingSegments)
+        );
+        assert_eq!(
+            load(&[(PF_R, USER_SPACE_END as u64, page)]),
+            Some(LoadError::OutOfUserSpace)
+        );
+        // a kernel address would alias the low half once truncated to 39 bits
+        assert_eq!(
+            load(&[(PF_R, TRAP_CONTEXT_ADDR as u64, page)]),
+            Some(LoadError::OutOfUserSpace)
+        );
+        assert_eq!(
+            load(&[(PF_R, 0x10000, u64::MAX)]),
+            Some(LoadError::BadSegment)
+        );
+        // the segment leaves no room for the stack below USER_SPACE_END
+        assert_eq!(
+            load(&[(PF_R, USER_SPACE_END as u64 - page, page)]),
+            Some(LoadError::OutOfUserSpace)
+        );
+    }
+
+    #[test_case]
+    fn large_bss_is_filled_on_demand() {
+        const PF_W: u32 = 2;
+        const PF_R: u32 = 4;
+        // a page of headers followed by a 4 MiB .bss, as a big user heap would be
+        let bss_pages = 1024;
+        let bss_start = 0x11000;
+        let bss_end = bss_start + bss_pages * PAGE_SIZE;
+        let elf = elf_with_segments(&[(PF_R | PF_W, 0x10000, (bss_end - 0x10000) as u64)]);
+        let (mut memory_set, ..) = MemorySet::from_elf(&elf).unwrap();
+        let headers = memory_set.translate(VirtPageNum(0x10)).unwrap().ppn();
+        assert_eq!(headers.get_bytes_array()[..4], [0x7f, b'E', b'L', b'F']);
+        assert!(!memory_set.is_zero_fill_range(VirtAddr::from(0x10000), bss_start.into()));
+        assert!(memory_set.is_zero_fill_range(bss_start.into(), bss_end.into()));
+
+        let frames_before = memory_set.frame_count();
+        let bss = VPNRange::new(
+            VirtAddr::from(bss_start).floor(),
+            VirtAddr::from(bss_end).floor(),
+        );
+        for vpn in bss {
+            assert!(
+                memory_set
+                    .handle_page_fault(vpn.get_first_addr(), true)
+                    .is_ok()
+            );
+            let pte = memory_set.translate(vpn).unwrap();
+            assert!(pte.writable());
+            let page = pte.ppn().get_bytes_array_mut();
+            // a write to a page before would show here if the frames were shared
+            assert!(page.iter().all(|&byte| byte == 0));
+            page[0] = 0xff;
+        }
+        assert_eq!(memory_set.frame_count(), frames_before + bss_pages);
+    }
+
+    #[test_case]
+    fn flat_binary_is_mapped_read_only_at_its_base() {
+        // `li a0, 0; ret`
+        let image = [0x13, 0x05, 0x00, 0x00, 0x67, 0x80, 0x00, 0x00];
+        let (memory_set, user_sp, entry) = MemorySet::from_flat(&image).unwrap();
+        assert_eq!(entry, FLAT_BASE_ADDR);
+        assert!(memory_set.is_user_accessible(entry.into(), (entry + image.len()).into(), false));
+        assert!(!memory_set.is_user_accessible(entry.into(), (entry + 4).into(), true));
+        let pte = memory_set.translate(VirtAddr::from(entry).floor()).unwrap();
+        assert!(pte.executable());
+        assert!(user_sp.0 > entry + PAGE_SIZE);
+        assert_eq!(MemorySet::from_flat(&[]).err(), Some(LoadError::BadSegment));
+    }
+
+    #[test_case]
+    fn user_stack_is_not_executable() {
+        let (memory_set, user_sp, _) = MemorySet::from_elf(crate::loader::get_app_data(0)).unwrap();
+        // the untouched top page maps the zero page, read-only until the first write
+        let pte = memory_set
+            .translate(VirtAddr::from(user_sp.0 - 1).floor())
+            .unwrap();
+        assert!(pte.is_valid() && !pte.executable());
+    }
+
+    #[test_case]
+    fn empty_vpn_range() {
+        let range = VPNRange::new(VirtPageNum(7), VirtPageNum(7));
+        assert_eq!(range.into_iter().count(), 0);
+        assert!(!range.contains(VirtPageNum(7)));
+    }
+}
@@ -0,0 +1,60 @@
+//! Boot-time memory test, built with the `memtest` feature.
+//!
+//! Runs before the frame allocator takes over physical memory, while nothing lives there yet.
+//! Every word of a frame is written with a pattern derived from its address and read back,
+//! then again with the inverted pattern, catching stuck bits and aliased addresses. Frames
+//! failing it are reserved, so the allocator never hands them out. The board's
+//! `RESERVED_MEMORY` is left alone: it may hold data kept across a reboot, see `pstore`.
+
+use super::address::{PhysAddr, PhysPageNum, phys_to_virt};
+use crate::board::RESERVED_MEMORY;
+use crate::config::PAGE_SIZE;
+use alloc::vec::Vec;
+use log::{info, warn};
+
+/// Mixed into the address so neighbouring words differ in many bits.
+const PATTERN: usize = 0x5a5a_c3c3_a5a5_3c3c;
+
+/// Returns `true` if every word of `ppn` holds both patterns.
+fn frame_ok(ppn: PhysPageNum) -> bool {
+    let words = unsafe {
+        core::slice::from_raw_parts_mut(
+            phys_to_virt(ppn.get_first_addr()).0 as *mut usize,
+            PAGE_SIZE / size_of::<usize>(),
+        )
+    };
+    for invert in [0, usize::MAX] {
+        for word in words.iter_mut() {
+            let value = (word as *mut usize as usize ^ PATTERN) ^ invert;
+            unsafe { (word as *mut usize).write_volatile(value) };
+        }
+        for word in words.iter() {
+            let value = (word as *const usize as usize ^ PATTERN) ^ invert;
+            if unsafe { (word as *const usize).read_volatile() } != value {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+/// Returns `true` if `ppn` is in the board's `RESERVED_MEMORY`.
+fn is_reserved(ppn: PhysPageNum) -> bool {
+    RESERVED_MEMORY.iter().any(|&(start, end)| {
+        PhysAddr::from(start).floor() <= ppn && ppn < PhysAddr::from(end).ceil()
+    })
+}
+
+/// Test the frames `[start, end)` and return the bad ones.
+pub fn bad_frames(start: PhysPageNum, end: PhysPageNum) -> Vec<PhysPageNum> {
+    info!("[kernel] memtest: testing {} frames", end.0 - start.0);
+    let bad: Vec<_> = (start.0..end.0)
+        .map(PhysPageNum)
+        .filter(|&ppn| !is_reserved(ppn) && !frame_ok(ppn))
+        .collect();
+    for ppn in bad.iter() {
+        warn!("[kernel] memtest: bad frame {ppn:?}, reserving it");
+    }
+    info!("[kernel] memtest: {} bad frames", bad.len());
+    bad
+}
@@ -10,22 +10,59 @@ pub mod address;
 mod frame_allocator;
 mod heap_allocator;
 mod memory_set;
+#[cfg(feature = "memtest")]
+mod memtest;
 mod page_table;
+mod slab;
+mod sum;
+mod user_ptr;
 
-pub use address::{PhysAddr, PhysPageNum, VirtAddr, VirtPageNum};
-pub use memory_set::{KERNEL_SPACE, MapPermission, MemorySet};
-pub use page_table::{PageTableEntry, translated_byte_buffer};
+pub use address::{PhysAddr, PhysPageNum, VirtAddr, VirtPageNum, phys_to_virt, virt_to_phys};
+pub use frame_allocator::{FrameTracker, frame_alloc, frame_stats};
+pub use heap_allocator::{heap_stats, slab_stats};
+pub use memory_set::{KERNEL_SPACE, LoadError, MapPermission, MemorySet, PageFaultError, VmaInfo};
+pub use page_table::{PageTable, PageTableEntry, translated_byte_buffer};
+pub use slab::SlabStat;
+pub use sum::SumGuard;
+pub use user_ptr::{UserPtr, UserSlice};
 
+#[cfg(feature = "selftest")]
 use self::frame_allocator::frame_allocator_test;
+#[cfg(feature = "selftest")]
 use self::heap_allocator::heap_test;
-use self::memory_set::{activate_kernel, remap_kernel_test};
+use self::memory_set::activate_kernel;
+#[cfg(feature = "selftest")]
+use self::memory_set::remap_kernel_test;
 
-/// initiate heap allocator, frame allocator and kernel space
-pub fn init() {
+/// Returns `true` if the heap, the frame allocator or the kernel space is in use, e.g. by
+/// code a panic interrupted.
+pub fn is_busy() -> bool {
+    heap_allocator::is_locked() || frame_allocator::is_busy() || KERNEL_SPACE.is_borrowed()
+}
+
+/// Make instruction fetches on this hart see all earlier stores to memory (`fence.i`).
+///
+/// Only the boot hart runs, so no other hart has to be shot down.
+pub fn flush_icache() {
+    unsafe { core::arch::asm!("fence.i") };
+}
+
+/// First phase of memory management: the heap and the frame allocator.
+///
+/// Paging is still off afterwards; `KERNEL_SPACE` can be built and extended (e.g. with MMIO
+/// regions of drivers) until `init_late` activates it.
+pub fn init_early() {
     heap_allocator::init_heap();
+    #[cfg(feature = "selftest")]
     heap_test();
     frame_allocator::init_frame_allocator();
+    #[cfg(feature = "selftest")]
     frame_allocator_test();
+}
+
+/// Second phase of memory management: switch to the kernel address space.
+pub fn init_late() {
     activate_kernel();
+    #[cfg(feature = "selftest")]
     remap_kernel_test();
 }
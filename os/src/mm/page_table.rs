@@ -1,4 +1,4 @@
-use super::address::{PhysPageNum, VirtAddr, VirtPageNum};
+use super::address::{PhysAddr, PhysPageNum, VirtAddr, VirtPageNum, phys_to_virt};
 use super::frame_allocator::{FrameTracker, frame_alloc};
 use crate::config::PAGE_SIZE;
 use alloc::vec;
@@ -41,6 +41,18 @@ impl PageTable {
         }
     }
 
+    /// Returns the number of frames holding page table nodes of this page table.
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Free the frames of all page table nodes.
+    ///
+    /// Only for tearing an address space down: `root_ppn` dangles afterwards.
+    pub fn free_frames(&mut self) {
+        self.frames.clear();
+    }
+
     /// Translate a virtual page number to its corresponding page table entry, if mapped.
     ///
     /// # Arguments
@@ -53,6 +65,49 @@ impl PageTable {
         self.find_pte_mut(vpn).map(|pte| *pte) // NOTE: PageTableEntry is Copy trait
     }
 
+    /// Translate a virtual address to its physical address, if mapped.
+    ///
+    /// # Arguments
+    /// * `va` - The virtual address to translate.
+    ///
+    /// # Returns
+    /// * `Some(PhysAddr)` if the page containing `va` is mapped.
+    /// * `None` otherwise.
+    pub fn translate_va(&self, va: VirtAddr) -> Option<PhysAddr> {
+        self.find_pte_mut(va.floor())
+            .filter(|pte| pte.is_valid())
+            .map(|pte| (pte.ppn().get_first_addr().0 + va.page_offset()).into())
+    }
+
+    /// Translate the virtual address range `[start, start + len)` into the physical extents
+    /// backing it. Pages mapped to physically contiguous frames are merged into one extent,
+    /// so a buffer in contiguous frames yields a single extent however many pages it spans.
+    ///
+    /// # Returns
+    /// `(start, len)` of each extent in virtual address order, or `None` if a page of the
+    /// range is not mapped or the kernel heap is out of memory.
+    pub fn translate_range(&self, start: usize, len: usize) -> Option<Vec<(PhysAddr, usize)>> {
+        let end = start.checked_add(len)?;
+        let mut extents: Vec<(PhysAddr, usize)> = Vec::new();
+        let mut current = start;
+        while current < end {
+            let pa = self.translate_va(VirtAddr::from(current))?;
+            let page_end = (current / PAGE_SIZE + 1) * PAGE_SIZE;
+            let chunk_len = page_end.min(end) - current;
+            match extents.last_mut() {
+                Some((extent_start, extent_len)) if extent_start.0 + *extent_len == pa.0 => {
+                    *extent_len += chunk_len;
+                }
+                _ => {
+                    extents.try_reserve(1).ok()?;
+                    extents.push((pa, chunk_len));
+                }
+            }
+            current += chunk_len;
+        }
+        Some(extents)
+    }
+
     /// Translate a virtual address range into a vector of byte slices mapped in physical memory.
     ///
     /// This function walks the page table and collects all contiguous physical memory slices
@@ -282,24 +337,105 @@ bitflags! {
     }
 }
 
-pub fn translated_byte_buffer(satp: usize, ptr: *const u8, len: usize) -> Vec<&'static mut [u8]> {
+/// Translate the buffer `[ptr, ptr + len)` of the address space of `satp` into slices of the
+/// physical memory backing it, one per physically contiguous extent.
+///
+/// Only checks that every page is mapped, not whether user space may access it.
+///
+/// # Returns
+/// `None` if a page of the buffer is not mapped or the kernel heap is out of memory.
+pub fn translated_byte_buffer(
+    satp: usize,
+    ptr: *const u8,
+    len: usize,
+) -> Option<Vec<&'static mut [u8]>> {
     let page_table = PageTable::from_token(satp);
-    let mut start = ptr as usize;
-    let end = start + len;
-    let mut v = Vec::new();
-    while start < end {
-        let start_va = VirtAddr::from(start);
-        let mut vpn = start_va.floor();
-        let ppn = page_table.translate(vpn).unwrap().ppn();
-        vpn.add(1);
-        let mut end_va: VirtAddr = vpn.get_first_addr();
-        end_va = end_va.min(VirtAddr::from(end));
-        if end_va.page_offset() == 0 {
-            v.push(&mut ppn.get_bytes_array_mut()[start_va.page_offset()..]);
-        } else {
-            v.push(&mut ppn.get_bytes_array_mut()[start_va.page_offset()..end_va.page_offset()]);
+    let extents = page_table.translate_range(ptr as usize, len)?;
+    let mut buffers = Vec::new();
+    buffers.try_reserve_exact(extents.len()).ok()?;
+    for (pa, len) in extents {
+        // SAFETY: physically contiguous memory is contiguous in the direct map too
+        buffers
+            .push(unsafe { core::slice::from_raw_parts_mut(phys_to_virt(pa).0 as *mut u8, len) });
+    }
+    Some(buffers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mm::frame_stats;
+
+    #[test_case]
+    fn map_translate_unmap() {
+        let mut page_table = PageTable::new();
+        let frame = frame_alloc().unwrap();
+        let vpn = VirtPageNum(0x12345);
+        assert!(page_table.translate(vpn).is_none());
+
+        page_table.map(vpn, frame.ppn, PTEFlags::R | PTEFlags::W);
+        let pte = page_table.translate(vpn).unwrap();
+        assert!(pte.is_valid());
+        assert_eq!(pte.ppn(), frame.ppn);
+        assert!(pte.readable() && pte.writable() && !pte.executable());
+        assert_eq!(
+            page_table.translate_va(VirtAddr::from(0x1234_5678)),
+            Some(PhysAddr::from(frame.ppn.get_first_addr().0 + 0x678))
+        );
+        // a neighbour shares the intermediate tables but is not mapped
+        assert!(
+            !page_table
+                .translate(VirtPageNum(0x12346))
+                .unwrap()
+                .is_valid()
+        );
+
+        page_table.unmap(vpn);
+        assert!(!page_table.translate(vpn).unwrap().is_valid());
+        assert!(
+            page_table
+                .translate_va(VirtAddr::from(0x1234_5678))
+                .is_none()
+        );
+    }
+
+    #[test_case]
+    fn translate_range_merges_contiguous_frames() {
+        let mut page_table = PageTable::new();
+        let frame = frame_alloc().unwrap();
+        let ppn = frame.ppn.0;
+        // only the page table is looked at, the frames need not be ours
+        page_table.map(VirtPageNum(0x100), PhysPageNum(ppn), PTEFlags::R);
+        page_table.map(VirtPageNum(0x101), PhysPageNum(ppn + 1), PTEFlags::R);
+        page_table.map(VirtPageNum(0x102), PhysPageNum(ppn + 5), PTEFlags::R);
+
+        let extents = page_table
+            .translate_range(0x10_0800, 3 * PAGE_SIZE - 0x900)
+            .unwrap();
+        assert_eq!(
+            extents,
+            [
+                (PhysAddr::from(ppn * PAGE_SIZE + 0x800), 0x1800),
+                (PhysAddr::from((ppn + 5) * PAGE_SIZE), 0xf00),
+            ]
+        );
+        assert_eq!(page_table.translate_range(0x10_0000, 0), Some(Vec::new()));
+        assert!(
+            page_table
+                .translate_range(0x10_2000, PAGE_SIZE + 1)
+                .is_none()
+        );
+    }
+
+    #[test_case]
+    fn page_table_frames_are_freed_on_drop() {
+        let (_, free_before) = frame_stats();
+        {
+            let mut page_table = PageTable::new();
+            let frame = frame_alloc().unwrap();
+            page_table.map(VirtPageNum(0x1), frame.ppn, PTEFlags::R);
+            page_table.map(VirtPageNum(1 << 20), frame.ppn, PTEFlags::R);
         }
-        start = end_va.bits();
+        assert_eq!(frame_stats().1, free_before);
     }
-    v
 }
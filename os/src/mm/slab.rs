@@ -0,0 +1,156 @@
+//! Slab caches for small kernel heap allocations.
+//!
+//! Most kernel allocations are small and of a few sizes: `BTreeMap` nodes of memory areas
+//! and timers, `Vec`s of line buffers, boxed callbacks. Each of `SIZE_CLASSES` gets a cache
+//! that carves page-sized slabs from the buddy heap into equal objects and keeps freed
+//! objects on a free list. Allocating and freeing one is then a list push or pop, and the
+//! churn of small objects no longer splits and merges the buddy heap's blocks. Slabs stay
+//! with their cache once allocated.
+//!
+//! Larger allocations go to the buddy heap directly.
+
+use crate::config::PAGE_SIZE;
+use core::alloc::Layout;
+use core::ptr::null_mut;
+
+/// Object sizes of the caches, each a power of two so objects are aligned to their size.
+pub const SIZE_CLASSES: [usize; 6] = [16, 32, 64, 128, 256, 512];
+
+/// Size and alignment of a slab.
+pub const SLAB_SIZE: usize = PAGE_SIZE;
+
+/// A free object, linking to the next one.
+struct FreeObject {
+    next: *mut FreeObject,
+}
+
+#[derive(Copy, Clone)]
+struct SlabCache {
+    free: *mut FreeObject,
+    stat: SlabStat,
+}
+
+/// Statistics of one cache, layout compatible with user space.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SlabStat {
+    /// Size of the objects in bytes.
+    pub size: usize,
+    /// Slabs the cache took from the heap.
+    pub slabs: usize,
+    /// Objects allocated right now.
+    pub in_use: usize,
+    /// Objects allocated since boot.
+    pub allocs: usize,
+}
+
+pub struct SlabAllocator {
+    caches: [SlabCache; SIZE_CLASSES.len()],
+}
+
+impl SlabAllocator {
+    pub const fn new() -> Self {
+        let mut caches = [SlabCache {
+            free: null_mut(),
+            stat: SlabStat {
+                size: 0,
+                slabs: 0,
+                in_use: 0,
+                allocs: 0,
+            },
+        }; SIZE_CLASSES.len()];
+        let mut i = 0;
+        while i < SIZE_CLASSES.len() {
+            caches[i].stat.size = SIZE_CLASSES[i];
+            i += 1;
+        }
+        Self { caches }
+    }
+
+    /// Returns the cache serving `layout`, `None` if it is too large for any.
+    pub fn class(layout: Layout) -> Option<usize> {
+        let size = layout.size().max(layout.align());
+        SIZE_CLASSES.iter().position(|&class| size <= class)
+    }
+
+    /// Allocate an object of cache `class`, taking a new slab from `new_slab` if the cache
+    /// has no free object.
+    ///
+    /// # Returns
+    /// The object, or null if `new_slab` returned null.
+    pub fn alloc(&mut self, class: usize, new_slab: impl FnOnce() -> *mut u8) -> *mut u8 {
+        let cache = &mut self.caches[class];
+        if cache.free.is_null() {
+            let slab = new_slab();
+            if slab.is_null() {
+                return null_mut();
+            }
+            // thread the free list through the new slab, lowest address first
+            for offset in (0..SLAB_SIZE).step_by(cache.stat.size).rev() {
+                let object = slab.wrapping_add(offset).cast::<FreeObject>();
+                unsafe { object.write(FreeObject { next: cache.free }) };
+                cache.free = object;
+            }
+            cache.stat.slabs += 1;
+        }
+        let object = cache.free;
+        cache.free = unsafe { (*object).next };
+        cache.stat.in_use += 1;
+        cache.stat.allocs += 1;
+        object.cast()
+    }
+
+    /// Return `ptr`, allocated from cache `class`, to it.
+    pub fn dealloc(&mut self, class: usize, ptr: *mut u8) {
+        let cache = &mut self.caches[class];
+        let object = ptr.cast::<FreeObject>();
+        unsafe { object.write(FreeObject { next: cache.free }) };
+        cache.free = object;
+        cache.stat.in_use -= 1;
+    }
+
+    pub fn stats(&self) -> [SlabStat; SIZE_CLASSES.len()] {
+        self.caches.map(|cache| cache.stat)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::alloc::{alloc, dealloc};
+
+    fn slab_layout() -> Layout {
+        Layout::from_size_align(SLAB_SIZE, SLAB_SIZE).unwrap()
+    }
+
+    #[test_case]
+    fn objects_are_reused_and_aligned() {
+        let mut slabs = SlabAllocator::new();
+        let class = SlabAllocator::class(Layout::new::<[u64; 5]>()).unwrap();
+        assert_eq!(SIZE_CLASSES[class], 64);
+        assert_eq!(
+            SlabAllocator::class(Layout::from_size_align(8, 128).unwrap()),
+            Some(3)
+        );
+        assert_eq!(SlabAllocator::class(Layout::new::<[u8; 513]>()), None);
+
+        let slab = unsafe { alloc(slab_layout()) };
+        let a = slabs.alloc(class, || slab);
+        let b = slabs.alloc(class, || panic!("the slab has room"));
+        assert_eq!((a, b), (slab, slab.wrapping_add(64)));
+        slabs.dealloc(class, a);
+        assert_eq!(slabs.alloc(class, || panic!("a is free")), a);
+
+        // fill the slab, the next object needs another one
+        for _ in 2..SLAB_SIZE / 64 {
+            slabs.alloc(class, || panic!("the slab has room"));
+        }
+        assert!(slabs.alloc(class, null_mut).is_null());
+        let stat = slabs.stats()[class];
+        assert_eq!(
+            (stat.size, stat.slabs, stat.in_use),
+            (64, 1, SLAB_SIZE / 64)
+        );
+        unsafe { dealloc(slab, slab_layout()) };
+    }
+}
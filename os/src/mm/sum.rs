@@ -0,0 +1,52 @@
+//! Scoped access to user memory through the user mapping.
+//!
+//! With `sstatus.SUM` clear, any S-mode access to a page with the U bit faults, so a stray
+//! kernel dereference of a user pointer cannot go unnoticed. Code reading or writing user
+//! memory through the user's own mapping sets it for just that access with a `SumGuard`.
+//!
+//! Traps switch to the kernel space, whose page table does not map user memory, so today user
+//! memory is always reached through the physical mapping instead, see `task::current_copy_out`.
+
+use riscv::register::sstatus;
+
+/// Sets `sstatus.SUM` while alive and restores the previous value when dropped.
+pub struct SumGuard {
+    was_set: bool,
+}
+
+impl SumGuard {
+    pub fn new() -> Self {
+        let was_set = sstatus::read().sum();
+        unsafe { sstatus::set_sum() };
+        Self { was_set }
+    }
+}
+
+impl Drop for SumGuard {
+    fn drop(&mut self) {
+        if !self.was_set {
+            unsafe { sstatus::clear_sum() };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn sum_guards_restore_the_previous_value() {
+        unsafe { sstatus::clear_sum() };
+        {
+            let _outer = SumGuard::new();
+            assert!(sstatus::read().sum());
+            {
+                let _inner = SumGuard::new();
+                assert!(sstatus::read().sum());
+            }
+            // the inner guard found SUM set and leaves it set
+            assert!(sstatus::read().sum());
+        }
+        assert!(!sstatus::read().sum());
+    }
+}
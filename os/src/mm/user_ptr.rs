@@ -0,0 +1,108 @@
+//! Pointers into the user part of an address space.
+//!
+//! Syscalls get user memory as `UserPtr`s and `UserSlice`s instead of raw pointers. They are
+//! only addresses, checked to lie below `USER_SPACE_END` when made: the kernel never
+//! dereferences them, it copies through `task::current_copy_in` and `task::current_copy_out`,
+//! which check that the task may access the memory first.
+
+use crate::config::USER_SPACE_END;
+use core::marker::PhantomData;
+use core::mem::size_of;
+
+/// Returns `true` if `size` bytes at `addr` lie in user space.
+fn in_user_space(addr: usize, size: usize) -> bool {
+    addr.checked_add(size)
+        .is_some_and(|end| end <= USER_SPACE_END)
+}
+
+/// Address of a `T` in user space.
+pub struct UserPtr<T> {
+    addr: usize,
+    _marker: PhantomData<*mut T>,
+}
+
+impl<T> UserPtr<T> {
+    /// Returns `None` if the `T` at `addr` does not lie in user space.
+    pub fn new(addr: usize) -> Option<Self> {
+        in_user_space(addr, size_of::<T>()).then_some(Self {
+            addr,
+            _marker: PhantomData,
+        })
+    }
+
+    pub fn addr(self) -> usize {
+        self.addr
+    }
+}
+
+impl<T> Clone for UserPtr<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for UserPtr<T> {}
+
+/// Address and length of an array of `len` `T`s in user space.
+pub struct UserSlice<T> {
+    addr: usize,
+    len: usize,
+    _marker: PhantomData<*mut T>,
+}
+
+impl<T> UserSlice<T> {
+    /// Returns `None` if the `len` `T`s at `addr` do not lie in user space.
+    pub fn new(addr: usize, len: usize) -> Option<Self> {
+        let size = len.checked_mul(size_of::<T>())?;
+        in_user_space(addr, size).then_some(Self {
+            addr,
+            len,
+            _marker: PhantomData,
+        })
+    }
+
+    pub fn addr(self) -> usize {
+        self.addr
+    }
+
+    /// Returns the number of `T`s.
+    pub fn len(self) -> usize {
+        self.len
+    }
+
+    /// The same array as `U`s, for element types that only exist with some features.
+    ///
+    /// Returns `None` if the `U`s do not lie in user space.
+    pub fn cast<U>(self) -> Option<UserSlice<U>> {
+        UserSlice::new(self.addr, self.len)
+    }
+}
+
+impl<T> Clone for UserSlice<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for UserSlice<T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn user_pointers_stay_in_user_space() {
+        assert!(UserPtr::<u64>::new(USER_SPACE_END - 8).is_some());
+        assert!(UserPtr::<u64>::new(USER_SPACE_END - 4).is_none());
+        assert!(UserPtr::<u64>::new(usize::MAX).is_none());
+
+        assert_eq!(
+            UserSlice::<u64>::new(0x1000, 4).map(UserSlice::len),
+            Some(4)
+        );
+        assert!(UserSlice::<u64>::new(0x1000, usize::MAX / 4).is_none());
+        let bytes = UserSlice::<u8>::new(USER_SPACE_END - 8, 8).unwrap();
+        assert!(bytes.cast::<u8>().is_some());
+        assert!(bytes.cast::<u64>().is_none());
+    }
+}
@@ -0,0 +1,39 @@
+//! Kernel oops: survive a panic raised while servicing a syscall for a user task.
+//!
+//! There is no unwinding, so everything borrowed at the panic stays borrowed. The task is
+//! only killed if no `UPSafeCell` was borrowed and the memory manager was not in use; panics
+//! holding any of them, in kthreads, in trap handling outside syscalls or during boot still
+//! halt the system.
+//!
+//! Syscalls reject bad user pointers with -1, an oops is only for kernel bugs.
+
+use crate::boot::{self, BootStage};
+use crate::mm;
+use crate::sync;
+use crate::task::{EXIT_KILLED, current_oops, current_task_label, exit_current_and_run_next};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use log::error;
+
+/// Number of oopses since boot.
+static OOPS_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Kill the current task instead of halting, if the panic allows it.
+///
+/// Returns only if the panic is fatal.
+pub fn try_recover() {
+    // before tasks run, even looking at the task manager would build it
+    if !boot::reached(BootStage::Tasks) || mm::is_busy() || sync::any_borrowed() {
+        return;
+    }
+    if current_oops().is_none() {
+        return;
+    }
+
+    let count = OOPS_COUNT.fetch_add(1, Ordering::Relaxed) + 1;
+    error!(
+        "[kernel] Oops #{} in syscall of {}, kernel killed it.",
+        count,
+        current_task_label()
+    );
+    exit_current_and_run_next(EXIT_KILLED);
+}
@@ -0,0 +1,49 @@
+//! Sampling profiler, built with the `profiler` feature.
+//!
+//! Every timer interrupt records the interrupted `sepc` into a ring buffer of the current
+//! task, which the task reads back with `sys_get_profile`. Interrupts are disabled in S-mode,
+//! so only user pcs are ever sampled.
+
+use crate::sync::UPSafeCell;
+use alloc::collections::btree_map::BTreeMap;
+use alloc::vec::Vec;
+use lazy_static::*;
+
+/// Samples kept per task, older ones are overwritten.
+const SAMPLES_PER_TASK: usize = 256;
+
+/// Ring buffer of the most recent samples of one task.
+#[derive(Default)]
+struct SampleRing {
+    samples: Vec<usize>,
+    /// Index of the oldest sample once the ring is full.
+    next: usize,
+}
+
+lazy_static! {
+    /// Sample rings keyed by task id.
+    static ref PROFILES: UPSafeCell<BTreeMap<usize, SampleRing>> =
+        unsafe { UPSafeCell::new(BTreeMap::new()) };
+}
+
+/// Record that task `task_id` was interrupted at `pc`.
+pub fn record(task_id: usize, pc: usize) {
+    let mut profiles = PROFILES.exclusive_access();
+    let ring = profiles.entry(task_id).or_default();
+    if ring.samples.len() < SAMPLES_PER_TASK {
+        ring.samples.push(pc);
+    } else {
+        ring.samples[ring.next] = pc;
+        ring.next = (ring.next + 1) % SAMPLES_PER_TASK;
+    }
+}
+
+/// Returns the samples of task `task_id`, oldest first.
+pub fn samples(task_id: usize) -> Vec<usize> {
+    let profiles = PROFILES.exclusive_access();
+    let Some(ring) = profiles.get(&task_id) else {
+        return Vec::new();
+    };
+    let (newer, older) = ring.samples.split_at(ring.next);
+    older.iter().chain(newer).copied().collect()
+}
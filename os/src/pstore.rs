@@ -0,0 +1,93 @@
+//! Kernel log kept across warm reboots.
+//!
+//! Every kernel log message also goes into a ring buffer in `PSTORE_BASE`, physical memory
+//! the frame allocator never hands out. A warm reboot, e.g. through `sys_reboot`, resets the
+//! machine without clearing RAM, so the next boot finds the log of this one there and prints
+//! it as the previous boot log before starting its own. That helps after crashes that took
+//! the console down with them. After a cold boot the region holds no valid header and is
+//! ignored.
+
+use crate::board::{PSTORE_BASE, PSTORE_SIZE};
+use crate::console;
+use crate::mm::{PhysAddr, phys_to_virt};
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicBool, Ordering};
+use log::info;
+
+/// "pstore01" in little endian, marks a header written by a previous boot.
+const MAGIC: u64 = 0x3130_6572_6f74_7370;
+
+/// Start of the region, followed by the ring buffer.
+#[repr(C)]
+struct Header {
+    magic: u64,
+    /// Warm reboots since the last cold boot.
+    boot: u64,
+    /// Bytes ever written to the ring; the ring holds the last `CAPACITY` of them.
+    written: u64,
+}
+
+const CAPACITY: usize = PSTORE_SIZE - size_of::<Header>();
+
+/// Set once the log of the previous boot is printed and the ring may be written.
+static READY: AtomicBool = AtomicBool::new(false);
+
+fn header() -> &'static mut Header {
+    unsafe { &mut *(phys_to_virt(PhysAddr(PSTORE_BASE)).0 as *mut Header) }
+}
+
+fn ring() -> &'static mut [u8] {
+    let start = phys_to_virt(PhysAddr(PSTORE_BASE)).0 + size_of::<Header>();
+    unsafe { core::slice::from_raw_parts_mut(start as *mut u8, CAPACITY) }
+}
+
+/// Print the log a previous boot left, if any, then start recording this boot's.
+pub fn init() {
+    let header = header();
+    let boot = if header.magic == MAGIC {
+        let written = header.written as usize;
+        let ring = ring();
+        info!(
+            "[kernel] previous boot log (boot {}, {} bytes):",
+            header.boot,
+            written.min(CAPACITY)
+        );
+        let end = written % CAPACITY;
+        if written >= CAPACITY {
+            // wrapped around, the oldest bytes follow the newest
+            console::write_bytes(&ring[end..]);
+        }
+        console::write_bytes(&ring[..end]);
+        info!("[kernel] end of previous boot log");
+        header.boot + 1
+    } else {
+        0
+    };
+    *header = Header {
+        magic: MAGIC,
+        boot,
+        written: 0,
+    };
+    READY.store(true, Ordering::Release);
+}
+
+struct Ring;
+
+impl Write for Ring {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let header = header();
+        let ring = ring();
+        for &byte in s.as_bytes() {
+            ring[header.written as usize % CAPACITY] = byte;
+            header.written += 1;
+        }
+        Ok(())
+    }
+}
+
+/// Append a log message, a no-op until `init` ran.
+pub fn log(args: fmt::Arguments) {
+    if READY.load(Ordering::Acquire) {
+        let _ = Ring.write_fmt(args);
+    }
+}
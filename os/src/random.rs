@@ -0,0 +1,38 @@
+//! Kernel pseudo random numbers.
+//!
+//! A xorshift64* generator seeded from the cycle counter on first use. There is no entropy
+//! source on the board, so the output is cheap and well distributed but predictable: fine for
+//! randomized layouts and tests, not for cryptography.
+
+use crate::sync::UPSafeCell;
+use crate::timer::get_time;
+use lazy_static::*;
+
+lazy_static! {
+    static ref RNG_STATE: UPSafeCell<u64> = unsafe { UPSafeCell::new(seed()) };
+}
+
+/// Mix the cycle counter into a non-zero seed (splitmix64 finalizer).
+fn seed() -> u64 {
+    let mut z = get_time().wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    (z ^ (z >> 31)) | 1
+}
+
+/// Returns the next pseudo random `u64`.
+pub fn next_u64() -> u64 {
+    let mut state = RNG_STATE.exclusive_access();
+    *state ^= *state >> 12;
+    *state ^= *state << 25;
+    *state ^= *state >> 27;
+    state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+}
+
+/// Fill `buf` with pseudo random bytes.
+pub fn fill_bytes(buf: &mut [u8]) {
+    for chunk in buf.chunks_mut(8) {
+        let bytes = next_u64().to_le_bytes();
+        chunk.copy_from_slice(&bytes[..chunk.len()]);
+    }
+}
@@ -0,0 +1,60 @@
+//! Rate-limited logging.
+//!
+//! A user program faulting in a loop, or hammering a syscall with bad arguments, must not
+//! flood the console. `log_ratelimited!` lets each call site print `RATELIMIT_BURST` messages
+//! per `RATELIMIT_INTERVAL_MS`, then counts what it drops and reports the count with the next
+//! message it prints.
+
+use crate::config::{RATELIMIT_BURST, RATELIMIT_INTERVAL_MS};
+use crate::timer::get_time_ms;
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+/// Rate limiting state of one call site.
+pub struct RateLimit {
+    interval_start: AtomicU64,
+    printed: AtomicUsize,
+    suppressed: AtomicUsize,
+}
+
+impl RateLimit {
+    pub const fn new() -> Self {
+        Self {
+            interval_start: AtomicU64::new(0),
+            printed: AtomicUsize::new(0),
+            suppressed: AtomicUsize::new(0),
+        }
+    }
+
+    /// Decide whether the next message may be printed.
+    ///
+    /// # Returns
+    /// `Some(n)` if it may, with `n` the number of messages suppressed since the last printed
+    /// one, or `None` if it has to be dropped.
+    pub fn allow(&self) -> Option<usize> {
+        let now = get_time_ms();
+        if now - self.interval_start.load(Ordering::Relaxed) >= RATELIMIT_INTERVAL_MS {
+            self.interval_start.store(now, Ordering::Relaxed);
+            self.printed.store(0, Ordering::Relaxed);
+        }
+        if self.printed.fetch_add(1, Ordering::Relaxed) < RATELIMIT_BURST {
+            Some(self.suppressed.swap(0, Ordering::Relaxed))
+        } else {
+            self.suppressed.fetch_add(1, Ordering::Relaxed);
+            None
+        }
+    }
+}
+
+/// Log like `log::log!`, but at most `RATELIMIT_BURST` times per interval for this call site.
+#[macro_export]
+macro_rules! log_ratelimited {
+    ($level: expr, $($arg: tt)+) => {{
+        static RATELIMIT: $crate::ratelimit::RateLimit = $crate::ratelimit::RateLimit::new();
+        if let Some(suppressed) = RATELIMIT.allow() {
+            if suppressed > 0 {
+                log::log!($level, "[kernel] {} similar messages suppressed", suppressed);
+            }
+            log::log!($level, $($arg)+);
+        }
+    }};
+}
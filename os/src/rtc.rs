@@ -0,0 +1,20 @@
+//! The goldfish RTC of the `virt` machine.
+//!
+//! It counts nanoseconds since the Unix epoch. Reading `TIME_LOW` latches the upper half into
+//! `TIME_HIGH`, so the two reads form one consistent value.
+
+use crate::board::GOLDFISH_RTC;
+
+const TIME_LOW: usize = 0x00;
+const TIME_HIGH: usize = 0x04;
+
+/// Returns the wall clock time in nanoseconds since the Unix epoch.
+///
+/// Works with or without paging, the device is identity-mapped in the kernel space.
+pub fn read_ns() -> u64 {
+    unsafe {
+        let low = ((GOLDFISH_RTC + TIME_LOW) as *const u32).read_volatile();
+        let high = ((GOLDFISH_RTC + TIME_HIGH) as *const u32).read_volatile();
+        (high as u64) << 32 | low as u64
+    }
+}
@@ -16,3 +16,11 @@ pub fn shutdown(failure: bool) -> ! {
     }
     unreachable!()
 }
+
+/// Ask the SBI for a warm reboot, which keeps RAM as it is.
+///
+/// Returns if the SBI implementation cannot reboot.
+pub fn warm_reboot() {
+    use sbi_rt::{NoReason, WarmReboot, system_reset};
+    system_reset(WarmReboot, NoReason);
+}
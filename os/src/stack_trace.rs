@@ -1,7 +1,59 @@
 use core::arch::asm;
 
+use crate::config::{KERNEL_STACK_SIZE, PAGE_SIZE, TRAMPOLINE_ADDR, kernel_stack_pos};
 use log::trace;
 
+/// Look up the kernel function containing `addr` in the symbol table embedded by build.rs.
+///
+/// # Returns
+/// The function name and the offset of `addr` into it, or `None` if the table is empty
+/// (first build) or `addr` lies before the first symbol.
+pub fn lookup_symbol(addr: usize) -> Option<(&'static str, usize)> {
+    // SAFETY: `_ksyms` is generated by build.rs: the number of symbols, their sorted
+    // addresses, then pointers to their NUL terminated names.
+    unsafe extern "C" {
+        fn _ksyms();
+    }
+
+    let ksyms = _ksyms as usize as *const usize;
+    let (addrs, names) = unsafe {
+        let num = ksyms.read_volatile();
+        (
+            core::slice::from_raw_parts(ksyms.add(1), num),
+            core::slice::from_raw_parts(ksyms.add(1 + num), num),
+        )
+    };
+
+    // last symbol starting at or before addr
+    let idx = addrs
+        .partition_point(|&start| start <= addr)
+        .checked_sub(1)?;
+    let name = unsafe { core::ffi::CStr::from_ptr(names[idx] as *const core::ffi::c_char) };
+    Some((name.to_str().unwrap_or("?"), addr - addrs[idx]))
+}
+
+/// Print `addr` together with the name of the function containing it, if known.
+pub fn print_symbolized(addr: usize) {
+    match lookup_symbol(addr) {
+        Some((name, offset)) => emergency_println!("0x{:016x} <{}+{:#x}>", addr, name, offset),
+        None => emergency_println!("0x{:016x}", addr),
+    }
+}
+
+/// Bounds `(bottom, top)` of the stack `fp` points into: the boot stack or a kernel stack.
+fn stack_bounds(fp: usize) -> (usize, usize) {
+    unsafe extern "C" {
+        fn boot_stack_lower_bound();
+        fn boot_stack_top();
+    }
+
+    let boot_stack = (boot_stack_lower_bound as usize, boot_stack_top as usize);
+    if (boot_stack.0..=boot_stack.1).contains(&fp) {
+        return boot_stack;
+    }
+    kernel_stack_pos(TRAMPOLINE_ADDR.wrapping_sub(fp) / (KERNEL_STACK_SIZE + PAGE_SIZE))
+}
+
 // Print kernel stack is unsafe
 pub unsafe fn print_stack_trace() {
     let mut fp: *const usize;
@@ -10,8 +62,15 @@ pub unsafe fn print_stack_trace() {
     unsafe {
         asm!("mv {}, fp", out(reg) fp);
     }
-    println!("== Begin stack trace ==");
-    while !fp.is_null() {
+    // the outermost frame pointer may be garbage (e.g. the user's fp saved on trap entry),
+    // so never leave the stack the walk started on
+    let (bottom, top) = stack_bounds(fp as usize);
+    let in_stack = |fp: *const usize| {
+        let fp = fp as usize;
+        fp % size_of::<usize>() == 0 && bottom + 2 * size_of::<usize>() <= fp && fp <= top
+    };
+    emergency_println!("== Begin stack trace ==");
+    while in_stack(fp) {
         // NOTE: function call prologue
         // see: https://rcore-os.cn/rCore-Tutorial-Book-v3/chapter1/5support-func-call.html#term-calling-convention
         // addi sp, sp, -frame_size
@@ -26,7 +85,8 @@ pub unsafe fn print_stack_trace() {
         }
 
         trace!("0x{:016x}, fp = 0x{:016x}", saved_ra, saved_fp);
+        print_symbolized(saved_ra);
         fp = saved_fp as *const usize;
     }
-    println!("== End stack trace ==");
+    emergency_println!("== End stack trace ==");
 }
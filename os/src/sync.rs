@@ -1,7 +1,17 @@
-use core::cell::{RefCell, RefMut};
+use core::cell::{Cell, RefCell, RefMut};
+use core::ops::{Deref, DerefMut};
+use core::panic::Location;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+// Number of UPSafeCells borrowed right now, so a panic can tell whether
+// it interrupted code holding one
+static BORROWED_CELLS: AtomicUsize = AtomicUsize::new(0);
 
 pub struct UPSafeCell<T> {
     inner: RefCell<T>,
+    // Caller of the last exclusive_access/try_access that got the data, so
+    // a double borrow panic can tell where the first borrow came from
+    borrowed_at: Cell<Option<&'static Location<'static>>>,
 }
 
 unsafe impl<T> Sync for UPSafeCell<T> {}
@@ -12,12 +22,90 @@ impl<T> UPSafeCell<T> {
     pub unsafe fn new(value: T) -> Self {
         UPSafeCell {
             inner: RefCell::new(value),
+            borrowed_at: Cell::new(None),
         }
     }
 
     // Always get mutable reference, so it will panic
-    // if the data has been borrow twice
-    pub fn exclusive_access(&self) -> RefMut<'_, T> {
-        self.inner.borrow_mut()
+    // if the data has been borrow twice, naming both callers
+    #[track_caller]
+    pub fn exclusive_access(&self) -> UPSafeRefMut<'_, T> {
+        match self.try_access() {
+            Some(inner) => inner,
+            None => match self.borrowed_at.get() {
+                Some(at) => panic!("UPSafeCell already borrowed at {}", at),
+                None => panic!("UPSafeCell already borrowed"),
+            },
+        }
+    }
+
+    // Get mutable reference, or None if the data is borrowed already, for
+    // paths that can do without it, e.g. code a panic may have interrupted
+    #[track_caller]
+    pub fn try_access(&self) -> Option<UPSafeRefMut<'_, T>> {
+        let inner = self.inner.try_borrow_mut().ok()?;
+        self.borrowed_at.set(Some(Location::caller()));
+        BORROWED_CELLS.fetch_add(1, Ordering::Relaxed);
+        Some(UPSafeRefMut(inner))
+    }
+
+    // Check whether the data is borrowed right now, e.g. by code a panic
+    // interrupted
+    pub fn is_borrowed(&self) -> bool {
+        self.inner.try_borrow_mut().is_err()
+    }
+
+    // Caller of the last borrow, the one holding the data if it is borrowed
+    pub fn borrowed_at(&self) -> Option<&'static Location<'static>> {
+        self.borrowed_at.get()
+    }
+}
+
+// Mutable borrow of the data of a UPSafeCell, counted in BORROWED_CELLS
+pub struct UPSafeRefMut<'a, T>(RefMut<'a, T>);
+
+impl<T> Deref for UPSafeRefMut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for UPSafeRefMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T> Drop for UPSafeRefMut<'_, T> {
+    fn drop(&mut self) {
+        BORROWED_CELLS.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+// Check whether any UPSafeCell is borrowed right now, e.g. by code a panic
+// interrupted
+pub fn any_borrowed() -> bool {
+    BORROWED_CELLS.load(Ordering::Relaxed) != 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn try_access_fails_while_borrowed() {
+        let cell = unsafe { UPSafeCell::new(0) };
+        let guard = cell.exclusive_access();
+        let line = line!() - 1;
+        assert!(cell.try_access().is_none());
+        assert!(cell.is_borrowed());
+        assert!(any_borrowed());
+        let at = cell.borrowed_at().unwrap();
+        assert_eq!((at.file(), at.line()), (file!(), line));
+        drop(guard);
+        *cell.try_access().unwrap() += 1;
+        assert_eq!(*cell.exclusive_access(), 1);
     }
 }
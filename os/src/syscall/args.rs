@@ -0,0 +1,54 @@
+//! Typed decoding of raw syscall arguments
+//!
+//! A syscall passes up to six arguments in a0-a5 as plain register values. `SyscallArgs`
+//! names what each one is when `syscall` dispatches, so handlers receive file descriptors,
+//! user pointers and lengths instead of indexing a raw array.
+//!
+//! File descriptors and user pointers are checked while decoding: the accessors return `None`
+//! for an fd the task cannot have or memory outside user space, and `syscall` fails the call
+//! with -1 before any handler runs.
+
+use crate::mm::{UserPtr, UserSlice};
+
+/// A file descriptor argument, below `FD_COUNT`.
+pub type Fd = usize;
+
+/// Number of file descriptors of every task: stdin, stdout and stderr.
+const FD_COUNT: usize = 3;
+
+/// The raw a0-a5 of a syscall.
+#[derive(Copy, Clone, Debug)]
+pub struct SyscallArgs(pub [usize; 6]);
+
+impl SyscallArgs {
+    /// Argument `i` as a plain number, e.g. a count, an address or flags.
+    pub fn usize(&self, i: usize) -> usize {
+        self.0[i]
+    }
+
+    /// Argument `i` as a C `int`, e.g. an exit code; the upper 32 bits are ignored.
+    pub fn i32(&self, i: usize) -> i32 {
+        self.0[i] as i32
+    }
+
+    /// Argument `i` as a file descriptor, `None` if the task cannot have it.
+    pub fn fd(&self, i: usize) -> Option<Fd> {
+        (self.0[i] < FD_COUNT).then_some(self.0[i])
+    }
+
+    /// Argument `i` as a length in bytes or elements.
+    pub fn len(&self, i: usize) -> usize {
+        self.0[i]
+    }
+
+    /// Argument `i` as a user pointer to a `T`, `None` if the `T` would not lie in user space.
+    /// Whether it is mapped is checked when the kernel copies through it.
+    pub fn ptr<T>(&self, i: usize) -> Option<UserPtr<T>> {
+        UserPtr::new(self.0[i])
+    }
+
+    /// Arguments `i` and `len` as a user array of `T`s and its length, see `ptr`.
+    pub fn slice<T>(&self, i: usize, len: usize) -> Option<UserSlice<T>> {
+        UserSlice::new(self.0[i], self.0[len])
+    }
+}
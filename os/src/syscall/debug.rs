@@ -0,0 +1,180 @@
+//! Debug syscalls.
+//!
+//! They are numbered from 1000, outside the range used by Linux.
+
+use crate::mm::UserSlice;
+
+/// Print the syscall latency histograms to the console.
+///
+/// # Returns
+/// 0 on success, or -1 if the kernel was built without the `syscall_latency` feature.
+pub fn sys_dump_syscall_latency() -> isize {
+    #[cfg(feature = "syscall_latency")]
+    {
+        super::latency::dump();
+        0
+    }
+    #[cfg(not(feature = "syscall_latency"))]
+    {
+        -1
+    }
+}
+
+/// Copy the profiler samples of the current task, oldest first, to `buf`.
+///
+/// # Returns
+/// The number of samples copied, or -1 if `buf` is not writable by the task or the kernel was
+/// built without the `profiler` feature.
+pub fn sys_get_profile(buf: UserSlice<usize>) -> isize {
+    #[cfg(feature = "profiler")]
+    {
+        use crate::task::{current_copy_out_slice, current_task_id};
+
+        let samples = crate::profiler::samples(current_task_id());
+        let count = samples.len().min(buf.len());
+        if !current_copy_out_slice(buf, &samples[..count]) {
+            return -1;
+        }
+        count as isize
+    }
+    #[cfg(not(feature = "profiler"))]
+    {
+        -1
+    }
+}
+
+/// Start recording the kernel coverage of the current task's syscalls into a buffer of
+/// `capacity` pcs, or stop recording and free the buffer if `capacity` is 0.
+///
+/// # Returns
+/// 0 on success, or -1 if the kernel was built without the `kcov` feature.
+pub fn sys_kcov_enable(capacity: usize) -> isize {
+    #[cfg(feature = "kcov")]
+    {
+        use crate::task::current_task_id;
+
+        if capacity == 0 {
+            crate::kcov::disable(current_task_id());
+        } else {
+            crate::kcov::enable(current_task_id(), capacity);
+        }
+        0
+    }
+    #[cfg(not(feature = "kcov"))]
+    {
+        -1
+    }
+}
+
+/// Move the pcs recorded for the current task since the last collection to `buf`.
+///
+/// # Returns
+/// The number of pcs copied, or -1 if `buf` is not writable by the task or the kernel was
+/// built without the `kcov` feature.
+pub fn sys_kcov_collect(buf: UserSlice<usize>) -> isize {
+    #[cfg(feature = "kcov")]
+    {
+        use crate::task::{current_copy_out_slice, current_task_id};
+
+        let pcs = crate::kcov::collect(current_task_id());
+        let count = pcs.len().min(buf.len());
+        if !current_copy_out_slice(buf, &pcs[..count]) {
+            return -1;
+        }
+        count as isize
+    }
+    #[cfg(not(feature = "kcov"))]
+    {
+        -1
+    }
+}
+
+/// Make frame and heap allocations of the current task's later syscalls fail with the given
+/// probabilities, in permille. `0, 0` turns fault injection off.
+///
+/// # Returns
+/// 0 on success, or -1 if a probability exceeds 1000 or the kernel was built without the
+/// `fault_injection` feature.
+pub fn sys_fault_inject(frame_permille: usize, heap_permille: usize) -> isize {
+    #[cfg(feature = "fault_injection")]
+    {
+        if frame_permille > 1000 || heap_permille > 1000 {
+            return -1;
+        }
+        crate::fault_inject::configure(
+            crate::task::current_task_id(),
+            frame_permille,
+            heap_permille,
+        );
+        0
+    }
+    #[cfg(not(feature = "fault_injection"))]
+    {
+        -1
+    }
+}
+
+/// Move as many of the oldest scheduler trace events as fit to `buf`, an array of
+/// `TraceRecord`s.
+///
+/// # Returns
+/// The number of events copied, or -1 if `buf` is not writable by the task or the kernel was
+/// built without the `sched_trace` feature.
+pub fn sys_trace_collect(buf: UserSlice<u8>) -> isize {
+    #[cfg(feature = "sched_trace")]
+    {
+        use crate::task::current_copy_out_slice;
+        use crate::trace::TraceRecord;
+
+        let Some(buf) = buf.cast::<TraceRecord>() else {
+            return -1;
+        };
+        let records = crate::trace::collect(buf.len());
+        if !current_copy_out_slice(buf, &records) {
+            return -1;
+        }
+        records.len() as isize
+    }
+    #[cfg(not(feature = "sched_trace"))]
+    {
+        -1
+    }
+}
+
+/// Move as many of the oldest accounting records of exited apps as fit to `buf`, an array of
+/// `AcctRecord`s.
+///
+/// # Returns
+/// The number of records copied, or -1 if `buf` is not writable by the task or the kernel was
+/// built without the `acct` feature.
+pub fn sys_acct_collect(buf: UserSlice<u8>) -> isize {
+    #[cfg(feature = "acct")]
+    {
+        use crate::acct::AcctRecord;
+        use crate::task::current_copy_out_slice;
+
+        let Some(buf) = buf.cast::<AcctRecord>() else {
+            return -1;
+        };
+        let records = crate::acct::collect(buf.len());
+        if !current_copy_out_slice(buf, &records) {
+            return -1;
+        }
+        records.len() as isize
+    }
+    #[cfg(not(feature = "acct"))]
+    {
+        -1
+    }
+}
+
+/// Fault in the kernel on purpose, with a load from address 0, to exercise the oops path
+/// that kills the calling task, see `oops`.
+///
+/// # Returns
+/// Does not return, unless the oops path is broken.
+pub fn sys_oops() -> isize {
+    // nothing is borrowed here, so the kernel can recover
+    unsafe { core::arch::asm!("ld zero, 0(zero)") };
+    -1
+}
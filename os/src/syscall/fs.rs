@@ -1,20 +1,46 @@
-use crate::mm::translated_byte_buffer;
-use crate::task::current_user_token;
+use super::args::Fd;
+use crate::console::write_bytes;
+use crate::mm::UserSlice;
+use crate::task::{current_stdout_flush, current_stdout_write, current_translated_byte_buffer};
+use crate::trap::cond_resched;
+use log::Level;
 
-const FD_STDOUT: usize = 1;
+const FD_STDOUT: Fd = 1;
+const FD_STDERR: Fd = 2;
 
-/// write buf of length `len`  to a file with `fd`
-pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
+/// write `buf` to a file with `fd`
+///
+/// stdout is line buffered per task, stderr goes to the console right away after any
+/// pending stdout output of the task.
+///
+/// # Returns
+/// The length of `buf`, or -1 for an unsupported fd or if `buf` is not readable by the task.
+pub fn sys_write(fd: Fd, buf: UserSlice<u8>) -> isize {
     match fd {
         FD_STDOUT => {
-            let buffers = translated_byte_buffer(current_user_token(), buf, len);
+            let Some(buffers) = current_translated_byte_buffer(buf, false) else {
+                return -1;
+            };
             for buffer in buffers {
-                print!("{}", core::str::from_utf8(buffer).unwrap());
+                current_stdout_write(buffer);
+                cond_resched();
             }
-            len as isize
+            buf.len() as isize
+        }
+        FD_STDERR => {
+            let Some(buffers) = current_translated_byte_buffer(buf, false) else {
+                return -1;
+            };
+            current_stdout_flush();
+            for buffer in buffers {
+                write_bytes(buffer);
+                cond_resched();
+            }
+            buf.len() as isize
         }
         _ => {
-            panic!("Unsupported fd in sys_write!");
+            log_ratelimited!(Level::Warn, "[kernel] sys_write: unsupported fd {}", fd);
+            -1
         }
     }
 }
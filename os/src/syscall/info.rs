@@ -0,0 +1,149 @@
+use crate::config::{BOARD_NAME, NUM_HARTS};
+use crate::mm::{SlabStat, UserPtr, UserSlice, frame_stats, heap_stats, slab_stats};
+use crate::random;
+use crate::task::{
+    current_copy_out, current_copy_out_slice, current_translated_byte_buffer, task_stats,
+};
+use crate::timer::{get_time_ms, ticks};
+use crate::trap::{cond_resched, nested_interrupts};
+use crate::version::{BUILD_TIME, MACHINE, OS_NAME, OS_VERSION};
+
+/// Length of each `UtsName` string, including the terminating NUL.
+const UTS_LEN: usize = 65;
+
+/// System identification returned by `sys_uname`, layout compatible with user space.
+///
+/// Strings are NUL terminated and truncated to fit.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct UtsName {
+    /// Operating system name.
+    pub sysname: [u8; UTS_LEN],
+    /// Board the kernel runs on.
+    pub nodename: [u8; UTS_LEN],
+    /// Kernel version (`git describe`).
+    pub release: [u8; UTS_LEN],
+    /// Build time.
+    pub version: [u8; UTS_LEN],
+    /// Machine architecture.
+    pub machine: [u8; UTS_LEN],
+    /// Number of harts.
+    pub harts: usize,
+}
+
+/// Copy `s` into a NUL terminated `UtsName` field.
+fn uts_field(s: &str) -> [u8; UTS_LEN] {
+    let mut field = [0u8; UTS_LEN];
+    let len = s.len().min(UTS_LEN - 1);
+    field[..len].copy_from_slice(&s.as_bytes()[..len]);
+    field
+}
+
+/// Fill `buf` with the name, version, build time, board and hart count of the system.
+pub fn sys_uname(buf: UserPtr<UtsName>) -> isize {
+    let uts = UtsName {
+        sysname: uts_field(OS_NAME),
+        nodename: uts_field(BOARD_NAME),
+        release: uts_field(OS_VERSION),
+        version: uts_field(BUILD_TIME),
+        machine: uts_field(MACHINE),
+        harts: NUM_HARTS,
+    };
+    if current_copy_out(buf, &uts) { 0 } else { -1 }
+}
+
+/// Fill `buf` with random bytes.
+///
+/// The bytes come from the kernel's non-cryptographic generator, see `random`. `flags` is
+/// accepted for compatibility and ignored: the generator never blocks.
+///
+/// # Returns
+/// The number of bytes written, always the length of `buf`, or -1 if `buf` is not writable by the task.
+pub fn sys_getrandom(buf: UserSlice<u8>, _flags: usize) -> isize {
+    let Some(buffers) = current_translated_byte_buffer(buf, true) else {
+        return -1;
+    };
+    for chunk in buffers {
+        random::fill_bytes(chunk);
+        cond_resched();
+    }
+    buf.len() as isize
+}
+
+/// System status returned by `sys_sysinfo`, layout compatible with user space.
+#[repr(C)]
+#[derive(Copy, Clone, Default)]
+pub struct SysInfo {
+    /// Milliseconds since boot.
+    pub uptime_ms: usize,
+    /// Physical frames managed by the frame allocator.
+    pub total_frames: usize,
+    /// Physical frames that are free.
+    pub free_frames: usize,
+    /// Kernel heap size in bytes.
+    pub heap_total: usize,
+    /// Kernel heap bytes allocated.
+    pub heap_used: usize,
+    /// Tasks ready to run.
+    pub tasks_ready: usize,
+    /// Tasks running.
+    pub tasks_running: usize,
+    /// Tasks that exited.
+    pub tasks_exited: usize,
+    /// Tasks blocked, e.g. sleeping.
+    pub tasks_blocked: usize,
+    /// Kthreads, also counted in their state.
+    pub kthreads: usize,
+    /// Number of harts.
+    pub harts: usize,
+    /// Timer interrupts handled by the (only) hart.
+    pub timer_ticks: usize,
+    /// Switches between different tasks on the (only) hart.
+    pub context_switches: usize,
+    /// Of those, switches away from tasks that yielded or slept.
+    pub voluntary_switches: usize,
+    /// Of those, switches away from tasks the timer preempted.
+    pub involuntary_switches: usize,
+    /// Timer interrupts that arrived while the kernel ran a syscall.
+    pub nested_interrupts: usize,
+}
+
+/// Fill `buf` with uptime, memory, task and scheduler statistics.
+pub fn sys_sysinfo(buf: UserPtr<SysInfo>) -> isize {
+    let (total_frames, free_frames) = frame_stats();
+    let (heap_total, heap_used) = heap_stats();
+    let tasks = task_stats();
+    let info = SysInfo {
+        uptime_ms: get_time_ms() as usize,
+        total_frames,
+        free_frames,
+        heap_total,
+        heap_used,
+        tasks_ready: tasks.ready,
+        tasks_running: tasks.running,
+        tasks_exited: tasks.exited,
+        tasks_blocked: tasks.blocked,
+        kthreads: tasks.kthreads,
+        harts: NUM_HARTS,
+        timer_ticks: ticks(),
+        context_switches: tasks.context_switches,
+        voluntary_switches: tasks.voluntary_switches,
+        involuntary_switches: tasks.involuntary_switches,
+        nested_interrupts: nested_interrupts(),
+    };
+    if current_copy_out(buf, &info) { 0 } else { -1 }
+}
+
+/// Copy the statistics of the kernel heap's slab caches to `buf`, smallest objects first.
+///
+/// # Returns
+/// The number of caches, which may exceed the length of `buf`; only the first ones that fit
+/// are copied. -1 if `buf` is not writable by the task.
+pub fn sys_slab_info(buf: UserSlice<SlabStat>) -> isize {
+    let stats = slab_stats();
+    let count = stats.len().min(buf.len());
+    if !current_copy_out_slice(buf, &stats[..count]) {
+        return -1;
+    }
+    stats.len() as isize
+}
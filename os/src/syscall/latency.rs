@@ -0,0 +1,59 @@
+//! Per-syscall latency histograms, built with the `syscall_latency` feature.
+//!
+//! Every syscall that reaches its handler is timed with the cycle counter from dispatch to
+//! return, so calls that give up the CPU (`sys_yield`, `sys_nanosleep`) include the time
+//! other tasks ran meanwhile.
+//! Bucket `i` of a histogram counts calls that took `[2^i, 2^(i+1))` cycles.
+
+use crate::sync::UPSafeCell;
+use alloc::collections::btree_map::BTreeMap;
+use lazy_static::*;
+
+const NUM_BUCKETS: usize = 32;
+
+#[derive(Default)]
+struct Histogram {
+    buckets: [u64; NUM_BUCKETS],
+    count: u64,
+    total: u64,
+    max: u64,
+}
+
+lazy_static! {
+    /// Histograms keyed by syscall id.
+    static ref SYSCALL_LATENCY: UPSafeCell<BTreeMap<usize, Histogram>> =
+        unsafe { UPSafeCell::new(BTreeMap::new()) };
+}
+
+/// Record that syscall `syscall_id` took `cycles` cycles.
+pub fn record(syscall_id: usize, cycles: u64) {
+    let bucket =
+        ((u64::BITS - cycles.leading_zeros()).saturating_sub(1) as usize).min(NUM_BUCKETS - 1);
+    let mut latency = SYSCALL_LATENCY.exclusive_access();
+    let histogram = latency.entry(syscall_id).or_default();
+    histogram.buckets[bucket] += 1;
+    histogram.count += 1;
+    histogram.total += cycles;
+    histogram.max = histogram.max.max(cycles);
+}
+
+/// Print every histogram recorded so far to the console.
+pub fn dump() {
+    let latency = SYSCALL_LATENCY.exclusive_access();
+    println!("== Begin syscall latency (cycles) ==");
+    for (id, histogram) in latency.iter() {
+        println!(
+            "syscall {}: count = {}, avg = {}, max = {}",
+            id,
+            histogram.count,
+            histogram.total / histogram.count,
+            histogram.max
+        );
+        for (i, &n) in histogram.buckets.iter().enumerate() {
+            if n != 0 {
+                println!("  [{:>10}, {:>10}): {}", 1u64 << i, 1u64 << (i + 1), n);
+            }
+        }
+    }
+    println!("== End syscall latency ==");
+}
@@ -0,0 +1,88 @@
+use crate::config::PAGE_SIZE;
+use crate::mm::{UserSlice, VirtAddr, VmaInfo, flush_icache};
+use crate::task::{current_copy_out_slice, with_current_memory_set};
+use crate::trap::cond_resched;
+
+/// `sys_madvise` advice: the range will be accessed soon, fault it in now.
+const MADV_WILLNEED: usize = 3;
+/// `sys_madvise` advice: the range is not needed, free its memory.
+const MADV_DONTNEED: usize = 4;
+
+/// Pages `sys_madvise` handles between preemption points.
+const MADVISE_BATCH_PAGES: usize = 64;
+
+/// `sys_riscv_flush_icache` flag: only the calling hart has to see the new code.
+const SYS_RISCV_FLUSH_ICACHE_LOCAL: usize = 1;
+
+/// Give the kernel a hint about the use of the anonymous memory `[start, start + len)`.
+///
+/// - `MADV_WILLNEED`: allocate the frames of untouched pages now, fails if out of memory.
+/// - `MADV_DONTNEED`: free the frames; the pages read as zeros on the next access.
+///
+/// # Returns
+/// 0 on success, -1 if `start` is not page aligned, the range is not entirely anonymous
+/// memory (e.g. ELF segments), `advice` is unknown or frames ran out.
+pub fn sys_madvise(start: usize, len: usize, advice: usize) -> isize {
+    let start_va = VirtAddr::from(start);
+    let Some(end) = start.checked_add(len) else {
+        return -1;
+    };
+    let end_va = VirtAddr::from(end);
+    if !start_va.aligned() {
+        return -1;
+    }
+
+    let valid =
+        with_current_memory_set(|memory_set| memory_set.is_zero_fill_range(start_va, end_va));
+    if valid != Some(true) || (advice != MADV_WILLNEED && advice != MADV_DONTNEED) {
+        return -1;
+    }
+    // a batch at a time, with a preemption point in between
+    for batch_start in (start..end).step_by(MADVISE_BATCH_PAGES * PAGE_SIZE) {
+        let batch_end = end.min(batch_start + MADVISE_BATCH_PAGES * PAGE_SIZE);
+        let (batch_start, batch_end) = (VirtAddr::from(batch_start), VirtAddr::from(batch_end));
+        let done = with_current_memory_set(|memory_set| match advice {
+            MADV_WILLNEED => memory_set.fill_zero_pages(batch_start, batch_end),
+            _ => {
+                memory_set.discard_zero_pages(batch_start, batch_end);
+                true
+            }
+        });
+        if done != Some(true) {
+            return -1;
+        }
+        cond_resched();
+    }
+    0
+}
+
+/// Copy the descriptions of the current task's memory areas to `buf`.
+///
+/// # Returns
+/// The number of memory areas, which may exceed the length of `buf`; only the first ones that
+/// fit are copied. -1 if `buf` is not writable by the task.
+pub fn sys_get_vma_info(buf: UserSlice<VmaInfo>) -> isize {
+    let Some(vmas) = with_current_memory_set(|memory_set| memory_set.vma_info()) else {
+        return -1;
+    };
+    let count = vmas.len().min(buf.len());
+    if !current_copy_out_slice(buf, &vmas[..count]) {
+        return -1;
+    }
+    vmas.len() as isize
+}
+
+/// Make instruction fetches of the current task see code it wrote to `[start, end)`.
+///
+/// The whole instruction cache is synchronized, not just the range. Only the boot hart runs,
+/// so `SYS_RISCV_FLUSH_ICACHE_LOCAL` makes no difference yet.
+///
+/// # Returns
+/// 0 on success, -1 if `start > end` or `flags` is unknown.
+pub fn sys_riscv_flush_icache(start: usize, end: usize, flags: usize) -> isize {
+    if start > end || flags & !SYS_RISCV_FLUSH_ICACHE_LOCAL != 0 {
+        return -1;
+    }
+    flush_icache();
+    0
+}
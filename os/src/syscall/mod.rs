@@ -1,20 +1,123 @@
+mod args;
+mod debug;
 mod fs;
+mod info;
+#[cfg(feature = "syscall_latency")]
+mod latency;
+mod mm;
 mod process;
 
+use crate::task::current_task_label;
+use args::SyscallArgs;
+use debug::*;
 use fs::*;
+use info::*;
+use log::Level;
+use mm::*;
 use process::*;
 
 const SYSCALL_WRITE: usize = 64;
+const SYSCALL_CAPGET: usize = 90;
+const SYSCALL_CAPSET: usize = 91;
 const SYSCALL_EXIT: usize = 93;
+const SYSCALL_NANOSLEEP: usize = 101;
+const SYSCALL_CLOCK_SETTIME: usize = 112;
+const SYSCALL_CLOCK_GETTIME: usize = 113;
+const SYSCALL_CLOCK_NANOSLEEP: usize = 115;
+const SYSCALL_SCHED_SETSCHEDULER: usize = 119;
 const SYSCALL_YIELD: usize = 124;
+const SYSCALL_REBOOT: usize = 142;
+const SYSCALL_UNAME: usize = 160;
+const SYSCALL_GETRUSAGE: usize = 165;
 const SYSCALL_GET_TIME: usize = 169;
+const SYSCALL_SYSINFO: usize = 179;
+const SYSCALL_MADVISE: usize = 233;
+const SYSCALL_RISCV_FLUSH_ICACHE: usize = 259;
+const SYSCALL_GETRANDOM: usize = 278;
+const SYSCALL_DUMP_SYSCALL_LATENCY: usize = 1000;
+const SYSCALL_GET_VMA_INFO: usize = 1001;
+const SYSCALL_GET_PROFILE: usize = 1002;
+const SYSCALL_KCOV_ENABLE: usize = 1003;
+const SYSCALL_KCOV_COLLECT: usize = 1004;
+const SYSCALL_FAULT_INJECT: usize = 1005;
+const SYSCALL_GROUP_CREATE: usize = 1006;
+const SYSCALL_GROUP_ATTACH: usize = 1007;
+const SYSCALL_GROUP_STAT: usize = 1008;
+const SYSCALL_TRACE_COLLECT: usize = 1009;
+const SYSCALL_TASK_INFO: usize = 1010;
+const SYSCALL_ACCT_COLLECT: usize = 1011;
+const SYSCALL_SLAB_INFO: usize = 1012;
+const SYSCALL_OOPS: usize = 1013;
 
-pub fn syscall(syscall_id: usize, args: [usize; 3]) -> isize {
-    match syscall_id {
-        SYSCALL_WRITE => sys_write(args[0], args[1] as *const u8, args[2]),
-        SYSCALL_EXIT => sys_exit(args[0] as i32),
+/// Dispatch syscall `syscall_id` with the raw arguments a0-a5.
+pub fn syscall(syscall_id: usize, args: [usize; 6]) -> isize {
+    #[cfg(feature = "syscall_latency")]
+    let start = crate::timer::get_time();
+
+    let ret = dispatch(syscall_id, SyscallArgs(args));
+
+    // only handled calls, random ids from a fuzzer would each get a histogram
+    #[cfg(feature = "syscall_latency")]
+    if ret.is_some() {
+        latency::record(syscall_id, crate::timer::get_time() - start);
+    }
+
+    ret.unwrap_or(-1)
+}
+
+/// Run the handler of syscall `syscall_id`.
+///
+/// # Returns
+/// The handler's return value, or `None` if the id is unknown or an argument failed to
+/// decode.
+fn dispatch(syscall_id: usize, args: SyscallArgs) -> Option<isize> {
+    let ret = match syscall_id {
+        SYSCALL_WRITE => sys_write(args.fd(0)?, args.slice(1, 2)?),
+        SYSCALL_CAPGET => sys_capget(),
+        SYSCALL_CAPSET => sys_capset(args.usize(0)),
+        SYSCALL_EXIT => sys_exit(args.i32(0)),
+        SYSCALL_NANOSLEEP => sys_nanosleep(args.ptr(0)?, args.ptr(1)?, args.usize(2)),
+        SYSCALL_CLOCK_SETTIME => sys_clock_settime(args.usize(0), args.ptr(1)?),
+        SYSCALL_CLOCK_GETTIME => sys_clock_gettime(args.usize(0), args.ptr(1)?),
+        SYSCALL_CLOCK_NANOSLEEP => {
+            sys_clock_nanosleep(args.usize(0), args.usize(1), args.ptr(2)?, args.ptr(3)?)
+        }
+        SYSCALL_SCHED_SETSCHEDULER => {
+            sys_sched_setscheduler(args.usize(0), args.usize(1), args.ptr(2)?)
+        }
         SYSCALL_YIELD => sys_yield(),
+        SYSCALL_REBOOT => sys_reboot(args.usize(0), args.usize(1), args.usize(2), args.usize(3)),
+        SYSCALL_UNAME => sys_uname(args.ptr(0)?),
+        SYSCALL_GETRUSAGE => sys_getrusage(args.usize(0) as isize, args.ptr(1)?),
         SYSCALL_GET_TIME => sys_get_time(),
-        _ => panic!("Unsupported syscall_id: {}", syscall_id),
-    }
+        SYSCALL_SYSINFO => sys_sysinfo(args.ptr(0)?),
+        SYSCALL_MADVISE => sys_madvise(args.usize(0), args.len(1), args.usize(2)),
+        SYSCALL_RISCV_FLUSH_ICACHE => {
+            sys_riscv_flush_icache(args.usize(0), args.usize(1), args.usize(2))
+        }
+        SYSCALL_GETRANDOM => sys_getrandom(args.slice(0, 1)?, args.usize(2)),
+        SYSCALL_DUMP_SYSCALL_LATENCY => sys_dump_syscall_latency(),
+        SYSCALL_GET_VMA_INFO => sys_get_vma_info(args.slice(0, 1)?),
+        SYSCALL_GET_PROFILE => sys_get_profile(args.slice(0, 1)?),
+        SYSCALL_KCOV_ENABLE => sys_kcov_enable(args.len(0)),
+        SYSCALL_KCOV_COLLECT => sys_kcov_collect(args.slice(0, 1)?),
+        SYSCALL_FAULT_INJECT => sys_fault_inject(args.usize(0), args.usize(1)),
+        SYSCALL_GROUP_CREATE => sys_group_create(args.usize(0)),
+        SYSCALL_GROUP_ATTACH => sys_group_attach(args.usize(0)),
+        SYSCALL_GROUP_STAT => sys_group_stat(args.usize(0), args.ptr(1)?),
+        SYSCALL_TRACE_COLLECT => sys_trace_collect(args.slice(0, 1)?),
+        SYSCALL_TASK_INFO => sys_task_info(args.usize(0), args.ptr(1)?),
+        SYSCALL_ACCT_COLLECT => sys_acct_collect(args.slice(0, 1)?),
+        SYSCALL_SLAB_INFO => sys_slab_info(args.slice(0, 1)?),
+        SYSCALL_OOPS => sys_oops(),
+        _ => {
+            log_ratelimited!(
+                Level::Warn,
+                "[kernel] Unsupported syscall_id {syscall_id} from {}",
+                current_task_label()
+            );
+            return None;
+        }
+    };
+    Some(ret)
 }
@@ -1,18 +1,269 @@
-use crate::task::{exit_current_and_run_next, suspend_current_and_run_next};
-use crate::timer::get_time_ms;
-use log::trace;
+use crate::finisher;
+use crate::mm::UserPtr;
+use crate::task::{
+    CAP_REBOOT, CAP_SETTIME, GroupStat, RUsage, SchedParam, TaskInfo, block_current_and_run_next,
+    create_group, current_attach_group, current_caps, current_copy_in, current_copy_out,
+    current_has_cap, current_rusage, current_task_id, current_task_label, end_current_job,
+    exit_current_and_run_next, group_stat, set_current_caps, set_current_deadline, task_info,
+    wake_all, wake_task, yield_current_and_run_next,
+};
+use crate::timer::{
+    CLOCK_MONOTONIC, CLOCK_REALTIME, TimeSpec, clock_now_ns, get_time_ms, get_time_ns,
+    set_realtime_ns,
+};
+use crate::timer_queue::{add_timer, cancel_timer};
+use log::{info, trace};
+
+/// `sys_nanosleep` flag: `req` is an absolute deadline on the boot clock instead of a duration.
+const TIMER_ABSTIME: usize = 1;
+
+/// `sys_sched_setscheduler` policies, Linux's values.
+const SCHED_OTHER: usize = 0;
+const SCHED_DEADLINE: usize = 6;
+
+/// `sys_getrusage` target, Linux's value.
+const RUSAGE_SELF: isize = 0;
+
+/// `sys_reboot` magic numbers, so a stray call with garbage arguments does nothing.
+const REBOOT_MAGIC1: usize = 0xfee1_dead;
+const REBOOT_MAGIC2: usize = 672_274_793;
+
+/// `sys_reboot` commands, Linux's values.
+const REBOOT_CMD_RESTART: usize = 0x0123_4567;
+const REBOOT_CMD_POWER_OFF: usize = 0x4321_fedc;
 
 pub fn sys_exit(exit_code: i32) -> ! {
     trace!("[kernel] Application exited with code {}", exit_code);
-    exit_current_and_run_next();
+    exit_current_and_run_next(exit_code);
     panic!("Unreachable in sys_exit!");
 }
 
+/// Give up the CPU. A deadline task ends its current job and returns once the next one is
+/// released, see `task::deadline`.
 pub fn sys_yield() -> isize {
-    suspend_current_and_run_next();
+    end_current_job();
+    yield_current_and_run_next();
     0
 }
 
 pub fn sys_get_time() -> isize {
     get_time_ms() as isize
 }
+
+/// Sleep until the time described by `req` has passed on `CLOCK_MONOTONIC`.
+///
+/// # Arguments
+/// * `req` - Duration to sleep, or the deadline with `TIMER_ABSTIME`.
+/// * `rem` - Where the remaining time is reported when a signal cuts the sleep short. There
+///   are no signals yet, so a sleep always runs to completion and `rem` is never written.
+/// * `flags` - `0` or `TIMER_ABSTIME`.
+///
+/// # Returns
+/// 0 on success, -1 if `req` is not a valid timespec.
+pub fn sys_nanosleep(req: UserPtr<TimeSpec>, rem: UserPtr<TimeSpec>, flags: usize) -> isize {
+    sys_clock_nanosleep(CLOCK_MONOTONIC, flags, req, rem)
+}
+
+/// Sleep until the time described by `req` has passed on clock `clock_id`.
+///
+/// A relative sleep always lasts `req`, whatever happens to the clock meanwhile. An absolute
+/// one on `CLOCK_REALTIME` ends once the clock reads `req`, even if it was set in between.
+///
+/// # Returns
+/// 0 on success, -1 for an unknown clock or if `req` is not a valid timespec.
+pub fn sys_clock_nanosleep(
+    clock_id: usize,
+    flags: usize,
+    req: UserPtr<TimeSpec>,
+    _rem: UserPtr<TimeSpec>,
+) -> isize {
+    let Some(req) = current_copy_in(req) else {
+        return -1;
+    };
+    if clock_now_ns(clock_id).is_none() || !req.is_valid() {
+        return -1;
+    }
+
+    if flags & TIMER_ABSTIME != 0 {
+        sleep_until(clock_id, req.to_ns());
+    } else {
+        sleep_until(CLOCK_MONOTONIC, get_time_ns().saturating_add(req.to_ns()));
+    }
+    0
+}
+
+/// Block the current task until clock `clock_id` reaches `deadline`.
+fn sleep_until(clock_id: usize, deadline: u64) {
+    let task = crate::task::current_task_id();
+    #[cfg(feature = "sched_trace")]
+    crate::trace::record(
+        crate::trace::TraceEvent::Block,
+        task,
+        (deadline / 1_000_000) as usize,
+    );
+    loop {
+        let now = clock_now_ns(clock_id).unwrap();
+        if now >= deadline {
+            break;
+        }
+        // setting the clock wakes realtime sleepers to re-arm, see `sys_clock_settime`
+        let monotonic = get_time_ns().saturating_add(deadline - now);
+        let timer = add_timer(monotonic, wake_sleeper, task);
+        block_current_and_run_next();
+        cancel_timer(timer);
+    }
+}
+
+/// Timer callback of `sleep_until`, wakes task `task_id`.
+fn wake_sleeper(task_id: usize) {
+    #[cfg(feature = "sched_trace")]
+    crate::trace::record(crate::trace::TraceEvent::Wakeup, task_id, 0);
+    wake_task(task_id);
+}
+
+/// Copy the time of clock `clock_id` to `tp`.
+///
+/// # Returns
+/// 0 on success, -1 for an unknown clock or if `tp` is not writable by the task.
+pub fn sys_clock_gettime(clock_id: usize, tp: UserPtr<TimeSpec>) -> isize {
+    let Some(now) = clock_now_ns(clock_id) else {
+        return -1;
+    };
+    if current_copy_out(tp, &TimeSpec::from_ns(now)) {
+        0
+    } else {
+        -1
+    }
+}
+
+/// Set `CLOCK_REALTIME` to `tp`. Sleeps until an absolute realtime deadline follow the clock.
+///
+/// The caller needs `CAP_SETTIME`.
+///
+/// # Returns
+/// 0 on success, -1 for any other clock, an invalid `tp` or a caller without the capability.
+pub fn sys_clock_settime(clock_id: usize, tp: UserPtr<TimeSpec>) -> isize {
+    let Some(tp) = current_copy_in(tp) else {
+        return -1;
+    };
+    if clock_id != CLOCK_REALTIME || !tp.is_valid() || !current_has_cap(CAP_SETTIME) {
+        return -1;
+    }
+    set_realtime_ns(tp.to_ns());
+    // realtime sleepers re-arm their timers for the new time
+    wake_all();
+    0
+}
+
+/// Restart the machine with a warm reboot, which keeps this boot's kernel log for the next
+/// one (see `pstore`), or power it off. The caller needs `CAP_REBOOT`.
+///
+/// # Returns
+/// -1 for wrong magic numbers, an unknown `cmd` or a caller without the capability; does
+/// not return otherwise.
+pub fn sys_reboot(magic1: usize, magic2: usize, cmd: usize, _arg: usize) -> isize {
+    if magic1 != REBOOT_MAGIC1 || magic2 != REBOOT_MAGIC2 || !current_has_cap(CAP_REBOOT) {
+        return -1;
+    }
+    match cmd {
+        REBOOT_CMD_RESTART => {
+            info!("[kernel] {} requested a reboot", current_task_label());
+            finisher::reboot()
+        }
+        REBOOT_CMD_POWER_OFF => {
+            info!("[kernel] {} requested a power off", current_task_label());
+            finisher::exit(0)
+        }
+        _ => -1,
+    }
+}
+
+/// Set the scheduling class of task `pid`, which must be 0 or the caller's task id:
+/// `SCHED_OTHER`, the normal class, or `SCHED_DEADLINE` with the period, deadline and
+/// runtime in `param`, see `task::deadline`. `param` is only read for `SCHED_DEADLINE`.
+///
+/// # Returns
+/// 0 on success, -1 for another task, an unknown policy, invalid parameters or if the
+/// deadline tasks would reserve too much of the CPU.
+pub fn sys_sched_setscheduler(pid: usize, policy: usize, param: UserPtr<SchedParam>) -> isize {
+    if pid != 0 && pid != current_task_id() {
+        return -1;
+    }
+    let param = match policy {
+        SCHED_OTHER => None,
+        SCHED_DEADLINE => match current_copy_in(param) {
+            Some(param) if param.is_valid() => Some(param),
+            _ => return -1,
+        },
+        _ => return -1,
+    };
+    if set_current_deadline(param) { 0 } else { -1 }
+}
+
+/// Create a task group that gets CPU time in proportion to `weight`, see `task::group`.
+///
+/// # Returns
+/// The group id, or -1 if `weight` is 0 or too large, or there are too many groups.
+pub fn sys_group_create(weight: usize) -> isize {
+    create_group(weight).map_or(-1, |group| group as isize)
+}
+
+/// Move the current task to task group `group`.
+///
+/// # Returns
+/// 0 on success, -1 if there is no such group.
+pub fn sys_group_attach(group: usize) -> isize {
+    if current_attach_group(group) { 0 } else { -1 }
+}
+
+/// Copy the weight, live task count and CPU ticks of task group `group` to `buf`.
+///
+/// # Returns
+/// 0 on success, -1 if there is no such group or `buf` is not writable by the task.
+pub fn sys_group_stat(group: usize, buf: UserPtr<GroupStat>) -> isize {
+    let Some(stat) = group_stat(group) else {
+        return -1;
+    };
+    if current_copy_out(buf, &stat) { 0 } else { -1 }
+}
+
+/// Copy the name, state and scheduler counters of task `task_id` to `buf`.
+///
+/// # Returns
+/// 0 on success, -1 if there is no such task or `buf` is not writable by the task. Task ids are dense, so a caller can walk all
+/// tasks from 0 until this fails.
+pub fn sys_task_info(task_id: usize, buf: UserPtr<TaskInfo>) -> isize {
+    let Some(info) = task_info(task_id) else {
+        return -1;
+    };
+    if current_copy_out(buf, &info) { 0 } else { -1 }
+}
+
+/// Copy the resource usage of the calling task, `RUSAGE_SELF`, to `usage`.
+///
+/// # Returns
+/// 0 on success, -1 if `usage` is not writable by the task or for any other `who`, including
+/// `RUSAGE_CHILDREN` as no task has children yet.
+pub fn sys_getrusage(who: isize, usage: UserPtr<RUsage>) -> isize {
+    if who != RUSAGE_SELF {
+        return -1;
+    }
+    if current_copy_out(usage, &current_rusage()) {
+        0
+    } else {
+        -1
+    }
+}
+
+/// Returns the capabilities of the calling task, `CAP_*` bits, see `task::caps`.
+pub fn sys_capget() -> isize {
+    current_caps() as isize
+}
+
+/// Set the capabilities of the calling task to `caps`, which may only drop some.
+///
+/// # Returns
+/// 0 on success, -1 if `caps` holds one the task does not: dropped capabilities are gone.
+pub fn sys_capset(caps: usize) -> isize {
+    if set_current_caps(caps) { 0 } else { -1 }
+}
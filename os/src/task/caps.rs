@@ -0,0 +1,47 @@
+//! Capabilities, a lite version of Linux's: bits of privilege a task holds, checked by the
+//! syscalls they guard instead of asking who the caller is.
+//!
+//! `INIT_TASK_NAME` starts with all of them, standing in for pid 1 as there are no pids
+//! yet, every other app with none. A task may drop capabilities but never get them back.
+
+/// Name of the app that starts with `CAP_ALL`.
+pub const INIT_TASK_NAME: &str = "initproc";
+
+/// Restart or power off the machine, `sys_reboot`.
+pub const CAP_REBOOT: usize = 1 << 0;
+/// Mount filesystems, for when there are any.
+pub const CAP_MOUNT: usize = 1 << 1;
+/// Access devices directly, for when user space may.
+pub const CAP_RAWIO: usize = 1 << 2;
+/// Set `CLOCK_REALTIME`, `sys_clock_settime`.
+pub const CAP_SETTIME: usize = 1 << 3;
+/// Signal tasks other than the caller, for when there are signals.
+pub const CAP_KILL_ANY: usize = 1 << 4;
+pub const CAP_ALL: usize = CAP_REBOOT | CAP_MOUNT | CAP_RAWIO | CAP_SETTIME | CAP_KILL_ANY;
+
+/// Returns the capabilities app `name` starts with.
+pub fn initial_caps(name: &str) -> usize {
+    if name == INIT_TASK_NAME { CAP_ALL } else { 0 }
+}
+
+/// Returns `true` if a task holding `caps` may set them to `new`: it only drops some.
+pub fn may_set(caps: usize, new: usize) -> bool {
+    new & !caps == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn capabilities_can_be_dropped_but_not_regained() {
+        let caps = initial_caps(INIT_TASK_NAME);
+        assert_eq!(caps, CAP_ALL);
+        assert_eq!(initial_caps("00power_3"), 0);
+        assert!(may_set(caps, CAP_ALL & !CAP_REBOOT));
+        assert!(may_set(CAP_SETTIME, 0));
+        assert!(!may_set(CAP_SETTIME, CAP_SETTIME | CAP_REBOOT));
+        assert!(!may_set(0, CAP_RAWIO));
+        assert!(may_set(CAP_ALL, CAP_ALL));
+    }
+}
@@ -1,3 +1,4 @@
+use super::kthread::kthread_entry;
 use crate::trap::trap_return;
 
 #[repr(C)]
@@ -8,7 +9,7 @@ pub struct TaskContext {
 }
 
 impl TaskContext {
-    pub fn empty() -> Self {
+    pub const fn empty() -> Self {
         Self {
             ra: 0,
             sp: 0,
@@ -23,4 +24,12 @@ impl TaskContext {
             s: [0; 12],
         }
     }
+
+    pub fn goto_kthread_entry(kstack_ptr: usize) -> Self {
+        Self {
+            ra: kthread_entry as usize,
+            sp: kstack_ptr,
+            s: [0; 12],
+        }
+    }
 }
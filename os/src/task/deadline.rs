@@ -0,0 +1,183 @@
+//! The deadline scheduling class, earliest deadline first with throttling.
+//!
+//! A task in it declares a period, a relative deadline and a runtime budget with
+//! `sys_sched_setscheduler`. Every period releases a job that must get `runtime` of CPU time
+//! before `deadline`. Deadline tasks run ahead of all others, the one with the earliest
+//! absolute deadline first. A job ends when its task yields, and the task then waits in the
+//! kernel for the next release; a job that used up its budget is throttled the same way, so
+//! an overrunning task cannot starve the rest.
+//!
+//! Budgets are charged per timer tick, so they are only as precise as `TICK_NS`. Admission
+//! control keeps the summed `runtime / period` of all deadline tasks at `MAX_UTIL_PERMILLE`.
+
+/// Share of the CPU all deadline tasks together may reserve, leaving some for the others.
+pub const MAX_UTIL_PERMILLE: u64 = 900;
+
+/// Parameters of `sys_sched_setscheduler` for `SCHED_DEADLINE`, layout compatible with user
+/// space. All times are in ns.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SchedParam {
+    pub runtime_ns: u64,
+    pub deadline_ns: u64,
+    pub period_ns: u64,
+}
+
+impl SchedParam {
+    /// Returns `true` if `0 < runtime <= deadline <= period`.
+    pub fn is_valid(&self) -> bool {
+        0 < self.runtime_ns
+            && self.runtime_ns <= self.deadline_ns
+            && self.deadline_ns <= self.period_ns
+    }
+
+    /// Returns the share of the CPU the task reserves.
+    pub fn util_permille(&self) -> u64 {
+        (self.runtime_ns as u128 * 1000 / self.period_ns as u128) as u64
+    }
+}
+
+/// Scheduling state of a deadline task.
+pub struct DeadlineTask {
+    pub param: SchedParam,
+    /// Release time of the current job, in ns since boot.
+    release_ns: u64,
+    /// Budget left to the current job.
+    budget_ns: u64,
+    /// The current job ended or ran out of budget, the task waits for the next release.
+    throttled: bool,
+    /// Jobs that ended after their deadline.
+    pub misses: usize,
+    /// Jobs throttled for using up their budget.
+    pub overruns: usize,
+}
+
+impl DeadlineTask {
+    /// Start with a job released at `now_ns`.
+    pub fn new(param: SchedParam, now_ns: u64) -> Self {
+        Self {
+            param,
+            release_ns: now_ns,
+            budget_ns: param.runtime_ns,
+            throttled: false,
+            misses: 0,
+            overruns: 0,
+        }
+    }
+
+    fn next_release_ns(&self) -> u64 {
+        self.release_ns + self.param.period_ns
+    }
+
+    /// Returns `true` if the task may run at `now_ns`: its job is not done, or the next one
+    /// is released.
+    pub fn is_runnable(&self, now_ns: u64) -> bool {
+        !self.throttled || now_ns >= self.next_release_ns()
+    }
+
+    /// Returns the absolute deadline the task runs with at `now_ns`, that of the next job if
+    /// the current one is done and the next released.
+    pub fn deadline_ns(&self, now_ns: u64) -> u64 {
+        let release_ns = if self.throttled && now_ns >= self.next_release_ns() {
+            self.next_release_ns()
+        } else {
+            self.release_ns
+        };
+        release_ns + self.param.deadline_ns
+    }
+
+    /// Start the next job if the current one is done and the next released; periods that
+    /// passed meanwhile are skipped.
+    pub fn replenish(&mut self, now_ns: u64) {
+        if !self.throttled || now_ns < self.next_release_ns() {
+            return;
+        }
+        let periods = (now_ns - self.release_ns) / self.param.period_ns;
+        self.release_ns += periods * self.param.period_ns;
+        self.budget_ns = self.param.runtime_ns;
+        self.throttled = false;
+    }
+
+    /// Returns `true` if the task waits for its next release at `now_ns`.
+    pub fn is_throttled(&mut self, now_ns: u64) -> bool {
+        self.replenish(now_ns);
+        self.throttled
+    }
+
+    /// Charge the current job `ns` of CPU time, throttling it once the budget is used up.
+    pub fn charge(&mut self, ns: u64) {
+        if self.throttled {
+            return;
+        }
+        self.budget_ns = self.budget_ns.saturating_sub(ns);
+        if self.budget_ns == 0 {
+            self.throttled = true;
+            self.overruns += 1;
+        }
+    }
+
+    /// End the current job at `now_ns`, the task yielded.
+    pub fn end_job(&mut self, now_ns: u64) {
+        if self.throttled {
+            return;
+        }
+        if now_ns > self.release_ns + self.param.deadline_ns {
+            self.misses += 1;
+        }
+        self.throttled = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: u64 = 1_000_000;
+
+    fn param(runtime_ms: u64, deadline_ms: u64, period_ms: u64) -> SchedParam {
+        SchedParam {
+            runtime_ns: runtime_ms * MS,
+            deadline_ns: deadline_ms * MS,
+            period_ns: period_ms * MS,
+        }
+    }
+
+    #[test_case]
+    fn params_are_checked() {
+        assert!(param(10, 50, 100).is_valid());
+        assert!(param(10, 10, 10).is_valid());
+        assert!(!param(0, 50, 100).is_valid());
+        assert!(!param(60, 50, 100).is_valid());
+        assert!(!param(10, 150, 100).is_valid());
+        assert_eq!(param(25, 50, 100).util_permille(), 250);
+    }
+
+    #[test_case]
+    fn jobs_end_on_yield_and_resume_at_the_next_release() {
+        let mut task = DeadlineTask::new(param(20, 50, 100), 0);
+        assert_eq!(task.deadline_ns(0), 50 * MS);
+        task.end_job(10 * MS);
+        assert!(task.is_throttled(99 * MS));
+        assert!(!task.is_runnable(99 * MS));
+        // the next job's deadline counts as soon as it is released
+        assert!(task.is_runnable(100 * MS));
+        assert_eq!(task.deadline_ns(100 * MS), 150 * MS);
+        assert!(!task.is_throttled(100 * MS));
+        assert_eq!(task.misses, 0);
+    }
+
+    #[test_case]
+    fn overruns_are_throttled_and_missed_periods_skipped() {
+        let mut task = DeadlineTask::new(param(20, 50, 100), 0);
+        task.charge(10 * MS);
+        assert!(!task.is_throttled(10 * MS));
+        task.charge(10 * MS);
+        assert!(task.is_throttled(20 * MS));
+        assert_eq!(task.overruns, 1);
+        // woken long after, it starts with the job of the current period
+        assert!(!task.is_throttled(350 * MS));
+        assert_eq!(task.deadline_ns(350 * MS), 350 * MS);
+        task.end_job(360 * MS);
+        assert_eq!(task.misses, 1);
+    }
+}
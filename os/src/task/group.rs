@@ -0,0 +1,53 @@
+//! Task groups, a lite version of cgroups for CPU shares.
+//!
+//! Every task belongs to a group, at boot all of them to group 0. The scheduler runs a ready
+//! task of the group with the smallest pass (stride scheduling), round-robin inside the group.
+//! Every timer tick a task runs advances its group's pass by `STRIDE_MAX / weight`, so groups
+//! get CPU time in proportion to their weights, no matter how many tasks they hold.
+
+/// Pass a group of weight 1 advances per tick.
+const STRIDE_MAX: u64 = 1 << 20;
+
+/// Weight of group 0.
+pub const DEFAULT_WEIGHT: usize = 100;
+
+/// Largest weight a group may have.
+pub const MAX_WEIGHT: usize = 10_000;
+
+/// Most groups that can exist, group 0 included.
+pub const MAX_GROUPS: usize = 16;
+
+pub struct TaskGroup {
+    pub weight: usize,
+    /// Stride scheduling pass, the group with the smallest one runs next.
+    pub pass: u64,
+    /// Timer ticks its tasks ran.
+    pub ticks: usize,
+}
+
+impl TaskGroup {
+    pub fn new(weight: usize, pass: u64) -> Self {
+        Self {
+            weight,
+            pass,
+            ticks: 0,
+        }
+    }
+
+    /// Account one timer tick a task of this group ran.
+    pub fn charge_tick(&mut self) {
+        self.pass += STRIDE_MAX / self.weight as u64;
+        self.ticks += 1;
+    }
+}
+
+/// Usage of a task group returned by `sys_group_stat`, layout compatible with user space.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct GroupStat {
+    pub weight: usize,
+    /// Tasks in the group that have not exited.
+    pub tasks: usize,
+    /// Timer ticks its tasks ran.
+    pub ticks: usize,
+}
@@ -0,0 +1,47 @@
+//! Kernel threads (kthreads)
+//!
+//! A kthread is a task without a user address space: it runs a kernel function in S-mode
+//! on its own kernel stack and is scheduled round-robin alongside user tasks. Since
+//! interrupts stay disabled in S-mode, a long-running kthread must call
+//! `yield_current_and_run_next` itself to give up the CPU.
+
+use super::{TASK_MANAGER, exit_current_and_run_next};
+
+/// Spawn a kthread called `name` running `entry` and return its task id.
+///
+/// The kthread exits when `entry` returns.
+pub fn spawn_kthread(name: &'static str, entry: fn()) -> usize {
+    TASK_MANAGER.add_kthread(name, entry)
+}
+
+/// First code executed by every kthread, reached through `__switch` returning to `ra`.
+pub(super) fn kthread_entry() -> ! {
+    let entry = TASK_MANAGER.get_current_kthread_entry();
+    entry();
+    exit_current_and_run_next(0);
+    unreachable!("exited kthread was scheduled again");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{TaskStatus, run_task_for_test};
+    use super::*;
+    use core::sync::atomic::{AtomicBool, Ordering};
+
+    static KTHREAD_RAN: AtomicBool = AtomicBool::new(false);
+
+    fn record_run() {
+        KTHREAD_RAN.store(true, Ordering::Relaxed);
+    }
+
+    #[test_case]
+    fn kthread_exits_when_its_entry_returns() {
+        let task_id = spawn_kthread("ktest", record_run);
+        assert!(!KTHREAD_RAN.load(Ordering::Relaxed));
+        assert_eq!(run_task_for_test(task_id), 0);
+        assert!(KTHREAD_RAN.load(Ordering::Relaxed));
+        let inner = TASK_MANAGER.inner.exclusive_access();
+        assert!(inner.tasks[task_id].is_kthread());
+        assert!(inner.tasks[task_id].task_status == TaskStatus::Exited);
+    }
+}
@@ -1,32 +1,77 @@
+mod caps;
 mod context;
+mod deadline;
+mod group;
+mod kthread;
 mod switch;
 #[allow(clippy::module_inception)]
 mod task;
 
-use crate::loader::{get_app_data, get_num_app};
-use crate::sbi::shutdown;
+use crate::config::USER_SPACE_END;
+use crate::finisher;
+use crate::loader::{get_app_name, get_num_app, get_verified_app_data};
+use crate::mm::{MemorySet, PageFaultError, UserPtr, UserSlice, translated_byte_buffer};
 use crate::sync::UPSafeCell;
-use crate::trap::TrapContext;
+use crate::timer::{TICK_NS, get_time_ns, set_next_trigger, set_trigger_at_ns};
+use crate::timer_queue::{next_deadline, run_expired_timers};
+use crate::trap::{InterruptGuard, TrapContext};
+use alloc::vec;
 use alloc::vec::Vec;
+use core::fmt;
+use core::sync::atomic::{AtomicBool, Ordering};
+#[cfg(test)]
+use core::sync::atomic::{AtomicI32, AtomicUsize};
+use deadline::{DeadlineTask, MAX_UTIL_PERMILLE};
+use group::{DEFAULT_WEIGHT, MAX_GROUPS, MAX_WEIGHT, TaskGroup};
 use lazy_static::*;
-use log::trace;
+use log::{error, info, trace};
 use switch::__switch;
 use task::{TaskControlBlock, TaskStatus};
 
+pub use caps::{CAP_REBOOT, CAP_SETTIME};
 pub use context::TaskContext;
+pub use deadline::SchedParam;
+pub use group::GroupStat;
+pub use kthread::spawn_kthread;
+
+/// Exit code of a task the kernel killed for a fault, an illegal instruction or an oops.
+///
+/// Apps provoke these on purpose to test the kernel, so they do not count as failures.
+pub const EXIT_KILLED: i32 = -2;
+/// Exit code of a task the watchdog killed, e.g. an app stuck in a loop.
+pub const EXIT_HUNG: i32 = -3;
+/// Exit code the user library's panic handler exits with, the same as Rust's std uses.
+pub const EXIT_PANICKED: i32 = 101;
+
+/// Set by `request_resched` until the current task takes the request.
+static RESCHED_PENDING: AtomicBool = AtomicBool::new(false);
 
 /// The `TaskManager` struct manages all tasks in the system.
 ///
-/// - `num_app`: The total number of applications/tasks managed (immutable after initialization).
 /// - `inner`: A thread-safe cell containing the mutable inner state of the task manager.
 pub struct TaskManager {
-    num_app: usize,
     inner: UPSafeCell<TaskManagerInner>,
 }
 
 struct TaskManagerInner {
     tasks: Vec<TaskControlBlock>,
     current_task: usize,
+    /// Number of switches between different tasks since boot.
+    context_switches: usize,
+    /// Task groups, indexed by group id, see `group`.
+    groups: Vec<TaskGroup>,
+    /// Apps that could not be loaded, plus user tasks that exited with a non-zero code
+    /// other than `EXIT_KILLED`.
+    failed_tasks: usize,
+}
+
+impl TaskManagerInner {
+    fn label(&self, task_id: usize) -> TaskLabel {
+        TaskLabel {
+            id: task_id,
+            name: self.tasks[task_id].name,
+        }
+    }
 }
 
 lazy_static! {
@@ -35,15 +80,36 @@ lazy_static! {
         let num_app = get_num_app();
         println!("num_app = {}", num_app);
         let mut tasks: Vec<TaskControlBlock> = Vec::new();
+        let mut rejected = 0;
         for i in 0..num_app {
-            tasks.push(TaskControlBlock::new(i, get_app_data(i)));
+            // task ids index `tasks`, so a rejected app does not take one
+            match get_verified_app_data(i)
+                .and_then(|data| TaskControlBlock::new(tasks.len(), get_app_name(i), data))
+            {
+                Ok(task) => {
+                    #[cfg(feature = "sched_trace")]
+                    crate::trace::record(crate::trace::TraceEvent::Spawn, tasks.len(), 0);
+                    tasks.push(task);
+                }
+                Err(err) => {
+                    error!(
+                        "[kernel] app {} '{}' cannot be loaded ({}), skipping it",
+                        i,
+                        get_app_name(i),
+                        err
+                    );
+                    rejected += 1;
+                }
+            }
         }
         TaskManager {
-            num_app,
             inner: unsafe {
                 UPSafeCell::new(TaskManagerInner {
                     tasks,
                     current_task: 0,
+                    context_switches: 0,
+                    groups: vec![TaskGroup::new(DEFAULT_WEIGHT, 0)],
+                    failed_tasks: rejected,
                 })
             },
         }
@@ -56,6 +122,8 @@ impl TaskManager {
         let task0 = &mut inner.tasks[0];
         task0.task_status = TaskStatus::Running;
         let next_task_cx_ptr = &task0.task_cx as *const TaskContext;
+        #[cfg(feature = "sched_trace")]
+        crate::trace::record(crate::trace::TraceEvent::SchedIn, 0, 0);
 
         drop(inner); // switch will modify inner
 
@@ -73,18 +141,405 @@ impl TaskManager {
         inner.tasks[cur].task_status = TaskStatus::Ready;
     }
 
-    fn mark_current_exited(&self) {
+    fn mark_current_blocked(&self) {
+        let mut inner = self.inner.exclusive_access();
+        let cur = inner.current_task;
+        inner.tasks[cur].task_status = TaskStatus::Blocked;
+    }
+
+    /// Make task `task_id` ready again if it is blocked.
+    fn wake_task(&self, task_id: usize) {
+        let mut inner = self.inner.exclusive_access();
+        if let Some(task) = inner.tasks.get_mut(task_id) {
+            if task.task_status == TaskStatus::Blocked {
+                task.task_status = TaskStatus::Ready;
+            }
+        }
+    }
+
+    /// Make every blocked task ready again.
+    fn wake_all(&self) {
+        let mut inner = self.inner.exclusive_access();
+        for task in inner.tasks.iter_mut() {
+            if task.task_status == TaskStatus::Blocked {
+                task.task_status = TaskStatus::Ready;
+            }
+        }
+    }
+
+    fn any_blocked(&self) -> bool {
+        let inner = self.inner.exclusive_access();
+        inner
+            .tasks
+            .iter()
+            .any(|task| task.task_status == TaskStatus::Blocked)
+    }
+
+    fn mark_current_exited(&self, exit_code: i32) {
         let mut inner = self.inner.exclusive_access();
         let cur = inner.current_task;
         inner.tasks[cur].task_status = TaskStatus::Exited;
+        #[cfg(feature = "sched_trace")]
+        crate::trace::record(crate::trace::TraceEvent::Exit, cur, exit_code as usize);
+        let label = inner.label(cur);
+        trace!(
+            "[kernel] {} exited after {} voluntary and {} involuntary switches",
+            label, inner.tasks[cur].voluntary_switches, inner.tasks[cur].involuntary_switches
+        );
+        if exit_code != 0 && exit_code != EXIT_KILLED && !inner.tasks[cur].is_kthread() {
+            if exit_code == EXIT_PANICKED {
+                info!("[kernel] {label} panicked");
+            } else {
+                info!("[kernel] {label} failed with exit code {exit_code}");
+            }
+            inner.failed_tasks += 1;
+        }
+        inner.tasks[cur].stdout.flush();
+        #[cfg(feature = "acct")]
+        if !inner.tasks[cur].is_kthread() {
+            inner.tasks[cur].sample_peak_frames();
+            let task = &inner.tasks[cur];
+            crate::acct::record(crate::acct::AcctRecord {
+                time_ns: crate::timer::get_time_ns(),
+                task: cur,
+                exit_code: exit_code as isize,
+                user_ticks: task.user_ticks,
+                kernel_ticks: task.kernel_ticks,
+                peak_frames: task.peak_frames,
+                name: name_bytes(task.name),
+            });
+        }
+        // nothing runs in the address space anymore, the trap context page goes with it
+        inner.tasks[cur].trap_cx_ppn = None;
+        inner.tasks[cur].memory_set = None;
+    }
+
+    fn write_current_stdout(&self, bytes: &[u8]) {
+        let mut inner = self.inner.exclusive_access();
+        let cur = inner.current_task;
+        inner.tasks[cur].stdout.write(bytes);
     }
 
-    fn find_next_task(&self) -> Option<usize> {
+    fn flush_current_stdout(&self) {
+        let mut inner = self.inner.exclusive_access();
+        let cur = inner.current_task;
+        inner.tasks[cur].stdout.flush();
+    }
+
+    /// Pick the runnable deadline task with the earliest deadline, else the next ready task
+    /// round-robin within the group with the smallest pass.
+    ///
+    /// A task that `yielded` goes to the tail of the ready queue: it only runs again when no
+    /// other task is ready, even if its group is the one with the smallest pass or it has
+    /// the earliest deadline. Throttled deadline tasks only run when nothing else is ready,
+    /// and then just wait for their next release.
+    fn find_next_task(&self, yielded: bool) -> Option<usize> {
         let inner = self.inner.exclusive_access();
         let current = inner.current_task;
-        (current + 1..current + self.num_app + 1)
-            .map(|id| id % self.num_app)
-            .find(|id| inner.tasks[*id].task_status == TaskStatus::Ready)
+        let num_task = inner.tasks.len();
+        let now_ns = get_time_ns();
+        let is_ready = |id: &usize| inner.tasks[*id].task_status == TaskStatus::Ready;
+        let requeued = yielded && (0..num_task).any(|id| id != current && is_ready(&id));
+        let is_candidate = |id: &usize| is_ready(id) && !(requeued && *id == current);
+        let earliest_deadline = (0..num_task)
+            .filter(is_candidate)
+            .filter_map(|id| {
+                let deadline = inner.tasks[id].deadline.as_ref()?;
+                deadline
+                    .is_runnable(now_ns)
+                    .then(|| (deadline.deadline_ns(now_ns), id))
+            })
+            .min();
+        if let Some((_, id)) = earliest_deadline {
+            return Some(id);
+        }
+        let mut round_robin = (current + 1..current + num_task + 1).map(|id| id % num_task);
+        let is_normal = |id: &usize| is_candidate(id) && inner.tasks[*id].deadline.is_none();
+        let Some(group) = (0..num_task)
+            .filter(is_normal)
+            .map(|id| inner.tasks[id].group)
+            .min_by_key(|&group| inner.groups[group].pass)
+        else {
+            return round_robin.find(is_candidate);
+        };
+        round_robin.find(|id| is_normal(id) && inner.tasks[*id].group == group)
+    }
+
+    fn charge_current_tick(&self, in_kernel: bool) {
+        let mut inner = self.inner.exclusive_access();
+        let cur = inner.current_task;
+        let task = &mut inner.tasks[cur];
+        if in_kernel {
+            task.kernel_ticks += 1;
+        } else {
+            task.user_ticks += 1;
+        }
+        task.sample_peak_frames();
+        if let Some(deadline) = task.deadline.as_mut() {
+            deadline.charge(TICK_NS);
+        }
+        let group = task.group;
+        inner.groups[group].charge_tick();
+    }
+
+    /// Move the current task to the deadline class with `param`, or back to the normal
+    /// class with `None`.
+    ///
+    /// # Returns
+    /// `false` if the deadline tasks would reserve more than `MAX_UTIL_PERMILLE` of the CPU.
+    fn set_current_deadline(&self, param: Option<SchedParam>) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        let cur = inner.current_task;
+        let Some(param) = param else {
+            inner.tasks[cur].deadline = None;
+            return true;
+        };
+        let reserved: u64 = inner
+            .tasks
+            .iter()
+            .enumerate()
+            .filter(|(id, task)| *id != cur && task.task_status != TaskStatus::Exited)
+            .filter_map(|(_, task)| task.deadline.as_ref())
+            .map(|deadline| deadline.param.util_permille())
+            .sum();
+        if reserved + param.util_permille() > MAX_UTIL_PERMILLE {
+            return false;
+        }
+        inner.tasks[cur].deadline = Some(DeadlineTask::new(param, get_time_ns()));
+        true
+    }
+
+    /// End the current job of the current task if it is a deadline task.
+    fn end_current_job(&self) {
+        let mut inner = self.inner.exclusive_access();
+        let cur = inner.current_task;
+        if let Some(deadline) = inner.tasks[cur].deadline.as_mut() {
+            deadline.end_job(get_time_ns());
+        }
+    }
+
+    fn current_caps(&self) -> usize {
+        let inner = self.inner.exclusive_access();
+        inner.tasks[inner.current_task].caps
+    }
+
+    /// Set the capabilities of the current task to `caps`.
+    ///
+    /// # Returns
+    /// `false` if `caps` holds one the task does not.
+    fn set_current_caps(&self, caps: usize) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        let cur = inner.current_task;
+        if !caps::may_set(inner.tasks[cur].caps, caps) {
+            return false;
+        }
+        inner.tasks[cur].caps = caps;
+        true
+    }
+
+    /// Returns `true` if the current task is a deadline task waiting for its next release.
+    fn current_throttled(&self) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        let cur = inner.current_task;
+        inner.tasks[cur]
+            .deadline
+            .as_mut()
+            .is_some_and(|deadline| deadline.is_throttled(get_time_ns()))
+    }
+
+    /// Add a group of `weight` and return its id, `None` if `weight` is out of range or
+    /// there are too many groups.
+    fn create_group(&self, weight: usize) -> Option<usize> {
+        let mut inner = self.inner.exclusive_access();
+        if weight == 0 || weight > MAX_WEIGHT || inner.groups.len() == MAX_GROUPS {
+            return None;
+        }
+        // start level with the others instead of catching up on all ticks since boot
+        let pass = inner
+            .groups
+            .iter()
+            .map(|group| group.pass)
+            .min()
+            .unwrap_or(0);
+        inner.groups.push(TaskGroup::new(weight, pass));
+        Some(inner.groups.len() - 1)
+    }
+
+    /// Move the current task to group `group`, `false` if there is no such group.
+    fn attach_current(&self, group: usize) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        if group >= inner.groups.len() {
+            return false;
+        }
+        let cur = inner.current_task;
+        inner.tasks[cur].group = group;
+        true
+    }
+
+    fn group_stat(&self, group: usize) -> Option<GroupStat> {
+        let inner = self.inner.exclusive_access();
+        let task_group = inner.groups.get(group)?;
+        Some(GroupStat {
+            weight: task_group.weight,
+            tasks: inner
+                .tasks
+                .iter()
+                .filter(|task| task.group == group && task.task_status != TaskStatus::Exited)
+                .count(),
+            ticks: task_group.ticks,
+        })
+    }
+
+    /// Returns `true` once every user task has exited.
+    ///
+    /// Kthreads are service tasks and do not keep the system alive on their own.
+    fn all_user_tasks_exited(&self) -> bool {
+        let inner = self.inner.exclusive_access();
+        inner
+            .tasks
+            .iter()
+            .all(|task| task.is_kthread() || task.task_status == TaskStatus::Exited)
+    }
+
+    /// Append a kthread called `name` running `entry` and return its task id.
+    fn add_kthread(&self, name: &'static str, entry: fn()) -> usize {
+        let task_id = self.inner.exclusive_access().tasks.len();
+        // KERNEL_SPACE is borrowed while building the kernel stack, keep inner released
+        let task = TaskControlBlock::new_kthread(task_id, name, entry);
+        self.inner.exclusive_access().tasks.push(task);
+        #[cfg(feature = "sched_trace")]
+        crate::trace::record(crate::trace::TraceEvent::Spawn, task_id, 1);
+        trace!("[kernel] spawned kthread '{name}' (task {task_id})");
+        task_id
+    }
+
+    fn get_current_kthread_entry(&self) -> fn() {
+        let inner = self.inner.exclusive_access();
+        inner.tasks[inner.current_task]
+            .kthread_entry
+            .expect("current task is not a kthread")
+    }
+
+    fn stats(&self) -> TaskStats {
+        let inner = self.inner.exclusive_access();
+        let mut stats = TaskStats {
+            context_switches: inner.context_switches,
+            ..TaskStats::default()
+        };
+        for task in inner.tasks.iter() {
+            if task.is_kthread() {
+                stats.kthreads += 1;
+            }
+            stats.voluntary_switches += task.voluntary_switches;
+            stats.involuntary_switches += task.involuntary_switches;
+            match task.task_status {
+                TaskStatus::Ready => stats.ready += 1,
+                TaskStatus::Running => stats.running += 1,
+                TaskStatus::Exited => stats.exited += 1,
+                TaskStatus::Blocked => stats.blocked += 1,
+            }
+        }
+        stats
+    }
+
+    fn get_current_id(&self) -> usize {
+        self.inner.exclusive_access().current_task
+    }
+
+    fn get_current_label(&self) -> TaskLabel {
+        let inner = self.inner.exclusive_access();
+        inner.label(inner.current_task)
+    }
+
+    fn task_info(&self, task_id: usize) -> Option<TaskInfo> {
+        let inner = self.inner.exclusive_access();
+        let task = inner.tasks.get(task_id)?;
+        Some(TaskInfo {
+            id: task_id,
+            status: task.task_status as usize,
+            kthread: task.is_kthread() as usize,
+            group: task.group,
+            voluntary_switches: task.voluntary_switches,
+            involuntary_switches: task.involuntary_switches,
+            name: name_bytes(task.name),
+        })
+    }
+
+    fn current_rusage(&self) -> RUsage {
+        let mut inner = self.inner.exclusive_access();
+        let cur = inner.current_task;
+        let task = &mut inner.tasks[cur];
+        task.sample_peak_frames();
+        RUsage {
+            user_ns: task.user_ticks as u64 * TICK_NS,
+            kernel_ns: task.kernel_ticks as u64 * TICK_NS,
+            peak_frames: task.peak_frames,
+            minor_faults: task.minor_faults,
+            major_faults: task.major_faults,
+            voluntary_switches: task.voluntary_switches,
+            involuntary_switches: task.involuntary_switches,
+        }
+    }
+
+    /// Charge the current task one timer tick and return its ticks since the last syscall.
+    fn tick_current_watchdog(&self) -> usize {
+        let mut inner = self.inner.exclusive_access();
+        let cur = inner.current_task;
+        inner.tasks[cur].watchdog_ticks += 1;
+        inner.tasks[cur].watchdog_ticks
+    }
+
+    fn feed_current_watchdog(&self) {
+        let mut inner = self.inner.exclusive_access();
+        let cur = inner.current_task;
+        inner.tasks[cur].watchdog_ticks = 0;
+    }
+
+    fn set_current_in_syscall(&self, in_syscall: bool) {
+        let mut inner = self.inner.exclusive_access();
+        let cur = inner.current_task;
+        inner.tasks[cur].in_syscall = in_syscall;
+    }
+
+    /// Take the reschedule request of the current task, see `request_resched`.
+    fn take_current_need_resched(&self) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        let cur = inner.current_task;
+        let task = &mut inner.tasks[cur];
+        task.need_resched |= RESCHED_PENDING.swap(false, Ordering::Relaxed);
+        core::mem::take(&mut task.need_resched)
+    }
+
+    /// Leave the syscall the current task panicked in and return the task id.
+    ///
+    /// Returns `None` if the panic interrupted the task manager itself or the current task
+    /// is not in a syscall.
+    fn oops_current(&self) -> Option<usize> {
+        let mut inner = self.inner.try_access()?;
+        let cur = inner.current_task;
+        let task = &mut inner.tasks[cur];
+        if !task.in_syscall {
+            return None;
+        }
+        task.in_syscall = false;
+        Some(cur)
+    }
+
+    fn with_current_memory_set<R>(&self, f: impl FnOnce(&mut MemorySet) -> R) -> Option<R> {
+        let mut inner = self.inner.exclusive_access();
+        let cur = inner.current_task;
+        inner.tasks[cur].memory_set.as_mut().map(f)
+    }
+
+    fn handle_current_page_fault(&self, va: usize, write: bool) -> Result<(), PageFaultError> {
+        let mut inner = self.inner.exclusive_access();
+        let cur = inner.current_task;
+        let task = &mut inner.tasks[cur];
+        let memory_set = task.memory_set.as_mut().ok_or(PageFaultError::BadAddress)?;
+        memory_set.handle_page_fault(va.into(), write)?;
+        task.minor_faults += 1;
+        task.sample_peak_frames();
+        Ok(())
     }
 
     fn get_current_token(&self) -> usize {
@@ -97,12 +552,102 @@ impl TaskManager {
         inner.tasks[inner.current_task].get_trap_cx()
     }
 
-    fn run_next_task(&self) {
-        if let Some(next) = self.find_next_task() {
+    /// Power off once all user tasks exited, QEMU exits with the number of failed tasks.
+    fn finish(&self) -> ! {
+        let failed = self.inner.exclusive_access().failed_tasks;
+        if failed == 0 {
+            println!("All applications completed!");
+        } else {
+            println!("All applications completed, {} failed!", failed);
+        }
+        // exit statuses are 8 bits wide and 255 reports a kernel panic
+        finisher::exit(failed.min(254) as u16)
+    }
+
+    /// Wait until a blocked task is woken while no task is ready.
+    ///
+    /// The periodic tick would only wake the hart for nothing meanwhile, so the timer is
+    /// programmed for the earliest kernel timer instead and the hart waits in `wfi`; the tick
+    /// is re-armed as soon as a task is ready.
+    ///
+    /// # Returns
+    /// The task to run next, or `None` if no task is blocked either.
+    fn idle(&self) -> Option<usize> {
+        let mut tickless = false;
+        while self.any_blocked() {
+            run_expired_timers();
+            if let Some(next) = self.find_next_task(false) {
+                if tickless {
+                    set_next_trigger();
+                }
+                return Some(next);
+            }
+            // a deadline already passed leaves the interrupt pending and `wfi` returns at once
+            set_trigger_at_ns(next_deadline());
+            tickless = true;
+            // wakes on the pending timer even with interrupts disabled, as `sie.STIE` is set
+            unsafe { core::arch::asm!("wfi") };
+        }
+        if tickless {
+            set_next_trigger();
+        }
+        None
+    }
+
+    /// Switch to the next task; `yielded` tells whether the current task gave up the CPU
+    /// itself or was preempted. The hart idles while all tasks are blocked, see `idle`.
+    fn run_next_task(&self, yielded: bool) {
+        // a nested trap must not see the task manager mid-switch, nor run timers from `idle`;
+        // restored once the current task is switched back to
+        let _interrupts = InterruptGuard::disable();
+        if self.all_user_tasks_exited() {
+            self.finish();
+        }
+
+        run_expired_timers();
+        if let Some(next) = self.find_next_task(yielded).or_else(|| self.idle()) {
             let mut inner = self.inner.exclusive_access();
             let current = inner.current_task;
             inner.tasks[next].task_status = TaskStatus::Running;
+            if let Some(deadline) = inner.tasks[next].deadline.as_mut() {
+                deadline.replenish(get_time_ns());
+            }
             inner.current_task = next;
+            // giving up the CPU serves a pending reschedule, it must not carry over to `next`
+            RESCHED_PENDING.store(false, Ordering::Relaxed);
+            inner.tasks[current].need_resched = false;
+            if next != current {
+                trace!(
+                    "[kernel] switch from {} to {}",
+                    inner.label(current),
+                    inner.label(next)
+                );
+                inner.context_switches += 1;
+                let task = &mut inner.tasks[current];
+                if task.task_status != TaskStatus::Exited {
+                    if yielded {
+                        task.voluntary_switches += 1;
+                    } else {
+                        task.involuntary_switches += 1;
+                    }
+                }
+                #[cfg(feature = "sched_trace")]
+                {
+                    use crate::trace::{SchedOutReason, TraceEvent, record};
+                    let reason = if task.task_status == TaskStatus::Exited {
+                        SchedOutReason::Exited
+                    } else if yielded {
+                        SchedOutReason::Yielded
+                    } else {
+                        SchedOutReason::Preempted
+                    };
+                    record(TraceEvent::SchedOut, current, reason as usize);
+                    record(TraceEvent::SchedIn, next, 0);
+                }
+                // the next task must not inherit the injected faults of a yielding syscall
+                #[cfg(feature = "fault_injection")]
+                crate::fault_inject::exit();
+            }
             let current_task_cx_ptr = &mut inner.tasks[current].task_cx as *mut TaskContext;
             let next_task_cx_ptr = &inner.tasks[next].task_cx as *const TaskContext;
 
@@ -112,24 +657,435 @@ impl TaskManager {
                 __switch(current_task_cx_ptr, next_task_cx_ptr);
             }
         } else {
-            println!("All applications completed!");
-            shutdown(false);
+            self.finish();
         }
     }
+
+    /// Run task `task_id` until it exits, see `run_task_for_test`.
+    #[cfg(test)]
+    fn run_task_for_test(&self, task_id: usize) -> i32 {
+        let mut inner = self.inner.exclusive_access();
+        let prev = inner.current_task;
+        inner.current_task = task_id;
+        inner.tasks[task_id].task_status = TaskStatus::Running;
+        let next_task_cx_ptr = &inner.tasks[task_id].task_cx as *const TaskContext;
+        drop(inner);
+        TEST_TASK.store(task_id, Ordering::Relaxed);
+        unsafe {
+            __switch(&raw mut TEST_RETURN_CX, next_task_cx_ptr);
+        }
+        TEST_TASK.store(usize::MAX, Ordering::Relaxed);
+        self.inner.exclusive_access().current_task = prev;
+        TEST_EXIT_CODE.load(Ordering::Relaxed)
+    }
+}
+
+/// The task `run_task_for_test` is running, `usize::MAX` if none.
+#[cfg(test)]
+static TEST_TASK: AtomicUsize = AtomicUsize::new(usize::MAX);
+/// The exit code `TEST_TASK` exited with.
+#[cfg(test)]
+static TEST_EXIT_CODE: AtomicI32 = AtomicI32::new(0);
+/// Where `run_task_for_test` switched away from.
+#[cfg(test)]
+static mut TEST_RETURN_CX: TaskContext = TaskContext::empty();
+
+/// Run task `task_id` from a test, for kernel code that only runs in a task.
+///
+/// Instead of switching to the next task, `exit_current_and_run_next` switches back to the
+/// test when the task exits.
+///
+/// # Returns
+/// The exit code of the task.
+#[cfg(test)]
+pub fn run_task_for_test(task_id: usize) -> i32 {
+    TASK_MANAGER.run_task_for_test(task_id)
 }
 
 pub fn run_first_task() -> ! {
     TASK_MANAGER.run_first_task();
 }
 
+/// Preempt the current task and switch to the next one.
 pub fn suspend_current_and_run_next() {
     TASK_MANAGER.mark_current_suspended();
-    TASK_MANAGER.run_next_task();
+    TASK_MANAGER.run_next_task(false);
+    wait_while_throttled();
+}
+
+/// Give up the CPU: the current task runs again after every other ready task.
+pub fn yield_current_and_run_next() {
+    TASK_MANAGER.mark_current_suspended();
+    TASK_MANAGER.run_next_task(true);
+    wait_while_throttled();
+}
+
+/// Block the current task until `wake_task` and switch to the next one.
+pub fn block_current_and_run_next() {
+    TASK_MANAGER.mark_current_blocked();
+    TASK_MANAGER.run_next_task(true);
+    wait_while_throttled();
+}
+
+/// Make task `task_id` ready again if it is blocked.
+pub fn wake_task(task_id: usize) {
+    TASK_MANAGER.wake_task(task_id);
+}
+
+/// Make every blocked task ready again, e.g. for them to check a clock that was set.
+pub fn wake_all() {
+    TASK_MANAGER.wake_all();
+}
+
+/// Let the other tasks run while the current task is a throttled deadline task, so it
+/// continues with its next job.
+fn wait_while_throttled() {
+    while TASK_MANAGER.current_throttled() {
+        TASK_MANAGER.mark_current_suspended();
+        TASK_MANAGER.run_next_task(true);
+    }
+}
+
+/// Move the current task to the deadline scheduling class with `param`, or back to the normal
+/// class with `None`, see `deadline`.
+///
+/// # Returns
+/// `false` if admission control refuses `param`.
+pub fn set_current_deadline(param: Option<SchedParam>) -> bool {
+    TASK_MANAGER.set_current_deadline(param)
+}
+
+/// Returns the capabilities of the current task, `CAP_*` bits.
+pub fn current_caps() -> usize {
+    TASK_MANAGER.current_caps()
+}
+
+/// Returns `true` if the current task holds capability `cap`.
+pub fn current_has_cap(cap: usize) -> bool {
+    current_caps() & cap != 0
+}
+
+/// Drop the capabilities of the current task to `caps`, `false` if that would add any.
+pub fn set_current_caps(caps: usize) -> bool {
+    TASK_MANAGER.set_current_caps(caps)
+}
+
+/// End the current job of the current task if it is a deadline task; it continues with the
+/// next job once that is released.
+pub fn end_current_job() {
+    TASK_MANAGER.end_current_job();
+}
+
+/// Exit the current task with `exit_code` and switch to the next one.
+pub fn exit_current_and_run_next(exit_code: i32) {
+    #[cfg(feature = "vector")]
+    crate::trap::vector::release(current_task_id());
+    TASK_MANAGER.mark_current_exited(exit_code);
+    #[cfg(test)]
+    if current_task_id() == TEST_TASK.load(Ordering::Relaxed) {
+        TEST_EXIT_CODE.store(exit_code, Ordering::Relaxed);
+        let mut exited = TaskContext::empty();
+        unsafe {
+            __switch(&mut exited, &raw const TEST_RETURN_CX);
+        }
+    }
+    TASK_MANAGER.run_next_task(false);
+}
+
+/// Task counts by state and scheduler counters, see `task_stats`.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TaskStats {
+    pub ready: usize,
+    pub running: usize,
+    pub exited: usize,
+    pub blocked: usize,
+    /// Kthreads, also counted in their state.
+    pub kthreads: usize,
+    pub context_switches: usize,
+    /// Switches away from tasks that yielded or slept, summed over all tasks.
+    pub voluntary_switches: usize,
+    /// Switches away from tasks the timer preempted, summed over all tasks.
+    pub involuntary_switches: usize,
+}
+
+pub fn task_stats() -> TaskStats {
+    TASK_MANAGER.stats()
+}
+
+pub fn current_task_id() -> usize {
+    TASK_MANAGER.get_current_id()
+}
+
+/// A task as it appears in log lines: `'name' (task id)`.
+#[derive(Copy, Clone)]
+pub struct TaskLabel {
+    pub id: usize,
+    pub name: &'static str,
+}
+
+impl fmt::Display for TaskLabel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "'{}' (task {})", self.name, self.id)
+    }
+}
+
+/// Returns the current task for log lines, see `TaskLabel`.
+pub fn current_task_label() -> TaskLabel {
+    TASK_MANAGER.get_current_label()
+}
+
+/// Length of `TaskInfo::name`, including the terminating NUL.
+pub const TASK_NAME_LEN: usize = 32;
+
+/// Returns `name` NUL terminated, truncated to fit.
+fn name_bytes(name: &str) -> [u8; TASK_NAME_LEN] {
+    let mut bytes = [0u8; TASK_NAME_LEN];
+    let len = name.len().min(TASK_NAME_LEN - 1);
+    bytes[..len].copy_from_slice(&name.as_bytes()[..len]);
+    bytes
+}
+
+/// A task as seen by `sys_task_info`, layout compatible with user space.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct TaskInfo {
+    pub id: usize,
+    /// 0 ready, 1 running, 2 exited, 3 blocked.
+    pub status: usize,
+    /// 1 for a kthread.
+    pub kthread: usize,
+    pub group: usize,
+    pub voluntary_switches: usize,
+    pub involuntary_switches: usize,
+    /// NUL terminated, truncated to fit.
+    pub name: [u8; TASK_NAME_LEN],
+}
+
+/// Returns the name, state and scheduler counters of task `task_id`, or `None` if there is
+/// no such task.
+pub fn task_info(task_id: usize) -> Option<TaskInfo> {
+    TASK_MANAGER.task_info(task_id)
+}
+
+/// Resource usage of a task as seen by `sys_getrusage`, layout compatible with user space.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct RUsage {
+    /// Time the task ran in user mode, counted in timer ticks.
+    pub user_ns: u64,
+    /// Time the task ran in syscalls, counted in timer ticks.
+    pub kernel_ns: u64,
+    /// Most frames the address space owned at once.
+    pub peak_frames: usize,
+    /// Page faults resolved without I/O: stack growth and first writes to zero-filled pages.
+    pub minor_faults: usize,
+    /// Page faults that had to read a page back in; always 0 until there is swap.
+    pub major_faults: usize,
+    pub voluntary_switches: usize,
+    pub involuntary_switches: usize,
+}
+
+/// Returns the resource usage of the current task.
+pub fn current_rusage() -> RUsage {
+    TASK_MANAGER.current_rusage()
+}
+
+/// Charge the timer tick the current task ran to the task and its group.
+///
+/// `in_kernel` tells whether the tick went to a syscall of the task rather than to its user
+/// code.
+pub fn charge_current_tick(in_kernel: bool) {
+    TASK_MANAGER.charge_current_tick(in_kernel);
+}
+
+/// Create a task group of `weight`, see `group`.
+///
+/// # Returns
+/// The group id, or `None` if `weight` is 0 or above `MAX_WEIGHT`, or there are too many groups.
+pub fn create_group(weight: usize) -> Option<usize> {
+    TASK_MANAGER.create_group(weight)
+}
+
+/// Move the current task to `group`, `false` if there is no such group.
+pub fn current_attach_group(group: usize) -> bool {
+    TASK_MANAGER.attach_current(group)
+}
+
+pub fn group_stat(group: usize) -> Option<GroupStat> {
+    TASK_MANAGER.group_stat(group)
+}
+
+pub fn current_watchdog_tick() -> usize {
+    TASK_MANAGER.tick_current_watchdog()
+}
+
+pub fn current_watchdog_feed() {
+    TASK_MANAGER.feed_current_watchdog();
+}
+
+/// Mark whether the kernel is servicing a syscall for the current task.
+pub fn set_current_in_syscall(in_syscall: bool) {
+    TASK_MANAGER.set_current_in_syscall(in_syscall);
+}
+
+/// Ask for the current task to be preempted at its next preemption point.
+///
+/// Called from a nested timer interrupt, which may have interrupted the task manager, so the
+/// request is only noted here and moved to the task by `take_current_need_resched`.
+pub fn request_resched() {
+    RESCHED_PENDING.store(true, Ordering::Relaxed);
+}
+
+/// Returns and clears whether the current task used up its time slice in a syscall.
+pub fn take_current_need_resched() -> bool {
+    TASK_MANAGER.take_current_need_resched()
+}
+
+/// Take the current task out of its syscall after a panic in it, see `oops`.
+///
+/// # Returns
+/// The task id, or `None` if the panic has to halt the system.
+pub fn current_oops() -> Option<usize> {
+    TASK_MANAGER.oops_current()
+}
+
+/// Write `bytes` to the console through the current task's line buffer.
+pub fn current_stdout_write(bytes: &[u8]) {
+    TASK_MANAGER.write_current_stdout(bytes);
+}
+
+/// Write out the partial line buffered for the current task.
+pub fn current_stdout_flush() {
+    TASK_MANAGER.flush_current_stdout();
+}
+
+/// Run `f` on the address space of the current task.
+///
+/// # Returns
+/// `None` if the current task is a kthread.
+///
+/// # Note
+/// `f` runs with the task manager borrowed and must not call back into this module.
+pub fn with_current_memory_set<R>(f: impl FnOnce(&mut MemorySet) -> R) -> Option<R> {
+    TASK_MANAGER.with_current_memory_set(f)
 }
 
-pub fn exit_current_and_run_next() {
-    TASK_MANAGER.mark_current_exited();
-    TASK_MANAGER.run_next_task();
+/// Try to resolve a page fault of the current task at `va`, see
+/// `MemorySet::handle_page_fault`.
+pub fn handle_current_page_fault(va: usize, write: bool) -> Result<(), PageFaultError> {
+    TASK_MANAGER.handle_current_page_fault(va, write)
+}
+
+/// Translate the current task's buffer `buf` into per-page slices the kernel can access, for
+/// writing if `write`.
+///
+/// Zero-filled pages under a buffer for writing get their private frame first.
+///
+/// # Returns
+/// `None` if part of the buffer is not mapped accessible to the task, or no frame is left for
+/// a zero-filled page.
+pub fn current_translated_byte_buffer(
+    buf: UserSlice<u8>,
+    write: bool,
+) -> Option<Vec<&'static mut [u8]>> {
+    if !current_user_accessible(buf.addr(), buf.len(), write) {
+        return None;
+    }
+    translated_byte_buffer(current_user_token(), buf.addr() as *const u8, buf.len())
+}
+
+/// Copy `value` to the current task's memory at `ptr`, which may cross page boundaries.
+///
+/// # Returns
+/// `false` if `ptr` does not point to memory the task may write; nothing is copied then.
+pub fn current_copy_out<T: Copy>(ptr: UserPtr<T>, value: &T) -> bool {
+    let src = unsafe {
+        core::slice::from_raw_parts(value as *const T as *const u8, core::mem::size_of::<T>())
+    };
+    current_copy_out_bytes(ptr.addr(), src)
+}
+
+/// Copy `values` to the start of the current task's array `buf`.
+///
+/// # Returns
+/// `false` if `buf` is shorter than `values` or not memory the task may write; nothing is
+/// copied then.
+pub fn current_copy_out_slice<T: Copy>(buf: UserSlice<T>, values: &[T]) -> bool {
+    let src = unsafe {
+        core::slice::from_raw_parts(values.as_ptr() as *const u8, core::mem::size_of_val(values))
+    };
+    values.len() <= buf.len() && current_copy_out_bytes(buf.addr(), src)
+}
+
+/// Copy a `T` from the current task's memory at `ptr`, which may cross page boundaries.
+///
+/// # Returns
+/// `None` if `ptr` does not point to memory the task may read.
+pub fn current_copy_in<T: Copy>(ptr: UserPtr<T>) -> Option<T> {
+    let mut value = core::mem::MaybeUninit::<T>::zeroed();
+    let dst = unsafe {
+        core::slice::from_raw_parts_mut(value.as_mut_ptr() as *mut u8, core::mem::size_of::<T>())
+    };
+    if !current_copy_in_bytes(ptr.addr(), dst) {
+        return None;
+    }
+    Some(unsafe { value.assume_init() })
+}
+
+/// Copy `src` to the current task's memory at `addr`, page by page through the physical
+/// mapping.
+fn current_copy_out_bytes(addr: usize, src: &[u8]) -> bool {
+    if !current_user_accessible(addr, src.len(), true) {
+        return false;
+    }
+    let token = current_user_token();
+    let Some(buffers) = translated_byte_buffer(token, addr as *const u8, src.len()) else {
+        return false;
+    };
+    let mut copied = 0;
+    for dst in buffers {
+        dst.copy_from_slice(&src[copied..copied + dst.len()]);
+        copied += dst.len();
+    }
+    true
+}
+
+/// Fill `dst` from the current task's memory at `addr`, like `current_copy_out_bytes`.
+fn current_copy_in_bytes(addr: usize, dst: &mut [u8]) -> bool {
+    if !current_user_accessible(addr, dst.len(), false) {
+        return false;
+    }
+    let token = current_user_token();
+    let Some(buffers) = translated_byte_buffer(token, addr as *const u8, dst.len()) else {
+        return false;
+    };
+    let mut copied = 0;
+    for src in buffers {
+        dst[copied..copied + src.len()].copy_from_slice(src);
+        copied += src.len();
+    }
+    true
+}
+
+/// Returns `true` if every page of `[start, start + len)` is mapped for the current task to
+/// read, or to write if `write`.
+///
+/// Zero-filled pages of a range for writing get their private frame first, as stores would
+/// fault on the shared zero page.
+fn current_user_accessible(start: usize, len: usize, write: bool) -> bool {
+    let Some(end) = start.checked_add(len) else {
+        return false;
+    };
+    if end > USER_SPACE_END {
+        return false;
+    }
+    with_current_memory_set(|memory_set| {
+        if write {
+            memory_set.fill_zero_pages(start.into(), end.into());
+        }
+        memory_set.is_user_accessible(start.into(), end.into(), write)
+    })
+    .unwrap_or(false)
 }
 
 pub fn current_user_token() -> usize {
@@ -1,58 +1,117 @@
 use super::TaskContext;
+use super::caps::initial_caps;
+use super::deadline::DeadlineTask;
 use crate::config::{TRAP_CONTEXT_ADDR, kernel_stack_pos};
-use crate::mm::{KERNEL_SPACE, MapPermission, MemorySet, PhysPageNum, VirtAddr};
+use crate::console::LineBuffer;
+use crate::loader::FLAT_APP_SUFFIX;
+use crate::mm::{KERNEL_SPACE, LoadError, MapPermission, MemorySet, PhysPageNum, VirtAddr};
 use crate::trap::{TrapContext, trap_handler};
 
 /// The TaskControlBlock holds all information needed to manage and schedule a task.
 ///
 /// Fields:
+/// - `name`: The name of the app the task runs, or of the kthread.
 /// - `task_status`: The current status of the task (e.g., Ready, Running, Exited).
 /// - `task_ctx`: The saved CPU context for context switching.
-/// - `memory_set`: The address space and memory mappings for the task.
-/// - `trap_ctx_ppn`: The physical page number of the trap context for this task.
+/// - `memory_set`: The address space and memory mappings for the task (`None` for kthreads).
+/// - `trap_ctx_ppn`: The physical page number of the trap context for this task (`None` for kthreads).
 /// - `base_size`: The size of the application from address 0x0 to the top of the user stack.
+/// - `kthread_entry`: The kernel function run by a kthread (`None` for user tasks).
+/// - `watchdog_ticks`: Timer ticks taken in user mode since the task's last syscall.
+/// - `in_syscall`: Whether the kernel is servicing a syscall for the task.
+/// - `need_resched`: Whether the task used up its time slice in a syscall, see `request_resched`.
+/// - `stdout`: Console output of the task not yet written out, see `LineBuffer`.
+/// - `group`: The task group the task's CPU time is charged to.
+/// - `user_ticks`, `kernel_ticks`: Timer ticks the task ran in user mode and in syscalls.
+/// - `peak_frames`: Most frames the address space owned at once, sampled on page faults,
+///   timer ticks and exit.
+/// - `minor_faults`, `major_faults`: Page faults resolved without and with I/O, see `RUsage`.
+/// - `deadline`: The state of a task in the deadline scheduling class, see `deadline`.
+/// - `caps`: The capabilities the task holds, `CAP_*` bits, see `caps`.
 pub struct TaskControlBlock {
+    pub name: &'static str,
     pub task_status: TaskStatus,
     pub task_cx: TaskContext,
-    pub memory_set: MemorySet,
-    pub trap_cx_ppn: PhysPageNum,
+    pub memory_set: Option<MemorySet>,
+    pub trap_cx_ppn: Option<PhysPageNum>,
     pub base_size: usize,
+    pub kthread_entry: Option<fn()>,
+    pub watchdog_ticks: usize,
+    pub in_syscall: bool,
+    pub need_resched: bool,
+    pub stdout: LineBuffer,
+    pub group: usize,
+    /// Times this task gave up the CPU to another task by yielding or sleeping.
+    pub voluntary_switches: usize,
+    /// Times this task was preempted by the timer for another task.
+    pub involuntary_switches: usize,
+    pub user_ticks: usize,
+    pub kernel_ticks: usize,
+    pub peak_frames: usize,
+    pub minor_faults: usize,
+    pub major_faults: usize,
+    pub deadline: Option<DeadlineTask>,
+    pub caps: usize,
 }
 
 impl TaskControlBlock {
-    /// Create a new `TaskControlBlock` from an ELF binary and application ID.
+    /// Create a new `TaskControlBlock` from an ELF binary and task ID.
     ///
     /// This function sets up the address space, kernel/user stacks, and trap context
     /// for a new user application. It loads the ELF, allocates the kernel stack,
     /// initializes the trap context, and prepares the task for scheduling.
     ///
     /// # Arguments
-    /// * `elf_data` - The ELF binary data for the application.
-    /// * `app_id` - The application identifier (used for kernel stack allocation).
+    /// * `task_id` - The task identifier (used for kernel stack allocation).
+    /// * `name` - The name of the application.
+    /// * `elf_data` - The ELF binary data for the application, or a flat binary if `name`
+    ///   ends with `FLAT_APP_SUFFIX`.
     ///
     /// # Returns
-    /// A fully initialized `TaskControlBlock` ready to be scheduled.
-    pub fn new(app_id: usize, elf_data: &[u8]) -> Self {
+    /// A fully initialized `TaskControlBlock` ready to be scheduled, or why the ELF cannot
+    /// be loaded.
+    pub fn new(task_id: usize, name: &'static str, elf_data: &[u8]) -> Result<Self, LoadError> {
         // memory_set with elf program headers/trampoline/trap context/user stack
-        let (memory_set, user_sp, entry_point) = MemorySet::from_elf(elf_data);
+        let (memory_set, user_sp, entry_point) = if name.ends_with(FLAT_APP_SUFFIX) {
+            MemorySet::from_flat(elf_data)?
+        } else {
+            MemorySet::from_elf(elf_data)?
+        };
         let trap_cx_ppn = memory_set
             .translate(VirtAddr::from(TRAP_CONTEXT_ADDR).floor())
             .unwrap()
             .ppn();
         let task_status = TaskStatus::Ready;
+        let peak_frames = memory_set.frame_count();
 
-        let (kernel_stack_bottom, kernel_stack_top) = kernel_stack_pos(app_id);
+        let (kernel_stack_bottom, kernel_stack_top) = kernel_stack_pos(task_id);
         KERNEL_SPACE.exclusive_access().insert_framed_area(
             kernel_stack_bottom.into(),
             kernel_stack_top.into(),
             MapPermission::R | MapPermission::W,
         );
         let task_control_block = Self {
+            name,
             task_status,
             task_cx: TaskContext::goto_trap_return(kernel_stack_top),
-            memory_set,
-            trap_cx_ppn,
+            memory_set: Some(memory_set),
+            trap_cx_ppn: Some(trap_cx_ppn),
             base_size: user_sp.bits(),
+            kthread_entry: None,
+            watchdog_ticks: 0,
+            in_syscall: false,
+            need_resched: false,
+            stdout: LineBuffer::default(),
+            group: 0,
+            voluntary_switches: 0,
+            involuntary_switches: 0,
+            user_ticks: 0,
+            kernel_ticks: 0,
+            peak_frames,
+            minor_faults: 0,
+            major_faults: 0,
+            deadline: None,
+            caps: initial_caps(name),
         };
 
         let trap_cx = task_control_block.get_trap_cx();
@@ -63,23 +122,89 @@ impl TaskControlBlock {
             kernel_stack_top,
             trap_handler as usize,
         );
-        task_control_block
+        trap_cx.set_canary();
+        Ok(task_control_block)
+    }
+
+    /// Create a new kernel thread `TaskControlBlock`.
+    ///
+    /// A kthread has no user address space and no trap context: it runs `entry` in S-mode
+    /// on its own kernel stack inside `KERNEL_SPACE`, and exits when `entry` returns.
+    ///
+    /// # Arguments
+    /// * `task_id` - The task identifier (used for kernel stack allocation).
+    /// * `name` - The name of the kthread, for logs and `sys_task_info`.
+    /// * `entry` - The kernel function to run.
+    pub fn new_kthread(task_id: usize, name: &'static str, entry: fn()) -> Self {
+        let (kernel_stack_bottom, kernel_stack_top) = kernel_stack_pos(task_id);
+        KERNEL_SPACE.exclusive_access().insert_framed_area(
+            kernel_stack_bottom.into(),
+            kernel_stack_top.into(),
+            MapPermission::R | MapPermission::W,
+        );
+        Self {
+            name,
+            task_status: TaskStatus::Ready,
+            task_cx: TaskContext::goto_kthread_entry(kernel_stack_top),
+            memory_set: None,
+            trap_cx_ppn: None,
+            base_size: 0,
+            kthread_entry: Some(entry),
+            watchdog_ticks: 0,
+            in_syscall: false,
+            need_resched: false,
+            stdout: LineBuffer::default(),
+            group: 0,
+            voluntary_switches: 0,
+            involuntary_switches: 0,
+            user_ticks: 0,
+            kernel_ticks: 0,
+            peak_frames: 0,
+            minor_faults: 0,
+            major_faults: 0,
+            deadline: None,
+            // kthreads make no syscalls
+            caps: 0,
+        }
+    }
+
+    /// Raise `peak_frames` to the frames the address space owns now.
+    pub fn sample_peak_frames(&mut self) {
+        if let Some(memory_set) = &self.memory_set {
+            self.peak_frames = self.peak_frames.max(memory_set.frame_count());
+        }
+    }
+
+    /// Returns `true` if this task is a kernel thread.
+    pub fn is_kthread(&self) -> bool {
+        self.kthread_entry.is_some()
     }
 
     /// Returns a mutable reference to the trap context for this task.
     ///
     /// The trap context holds the processor state to be restored when returning
     /// from a trap (interrupt, exception, or syscall).
+    ///
+    /// # Panics
+    /// Panics if the task is a kthread.
     pub fn get_trap_cx(&self) -> &'static mut TrapContext {
-        self.trap_cx_ppn.get_mut()
+        self.trap_cx_ppn
+            .expect("kthread has no trap context")
+            .get_mut()
     }
 
     /// Returns the SATP value for this task's address space.
     ///
     /// This value encodes the page table root and mode for address translation,
     /// and is used to activate the task's memory mapping.
+    ///
+    /// # Panics
+    /// Panics if the task is a kthread.
     pub fn get_user_token(&self) -> usize {
-        self.memory_set.token()
+        self.memory_set
+            .as_ref()
+            .expect("kthread has no user address space")
+            .token()
     }
 }
 
@@ -88,4 +213,6 @@ pub enum TaskStatus {
     Ready,
     Running,
     Exited,
+    /// Waiting for `wake_task`, e.g. from a timer.
+    Blocked,
 }
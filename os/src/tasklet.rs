@@ -0,0 +1,76 @@
+//! Deferred work (tasklets) for interrupt handlers.
+//!
+//! Interrupt handlers should only do the minimum in the trap path and push heavier work here
+//! with [`tasklet_schedule`]. Pending tasklets run in FIFO order from `trap_return`, before
+//! any task gets back to user mode, so they take priority over normal tasks.
+
+use crate::sync::UPSafeCell;
+use alloc::collections::vec_deque::VecDeque;
+use lazy_static::*;
+
+/// Maximum number of tasklets run on one return to user mode.
+///
+/// The remaining ones wait for the next trap, so a flood of deferred work can't starve tasks.
+const MAX_TASKLETS_PER_RUN: usize = 32;
+
+/// A deferred call of `func(data)`.
+#[derive(Copy, Clone)]
+struct Tasklet {
+    func: fn(usize),
+    data: usize,
+}
+
+lazy_static! {
+    static ref TASKLET_QUEUE: UPSafeCell<VecDeque<Tasklet>> =
+        unsafe { UPSafeCell::new(VecDeque::new()) };
+}
+
+/// Schedule `func(data)` to run before the next return to user mode.
+pub fn tasklet_schedule(func: fn(usize), data: usize) {
+    TASKLET_QUEUE
+        .exclusive_access()
+        .push_back(Tasklet { func, data });
+}
+
+/// Run pending tasklets, at most `MAX_TASKLETS_PER_RUN` of them.
+pub fn do_tasklets() {
+    for _ in 0..MAX_TASKLETS_PER_RUN {
+        // the queue is released before the call, so a tasklet may schedule more work
+        let Some(tasklet) = TASKLET_QUEUE.exclusive_access().pop_front() else {
+            break;
+        };
+        (tasklet.func)(tasklet.data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    lazy_static! {
+        static ref RAN: UPSafeCell<Vec<usize>> = unsafe { UPSafeCell::new(Vec::new()) };
+    }
+
+    fn record(data: usize) {
+        RAN.exclusive_access().push(data);
+    }
+
+    #[test_case]
+    fn scheduled_tasklets_run_in_order() {
+        for data in 0..MAX_TASKLETS_PER_RUN + 2 {
+            tasklet_schedule(record, data);
+        }
+        assert!(RAN.exclusive_access().is_empty());
+        do_tasklets();
+        let first_run: Vec<usize> = (0..MAX_TASKLETS_PER_RUN).collect();
+        assert_eq!(*RAN.exclusive_access(), first_run);
+        // the rest wait for the next run
+        do_tasklets();
+        assert_eq!(RAN.exclusive_access().len(), MAX_TASKLETS_PER_RUN + 2);
+        assert_eq!(
+            RAN.exclusive_access()[MAX_TASKLETS_PER_RUN..],
+            [MAX_TASKLETS_PER_RUN, MAX_TASKLETS_PER_RUN + 1]
+        );
+    }
+}
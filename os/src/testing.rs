@@ -0,0 +1,31 @@
+//! Kernel unit tests, run under QEMU by `make test`.
+//!
+//! The os crate uses Rust's custom test framework: `cargo test` collects every `#[test_case]`
+//! function into `test_main`, which `rust_main` calls instead of running the user apps once
+//! memory management is up. A test fails by panicking; the panic handler then makes QEMU
+//! exit with `EXIT_PANIC`.
+
+use crate::finisher;
+
+/// A test the runner can execute.
+pub trait Testable {
+    fn run(&self);
+}
+
+impl<T: Fn()> Testable for T {
+    fn run(&self) {
+        print!("test {} ... ", core::any::type_name::<T>());
+        self();
+        println!("ok");
+    }
+}
+
+/// Run all `tests` and exit QEMU with status 0, the test runner of the os crate.
+pub fn run_tests(tests: &[&dyn Testable]) {
+    println!("running {} tests", tests.len());
+    for test in tests {
+        test.run();
+    }
+    println!("test result: ok. {} passed", tests.len());
+    finisher::exit(0)
+}
@@ -1,9 +1,42 @@
 use crate::config::CLOCK_FREQ;
 use crate::sbi::set_timer;
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use riscv::register::time;
 
 const TICKS_PER_SEC: u64 = 100;
 const MSEC_PER_SEC: u64 = 1000;
+const NSEC_PER_SEC: u64 = 1_000_000_000;
+
+/// Length of a timer tick in ns.
+pub const TICK_NS: u64 = NSEC_PER_SEC / TICKS_PER_SEC;
+
+/// A `struct timespec` shared with user programs.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TimeSpec {
+    pub sec: usize,
+    pub nsec: usize,
+}
+
+impl TimeSpec {
+    /// Returns `true` if `nsec` is within `[0, 1s)`.
+    pub fn is_valid(&self) -> bool {
+        (self.nsec as u64) < NSEC_PER_SEC
+    }
+
+    pub fn to_ns(self) -> u64 {
+        (self.sec as u64)
+            .saturating_mul(NSEC_PER_SEC)
+            .saturating_add(self.nsec as u64)
+    }
+
+    pub fn from_ns(ns: u64) -> Self {
+        Self {
+            sec: (ns / NSEC_PER_SEC) as usize,
+            nsec: (ns % NSEC_PER_SEC) as usize,
+        }
+    }
+}
 
 /// Returns the current time in cycles since boot.
 ///
@@ -18,6 +51,63 @@ pub fn get_time_ms() -> u64 {
     time::read64() / (CLOCK_FREQ / MSEC_PER_SEC)
 }
 
+/// Returns the time since boot in nanoseconds (resolution is one timer cycle).
+pub fn get_time_ns() -> u64 {
+    (time::read64() as u128 * NSEC_PER_SEC as u128 / CLOCK_FREQ as u128) as u64
+}
+
+/// Wall clock time, settable, in ns since the Unix epoch.
+pub const CLOCK_REALTIME: usize = 0;
+/// Time since boot in ns, never set and never going backwards.
+pub const CLOCK_MONOTONIC: usize = 1;
+
+/// `CLOCK_REALTIME` minus `CLOCK_MONOTONIC`, in ns.
+static REALTIME_OFFSET_NS: AtomicU64 = AtomicU64::new(0);
+
+/// Start `CLOCK_REALTIME` at the wall clock time the RTC reports.
+pub fn init_realtime() {
+    set_realtime_ns(crate::rtc::read_ns());
+}
+
+/// Returns the time of clock `clock_id` in ns, `None` for an unknown clock.
+pub fn clock_now_ns(clock_id: usize) -> Option<u64> {
+    let now = get_time_ns();
+    match clock_id {
+        CLOCK_REALTIME => Some(now.wrapping_add(REALTIME_OFFSET_NS.load(Ordering::Relaxed))),
+        CLOCK_MONOTONIC => Some(now),
+        _ => None,
+    }
+}
+
+/// Set `CLOCK_REALTIME` to `ns`. `CLOCK_MONOTONIC` is not affected.
+pub fn set_realtime_ns(ns: u64) {
+    REALTIME_OFFSET_NS.store(ns.wrapping_sub(get_time_ns()), Ordering::Relaxed);
+}
+
+/// Timer interrupts handled since boot.
+static TICKS: AtomicUsize = AtomicUsize::new(0);
+
+/// Count one handled timer interrupt.
+pub fn record_tick() {
+    TICKS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Returns the number of timer interrupts handled since boot.
+pub fn ticks() -> usize {
+    TICKS.load(Ordering::Relaxed)
+}
+
 pub fn set_next_trigger() {
     set_timer(get_time() + CLOCK_FREQ / TICKS_PER_SEC);
 }
+
+/// Program the next timer interrupt for `deadline_ns` since boot instead of the next tick,
+/// or for the next tick if there is no deadline, see `TaskManager::idle`.
+pub fn set_trigger_at_ns(deadline_ns: Option<u64>) {
+    match deadline_ns {
+        Some(deadline_ns) => {
+            set_timer((deadline_ns as u128 * CLOCK_FREQ as u128 / NSEC_PER_SEC as u128) as u64)
+        }
+        None => set_next_trigger(),
+    }
+}
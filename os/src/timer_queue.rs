@@ -0,0 +1,191 @@
+//! Kernel timers
+//!
+//! A timer calls `func(data)` once the boot clock reaches its deadline. Armed timers sit in a
+//! binary heap ordered by deadline, so arming one is O(log n) and finding the next one O(1).
+//! Cancelling only forgets the callback: the heap entry stays behind as a tombstone and is
+//! dropped when it reaches the top, or when tombstones outnumber the armed timers.
+//!
+//! Expired timers run from `run_expired_timers`, whenever the scheduler picks the next task
+//! and while the hart idles waiting for the earliest one, see `next_deadline`.
+
+use crate::sync::UPSafeCell;
+use crate::timer::get_time_ns;
+use alloc::collections::binary_heap::BinaryHeap;
+use alloc::collections::btree_map::BTreeMap;
+use core::cmp::Reverse;
+use lazy_static::*;
+
+/// Handle of an armed timer, for `cancel_timer`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct TimerId(u64);
+
+/// The callback of a timer and its argument.
+#[derive(Copy, Clone)]
+struct Timer {
+    func: fn(usize),
+    data: usize,
+}
+
+struct TimerQueue {
+    /// Deadlines in ns since boot and timer ids, earliest first, cancelled ones included.
+    heap: BinaryHeap<Reverse<(u64, u64)>>,
+    /// Armed timers by id.
+    timers: BTreeMap<u64, Timer>,
+    next_id: u64,
+}
+
+impl TimerQueue {
+    fn new() -> Self {
+        Self {
+            heap: BinaryHeap::new(),
+            timers: BTreeMap::new(),
+            next_id: 0,
+        }
+    }
+
+    fn add(&mut self, deadline_ns: u64, timer: Timer) -> TimerId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.heap.push(Reverse((deadline_ns, id)));
+        self.timers.insert(id, timer);
+        TimerId(id)
+    }
+
+    fn cancel(&mut self, id: TimerId) -> bool {
+        if self.timers.remove(&id.0).is_none() {
+            return false;
+        }
+        // keep tombstones from piling up when most timers are cancelled long before expiry
+        if self.heap.len() > 2 * self.timers.len() + 16 {
+            let timers = &self.timers;
+            self.heap.retain(|Reverse((_, id))| timers.contains_key(id));
+        }
+        true
+    }
+
+    #[cfg(test)]
+    fn is_pending(&self, id: TimerId) -> bool {
+        self.timers.contains_key(&id.0)
+    }
+
+    /// Returns the earliest deadline of an armed timer, dropping tombstones in front of it.
+    fn next_deadline(&mut self) -> Option<u64> {
+        while let Some(&Reverse((deadline_ns, id))) = self.heap.peek() {
+            if self.timers.contains_key(&id) {
+                return Some(deadline_ns);
+            }
+            self.heap.pop();
+        }
+        None
+    }
+
+    /// Remove and return the earliest timer whose deadline is not after `now_ns`.
+    fn pop_expired(&mut self, now_ns: u64) -> Option<Timer> {
+        while let Some(&Reverse((deadline_ns, id))) = self.heap.peek() {
+            if deadline_ns > now_ns {
+                return None;
+            }
+            self.heap.pop();
+            // a tombstone unless still armed
+            if let Some(timer) = self.timers.remove(&id) {
+                return Some(timer);
+            }
+        }
+        None
+    }
+}
+
+lazy_static! {
+    static ref TIMER_QUEUE: UPSafeCell<TimerQueue> = unsafe { UPSafeCell::new(TimerQueue::new()) };
+}
+
+/// Arm a timer calling `func(data)` once the time since boot reaches `deadline_ns`.
+///
+/// A deadline in the past fires on the next `run_expired_timers`.
+pub fn add_timer(deadline_ns: u64, func: fn(usize), data: usize) -> TimerId {
+    TIMER_QUEUE
+        .exclusive_access()
+        .add(deadline_ns, Timer { func, data })
+}
+
+/// Disarm timer `id`.
+///
+/// # Returns
+/// `false` if it already fired or was cancelled.
+pub fn cancel_timer(id: TimerId) -> bool {
+    TIMER_QUEUE.exclusive_access().cancel(id)
+}
+
+/// Returns the time since boot in ns at which the next timer fires, `None` if none is armed.
+pub fn next_deadline() -> Option<u64> {
+    TIMER_QUEUE.exclusive_access().next_deadline()
+}
+
+/// Call the callbacks of all expired timers, earliest deadline first.
+pub fn run_expired_timers() {
+    let now_ns = get_time_ns();
+    // the queue is released before each call, so a callback may arm or cancel timers
+    loop {
+        let Some(timer) = TIMER_QUEUE.exclusive_access().pop_expired(now_ns) else {
+            break;
+        };
+        (timer.func)(timer.data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    fn timer(data: usize) -> Timer {
+        Timer { func: |_| {}, data }
+    }
+
+    fn expired(queue: &mut TimerQueue, now_ns: u64) -> Vec<usize> {
+        core::iter::from_fn(|| queue.pop_expired(now_ns))
+            .map(|timer| timer.data)
+            .collect()
+    }
+
+    #[test_case]
+    fn timers_expire_in_deadline_order() {
+        let mut queue = TimerQueue::new();
+        queue.add(300, timer(3));
+        queue.add(100, timer(1));
+        queue.add(200, timer(2));
+        queue.add(100, timer(4));
+        assert!(expired(&mut queue, 50).is_empty());
+        assert_eq!(expired(&mut queue, 100), [1, 4]);
+        assert_eq!(expired(&mut queue, 1000), [2, 3]);
+    }
+
+    #[test_case]
+    fn cancelled_timers_do_not_fire() {
+        let mut queue = TimerQueue::new();
+        let first = queue.add(100, timer(1));
+        let second = queue.add(200, timer(2));
+        assert_eq!(queue.next_deadline(), Some(100));
+        assert!(queue.cancel(first));
+        assert!(!queue.cancel(first));
+        assert_eq!(queue.next_deadline(), Some(200));
+        assert!(!queue.is_pending(first));
+        assert!(queue.is_pending(second));
+        assert_eq!(expired(&mut queue, 1000), [2]);
+        assert!(!queue.is_pending(second));
+        assert_eq!(queue.next_deadline(), None);
+        assert!(!queue.cancel(second));
+    }
+
+    #[test_case]
+    fn tombstones_are_compacted() {
+        let mut queue = TimerQueue::new();
+        let kept = queue.add(u64::MAX, timer(0));
+        for i in 0..1000 {
+            let id = queue.add(u64::MAX - 1, timer(i));
+            queue.cancel(id);
+        }
+        assert!(queue.heap.len() <= 2 * queue.timers.len() + 16);
+        assert!(queue.is_pending(kept));
+    }
+}
@@ -0,0 +1,78 @@
+//! Scheduler tracepoints, built with the `sched_trace` feature.
+//!
+//! The scheduler and the syscalls that block a task record fixed-size events into a global
+//! ring buffer, which `sys_trace_collect` drains to user space. When the ring is full the
+//! oldest events are overwritten, so a viewer that collects often sees a gap-free timeline.
+
+use crate::sync::UPSafeCell;
+use crate::timer::get_time_ns;
+use alloc::collections::vec_deque::VecDeque;
+use alloc::vec::Vec;
+use lazy_static::*;
+
+/// Events kept before the oldest ones are overwritten.
+const TRACE_CAPACITY: usize = 1024;
+
+/// What happened in a `TraceRecord`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(usize)]
+pub enum TraceEvent {
+    /// `task` stopped running, `arg` is a `SchedOutReason`.
+    SchedOut = 1,
+    /// `task` started running.
+    SchedIn = 2,
+    /// `task` went to sleep, `arg` is the deadline in ms on the clock it sleeps on.
+    Block = 3,
+    /// The sleep timer of `task` fired.
+    Wakeup = 4,
+    /// `task` was created, `arg` is 1 for a kthread and 0 for an app.
+    Spawn = 5,
+    /// `task` exited, `arg` is its exit code.
+    Exit = 6,
+}
+
+/// Why a task stopped running, the `arg` of `TraceEvent::SchedOut`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(usize)]
+pub enum SchedOutReason {
+    Preempted = 0,
+    Yielded = 1,
+    Exited = 2,
+}
+
+/// One traced event, layout compatible with the user library's.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TraceRecord {
+    /// Time since boot in nanoseconds.
+    pub time_ns: u64,
+    pub event: usize,
+    pub task: usize,
+    pub arg: usize,
+}
+
+lazy_static! {
+    static ref TRACE: UPSafeCell<VecDeque<TraceRecord>> =
+        unsafe { UPSafeCell::new(VecDeque::with_capacity(TRACE_CAPACITY)) };
+}
+
+/// Record `event` of task `task`.
+pub fn record(event: TraceEvent, task: usize, arg: usize) {
+    let mut trace = TRACE.exclusive_access();
+    if trace.len() == TRACE_CAPACITY {
+        trace.pop_front();
+    }
+    trace.push_back(TraceRecord {
+        time_ns: get_time_ns(),
+        event: event as usize,
+        task,
+        arg,
+    });
+}
+
+/// Remove and return up to `max` of the oldest events.
+pub fn collect(max: usize) -> Vec<TraceRecord> {
+    let mut trace = TRACE.exclusive_access();
+    let len = trace.len().min(max);
+    trace.drain(..len).collect()
+}
@@ -1,4 +1,9 @@
-use riscv::register::sstatus::{self, SPP, Sstatus};
+use crate::config::PAGE_SIZE;
+use riscv::register::sstatus::{self, FS, SPP, Sstatus};
+
+/// Magic value in the last word of every trap context page ("TRAPCANY"), checked on trap
+/// entry and exit in debug builds.
+pub const TRAP_CONTEXT_CANARY: usize = 0x5452_4150_4341_4e59;
 
 #[repr(C)]
 /// The trap context structure used to save and restore processor state during a trap (interrupt, exception, or syscall).
@@ -13,6 +18,7 @@ use riscv::register::sstatus::{self, SPP, Sstatus};
 /// - `kernel_satp`: The kernel page table root (SATP register value)
 /// - `kernel_sp`: The kernel stack pointer for trap handling
 /// - `trap_handler`: The address of the kernel's trap handler function
+/// - `fp`: The floating-point registers, saved lazily (see `trap::fp`)
 pub struct TrapContext {
     /// general regs[0..31]
     pub x: [usize; 32],
@@ -26,6 +32,18 @@ pub struct TrapContext {
     pub kernel_sp: usize,
     /// Addr of trap_handler function
     pub trap_handler: usize,
+    /// FP regs, not touched by trap.S
+    pub fp: FpContext,
+}
+
+/// Floating-point state of a user task, layout known to `fp.S`.
+#[repr(C)]
+#[derive(Default)]
+pub struct FpContext {
+    /// f0..f31
+    pub f: [u64; 32],
+    /// CSR fcsr
+    pub fcsr: usize,
 }
 
 impl TrapContext {
@@ -57,6 +75,9 @@ impl TrapContext {
     ) -> Self {
         let mut sstatus = sstatus::read(); // CSR sstatus
         sstatus.set_spp(SPP::User); //previous privilege mode: user mode
+        sstatus.set_fs(FS::Initial); // FP registers not used yet
+        #[cfg(feature = "vector")]
+        super::vector::set_initial(&mut sstatus);
         let mut cx = Self {
             x: [0; 32],
             sstatus,
@@ -64,8 +85,26 @@ impl TrapContext {
             kernel_satp,  // addr of page table
             kernel_sp,    // kernel stack
             trap_handler, // addr of trap_handler function
+            fp: FpContext::default(),
         };
         cx.set_sp(sp); // app's user stack pointer
         cx // return initial Trap Context of app
     }
+
+    /// Address of the canary word at the end of the page this context starts.
+    fn canary_addr(&self) -> usize {
+        let page = self as *const Self as usize & !(PAGE_SIZE - 1);
+        page + PAGE_SIZE - size_of::<usize>()
+    }
+
+    /// Write `TRAP_CONTEXT_CANARY` behind this context, at the end of its page.
+    pub fn set_canary(&mut self) {
+        unsafe { (self.canary_addr() as *mut usize).write_volatile(TRAP_CONTEXT_CANARY) };
+    }
+
+    /// Returns the word at the end of this context's page, `TRAP_CONTEXT_CANARY` unless
+    /// something wrote past the context.
+    pub fn canary(&self) -> usize {
+        unsafe { (self.canary_addr() as *const usize).read_volatile() }
+    }
 }
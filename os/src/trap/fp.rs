@@ -0,0 +1,56 @@
+//! Lazy floating-point state of user tasks
+//!
+//! User tasks start with `sstatus.FS` = Initial. The hardware sets FS to Dirty once a task
+//! writes an FP register, so a trap arriving with FS = Dirty saves the registers into the
+//! task's `TrapContext` and marks them Clean again; tasks not using FP cost nothing. The
+//! registers keep holding the state of the last task returned to user mode, so they are only
+//! reloaded when returning to a different task.
+//!
+//! The kernel target is riscv64gc with the lp64d ABI, so the compiler may emit FP
+//! instructions anywhere in it, which would clobber the user state held in the registers.
+//! The kernel therefore runs with FS = Off and turns the FP unit on only to save and restore
+//! that state; an accidental FP instruction in the kernel traps instead.
+
+use super::context::{FpContext, TrapContext};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use riscv::register::sstatus::{self, FS};
+
+core::arch::global_asm!(include_str!("fp.S"));
+
+unsafe extern "C" {
+    fn __fp_save(fp: *mut FpContext);
+    fn __fp_restore(fp: *const FpContext);
+}
+
+/// Task whose FP state is in the FP registers, `usize::MAX` for none.
+static FP_OWNER: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+/// Turn the FP unit off for the kernel.
+pub fn init() {
+    unsafe { sstatus::set_fs(FS::Off) };
+}
+
+/// Save the FP registers into `cx` if the task trapping with it changed them, then turn the
+/// FP unit off until the return to user mode.
+///
+/// The trap leaves FS as the task had it, the task's own value is kept in `cx.sstatus`.
+pub fn save_if_dirty(cx: &mut TrapContext) {
+    if cx.sstatus.fs() == FS::Dirty {
+        unsafe { __fp_save(&mut cx.fp) };
+        cx.sstatus.set_fs(FS::Clean);
+    }
+    unsafe { sstatus::set_fs(FS::Off) };
+}
+
+/// Load the FP state of `task_id` from `cx`, unless the registers already hold it.
+///
+/// `__restore` then sets FS from `cx.sstatus` on the way to user mode.
+pub fn restore(task_id: usize, cx: &TrapContext) {
+    if FP_OWNER.swap(task_id, Ordering::Relaxed) != task_id {
+        unsafe {
+            sstatus::set_fs(FS::Clean);
+            __fp_restore(&cx.fp);
+            sstatus::set_fs(FS::Off);
+        }
+    }
+}
@@ -1,43 +1,66 @@
 mod context;
+mod fp;
+mod table;
+#[cfg(feature = "vector")]
+pub mod vector;
 
 use crate::config::{TRAMPOLINE_ADDR, TRAP_CONTEXT_ADDR};
+use crate::mm::{PageFaultError, flush_icache, frame_stats};
 use crate::syscall::syscall;
 use crate::task::{
-    current_trap_cx, current_user_token, exit_current_and_run_next, suspend_current_and_run_next,
+    EXIT_HUNG, EXIT_KILLED, charge_current_tick, current_task_id, current_task_label,
+    current_trap_cx, current_user_token, exit_current_and_run_next, handle_current_page_fault,
+    request_resched, set_current_in_syscall, suspend_current_and_run_next,
+    take_current_need_resched,
 };
+use crate::tasklet::do_tasklets;
 use crate::timer::{self, set_next_trigger};
+use crate::watchdog;
 use core::arch::{asm, global_asm};
-use log::info;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use log::{Level, info};
 use riscv::interrupt::{Exception, Interrupt};
 use riscv::register;
 use riscv::register::{
     mtvec::TrapMode,
     scause::{self, Trap},
-    stval,
+    sstatus, stval,
 };
 
 global_asm!(include_str!("trap.S"));
+global_asm!(include_str!("kernel_trap.S"));
+
+/// Timer interrupts taken in S-mode since boot, see `kernel_trap_handler`.
+static NESTED_INTERRUPTS: AtomicUsize = AtomicUsize::new(0);
 
 /// Initialize the trap handling subsystem.
 ///
 /// This function sets the kernel trap entry point, configuring the hardware to use
-/// the appropriate trap vector for handling exceptions, interrupts, and syscalls in S-mode.
+/// the appropriate trap vector for handling exceptions, interrupts, and syscalls in S-mode,
+/// and turns the FP unit off, see `fp`.
 pub fn init() {
     set_kernel_trap_entry();
+    register_handlers();
+    fp::init();
+    #[cfg(feature = "vector")]
+    vector::init();
 }
 
 /// Set the S-mode trap entry point for the kernel.
 ///
 /// # Note
 /// Once the kernel is entered, if another trap occurs in S-mode, the hardware will set some CSR registers
-/// and then jump directly to `trap_from_kernel` without saving general-purpose registers.
+/// and then jump to `__kerneltrap`, which saves the caller-saved registers on the current kernel stack.
 /// This is because, after separating kernel and user address spaces, the context saving/restoring and trap
 /// handling logic for U-mode → S-mode and S-mode → S-mode traps are very different.
-/// For simplicity, S-mode → S-mode trap handling is minimized here: we simply panic in `trap_from_kernel`.
+/// S-mode → S-mode trap handling is minimized here, see `kernel_trap_handler`.
 fn set_kernel_trap_entry() {
+    unsafe extern "C" {
+        fn __kerneltrap();
+    }
     let mut stvec = register::stvec::read();
     stvec.set_trap_mode(TrapMode::Direct);
-    stvec.set_address(trap_from_kernel as usize);
+    stvec.set_address(__kerneltrap as usize);
     unsafe {
         register::stvec::write(stvec);
     }
@@ -71,35 +94,18 @@ pub fn enable_timer_interrupt() {
 pub fn trap_handler() -> ! {
     set_kernel_trap_entry();
     let cx = current_trap_cx();
+    #[cfg(debug_assertions)]
+    check_trap_cx(cx, "entry");
+    fp::save_if_dirty(cx);
+    #[cfg(feature = "vector")]
+    vector::save_if_dirty(current_task_id(), cx);
     let scause = register::scause::read();
     let stval = stval::read();
 
     let raw_trap: Trap<usize, usize> = scause.cause();
-    let standard_trap: Trap<Interrupt, Exception> = raw_trap.try_into().unwrap();
-    match standard_trap {
-        Trap::Exception(Exception::UserEnvCall) => {
-            cx.sepc += 4;
-            cx.x[10] = syscall(cx.x[17], [cx.x[10], cx.x[11], cx.x[12]]) as usize;
-        }
-        Trap::Exception(Exception::StoreFault)
-        | Trap::Exception(Exception::StorePageFault)
-        | Trap::Exception(Exception::LoadFault)
-        | Trap::Exception(Exception::LoadPageFault) => {
-            info!(
-                "[kernel] PageFault in application, bad addr = {:#x}, bad instruction = {:#x}, kernel killed it.",
-                stval, cx.sepc
-            );
-            exit_current_and_run_next();
-        }
-        Trap::Exception(Exception::IllegalInstruction) => {
-            info!("[kernel] IllegalInstruction in application, kernel killed it.");
-            exit_current_and_run_next();
-        }
-        Trap::Interrupt(Interrupt::SupervisorTimer) => {
-            set_next_trigger();
-            suspend_current_and_run_next();
-        }
-        _ => {
+    match table::lookup(raw_trap) {
+        Some(handler) => handler(cx, stval),
+        None => {
             panic!(
                 "Unsupported trap {:?}, stval = {:#x}!",
                 scause.cause(),
@@ -110,11 +116,192 @@ pub fn trap_handler() -> ! {
     trap_return();
 }
 
+/// Install the handlers of the traps user tasks cause.
+fn register_handlers() {
+    register_exception(Exception::UserEnvCall, handle_syscall);
+    register_exception(Exception::StorePageFault, handle_store_page_fault);
+    register_exception(Exception::LoadPageFault, handle_load_page_fault);
+    register_exception(Exception::StoreFault, handle_access_fault);
+    register_exception(Exception::LoadFault, handle_access_fault);
+    register_exception(Exception::IllegalInstruction, handle_illegal_instruction);
+    register_interrupt(Interrupt::SupervisorTimer, handle_timer);
+}
+
+fn handle_syscall(cx: &mut TrapContext, _stval: usize) {
+    watchdog::feed();
+    cx.sepc += 4;
+    set_current_in_syscall(true);
+    #[cfg(feature = "kcov")]
+    crate::kcov::enter(current_task_id());
+    #[cfg(feature = "fault_injection")]
+    crate::fault_inject::enter(current_task_id());
+    enable_kernel_interrupts();
+    let args = [cx.x[10], cx.x[11], cx.x[12], cx.x[13], cx.x[14], cx.x[15]];
+    let ret = syscall(cx.x[17], args);
+    disable_kernel_interrupts();
+    cx.x[10] = ret as usize;
+    #[cfg(feature = "fault_injection")]
+    crate::fault_inject::exit();
+    #[cfg(feature = "kcov")]
+    crate::kcov::exit();
+    set_current_in_syscall(false);
+    // the task used up its time slice in the syscall, preempt it before it returns
+    if take_current_need_resched() {
+        charge_current_tick(true);
+        suspend_current_and_run_next();
+    }
+}
+
+fn handle_store_page_fault(cx: &mut TrapContext, stval: usize) {
+    // stack growth or first write to a zero-filled page, retry the store
+    match handle_current_page_fault(stval, true) {
+        Ok(()) => {}
+        Err(PageFaultError::OutOfMemory) => handle_out_of_memory(stval),
+        Err(PageFaultError::BadAddress) => handle_access_fault(cx, stval),
+    }
+}
+
+fn handle_load_page_fault(cx: &mut TrapContext, stval: usize) {
+    // stack growth, retry the load
+    if handle_current_page_fault(stval, false).is_err() {
+        handle_access_fault(cx, stval);
+    }
+}
+
+/// Kill the current task, which needs a frame for `stval` that it cannot have. Other tasks
+/// keep running and get the frames back once it is gone.
+fn handle_out_of_memory(stval: usize) {
+    let (_, free_frames) = frame_stats();
+    log_ratelimited!(
+        Level::Warn,
+        "[kernel] Out of memory in {} at {:#x} ({} frames free), kernel killed it.",
+        current_task_label(),
+        stval,
+        free_frames
+    );
+    exit_current_and_run_next(EXIT_KILLED);
+}
+
+fn handle_access_fault(cx: &mut TrapContext, stval: usize) {
+    log_ratelimited!(
+        Level::Info,
+        "[kernel] PageFault in {}, bad addr = {:#x}, bad instruction = {:#x}, kernel killed it.",
+        current_task_label(),
+        stval,
+        cx.sepc
+    );
+    exit_current_and_run_next(EXIT_KILLED);
+}
+
+fn handle_illegal_instruction(_cx: &mut TrapContext, _stval: usize) {
+    log_ratelimited!(
+        Level::Info,
+        "[kernel] IllegalInstruction in {}, kernel killed it.",
+        current_task_label()
+    );
+    exit_current_and_run_next(EXIT_KILLED);
+}
+
+fn handle_timer(cx: &mut TrapContext, _stval: usize) {
+    set_next_trigger();
+    timer::record_tick();
+    charge_current_tick(false);
+    #[cfg(feature = "profiler")]
+    crate::profiler::record(current_task_id(), cx.sepc);
+    if watchdog::check(cx.sepc) {
+        info!(
+            "[kernel] Watchdog timeout in {}, kernel killed it.",
+            current_task_label()
+        );
+        exit_current_and_run_next(EXIT_HUNG);
+    } else {
+        suspend_current_and_run_next();
+    }
+}
+
+/// Let timer interrupts in while the kernel runs a syscall, see `kernel_trap_handler`.
+fn enable_kernel_interrupts() {
+    unsafe { sstatus::set_sie() };
+}
+
+fn disable_kernel_interrupts() {
+    unsafe { sstatus::clear_sie() };
+}
+
+/// Keeps S-mode interrupts disabled while alive and restores the previous state when dropped,
+/// e.g. around a task switch, which must not take a nested trap.
+pub struct InterruptGuard {
+    was_enabled: bool,
+}
+
+impl InterruptGuard {
+    pub fn disable() -> Self {
+        let was_enabled = sstatus::read().sie();
+        disable_kernel_interrupts();
+        Self { was_enabled }
+    }
+}
+
+impl Drop for InterruptGuard {
+    fn drop(&mut self) {
+        if self.was_enabled {
+            enable_kernel_interrupts();
+        }
+    }
+}
+
+/// Preemption point of long-running syscalls: if the time slice ran out meanwhile, let the
+/// other tasks run before going on.
+///
+/// Only call it from a syscall, with nothing borrowed that another task may need, e.g. not
+/// from inside `with_current_memory_set`.
+pub fn cond_resched() {
+    if take_current_need_resched() {
+        charge_current_tick(true);
+        suspend_current_and_run_next();
+    }
+}
+
+/// Returns the number of timer interrupts taken in S-mode, i.e. nested in a syscall.
+pub fn nested_interrupts() -> usize {
+    NESTED_INTERRUPTS.load(Ordering::Relaxed)
+}
+
+/// Handle a trap taken in S-mode, entered through `__kerneltrap`.
+///
+/// Only timer interrupts are expected, let in by `enable_kernel_interrupts` during syscalls.
+/// The interrupted code may hold the task manager or the heap, so the tick only arms the
+/// next one and requests a reschedule of the current task, see `task::request_resched`; the
+/// task is preempted at the next `cond_resched` or when its syscall returns. Any other trap
+/// is a kernel bug.
+#[unsafe(no_mangle)]
+extern "C" fn kernel_trap_handler() {
+    let scause = scause::read();
+    match scause.cause() {
+        Trap::Interrupt(code) if code == Interrupt::SupervisorTimer as usize => {
+            set_next_trigger();
+            timer::record_tick();
+            NESTED_INTERRUPTS.fetch_add(1, Ordering::Relaxed);
+            request_resched();
+        }
+        cause => panic!(
+            "a trap from kernel: {:?}, stval = {:#x}, sepc = {:#x}",
+            cause,
+            stval::read(),
+            register::sepc::read()
+        ),
+    }
+}
+
 #[unsafe(no_mangle)]
+/// run pending tasklets, then
 /// set the new addr of __restore asm function in TRAMPOLINE page,
 /// set the reg a0 = trap_cx_ptr, reg a1 = phy addr of usr page table,
 /// finally, jump to new addr of __restore asm function
 pub fn trap_return() -> ! {
+    // stvec is about to point at the trampoline, which cannot take S-mode traps
+    disable_kernel_interrupts();
+    do_tasklets();
     set_user_trap_entry();
     let trap_cx_ptr = TRAP_CONTEXT_ADDR;
     let user_satp = current_user_token();
@@ -122,10 +309,16 @@ pub fn trap_return() -> ! {
         fn __alltraps();
         fn __restore();
     }
+    #[cfg(debug_assertions)]
+    check_trap_cx(current_trap_cx(), "exit");
+    fp::restore(current_task_id(), current_trap_cx());
+    #[cfg(feature = "vector")]
+    vector::restore(current_task_id());
     let restore_va = __restore as usize - __alltraps as usize + TRAMPOLINE_ADDR;
+    // the loader and the kernel write user code through the kernel mapping
+    flush_icache();
     unsafe {
         asm!(
-            "fence.i",
             "jr {restore_va}",             // jump to new addr of __restore asm function
             restore_va = in(reg) restore_va,
             in("a0") trap_cx_ptr,      // a0 = virt addr of Trap Context
@@ -135,9 +328,24 @@ pub fn trap_return() -> ! {
     }
 }
 
-#[unsafe(no_mangle)]
-pub fn trap_from_kernel() -> ! {
-    panic!("a trap from kernel!");
+/// Panic if the canary behind the current task's trap context was overwritten, instead of
+/// restoring registers from a corrupted context.
+#[cfg(debug_assertions)]
+fn check_trap_cx(cx: &TrapContext, when: &str) {
+    let canary = cx.canary();
+    if canary != TRAP_CONTEXT_CANARY {
+        panic!(
+            "trap context of {} corrupted on trap {}: canary {:#x} != {:#x}, sepc = {:#x}, sp = {:#x}, kernel_sp = {:#x}",
+            current_task_label(),
+            when,
+            canary,
+            TRAP_CONTEXT_CANARY,
+            cx.sepc,
+            cx.x[2],
+            cx.kernel_sp
+        );
+    }
 }
 
-pub use context::TrapContext;
+pub use context::{TRAP_CONTEXT_CANARY, TrapContext};
+pub use table::{TrapHandlerFn, register_exception, register_interrupt};
@@ -0,0 +1,53 @@
+//! Trap dispatch table
+//!
+//! `trap_handler` looks up the handler of a trap from user mode by its `scause`, so drivers
+//! and subsystems can claim an exception or interrupt source with `register_exception` or
+//! `register_interrupt` instead of growing a central match. Causes without a handler are
+//! fatal.
+
+use super::TrapContext;
+use crate::sync::UPSafeCell;
+use lazy_static::*;
+use riscv::interrupt::{Exception, Interrupt};
+use riscv::register::scause::Trap;
+
+/// Handles a trap of the current task, given its trap context and `stval`.
+pub type TrapHandlerFn = fn(cx: &mut TrapContext, stval: usize);
+
+/// Exception and interrupt codes are below 16 for everything S-mode can receive.
+const MAX_CAUSES: usize = 16;
+
+struct TrapTable {
+    exceptions: [Option<TrapHandlerFn>; MAX_CAUSES],
+    interrupts: [Option<TrapHandlerFn>; MAX_CAUSES],
+}
+
+lazy_static! {
+    static ref TRAP_TABLE: UPSafeCell<TrapTable> = unsafe {
+        UPSafeCell::new(TrapTable {
+            exceptions: [None; MAX_CAUSES],
+            interrupts: [None; MAX_CAUSES],
+        })
+    };
+}
+
+/// Let `handler` handle `exception` from user mode, replacing the previous handler.
+pub fn register_exception(exception: Exception, handler: TrapHandlerFn) {
+    TRAP_TABLE.exclusive_access().exceptions[exception as usize] = Some(handler);
+}
+
+/// Let `handler` handle `interrupt` arriving in user mode, replacing the previous handler.
+pub fn register_interrupt(interrupt: Interrupt, handler: TrapHandlerFn) {
+    TRAP_TABLE.exclusive_access().interrupts[interrupt as usize] = Some(handler);
+}
+
+/// Returns the handler registered for the raw `scause` cause `trap`.
+///
+/// The table is released before the handler runs, as handlers may switch tasks.
+pub fn lookup(trap: Trap<usize, usize>) -> Option<TrapHandlerFn> {
+    let table = TRAP_TABLE.exclusive_access();
+    match trap {
+        Trap::Exception(code) => *table.exceptions.get(code)?,
+        Trap::Interrupt(code) => *table.interrupts.get(code)?,
+    }
+}
@@ -0,0 +1,170 @@
+//! Lazy vector (RVV) state of user tasks, built with the `vector` feature.
+//!
+//! Works like the FP state in `fp.rs`, with `sstatus.VS` in place of `sstatus.FS`. The 32
+//! vector registers take `32 * vlenb` bytes, known only at boot, so each task's state lives
+//! in a heap area allocated the first time the task returns to user mode, not in its
+//! `TrapContext`. Without the V extension VS stays Off and user vector instructions trap as
+//! illegal instructions.
+
+use super::context::TrapContext;
+use crate::sync::UPSafeCell;
+use alloc::collections::btree_map::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::arch::asm;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use lazy_static::*;
+use log::info;
+use riscv::register::sstatus::{self, Sstatus};
+
+/// `sstatus.VS`, the vector counterpart of `sstatus.FS`.
+const VS_MASK: usize = 3 << 9;
+const VS_INITIAL: usize = 1 << 9;
+const VS_CLEAN: usize = 2 << 9;
+const VS_DIRTY: usize = 3 << 9;
+
+/// Bytes per vector register, 0 without the V extension.
+static VLENB: AtomicUsize = AtomicUsize::new(0);
+
+/// Task whose vector state is in the vector registers, `usize::MAX` for none.
+static VECTOR_OWNER: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+/// Vector registers and CSRs of a task.
+struct VectorContext {
+    /// v0..v31, `vlenb` bytes each
+    regs: Vec<u8>,
+    vstart: usize,
+    vcsr: usize,
+    vl: usize,
+    vtype: usize,
+}
+
+impl VectorContext {
+    fn new(vlenb: usize) -> Self {
+        Self {
+            regs: vec![0; 32 * vlenb],
+            vstart: 0,
+            vcsr: 0,
+            vl: 0,
+            // vill: no vector configuration set yet
+            vtype: 1 << 63,
+        }
+    }
+
+    fn save(&mut self) {
+        let group = self.regs.len() / 4;
+        unsafe {
+            asm!(
+                ".option push",
+                ".option arch, +v",
+                "csrr {vstart}, vstart",
+                "csrr {vcsr}, vcsr",
+                "csrr {vl}, vl",
+                "csrr {vtype}, vtype",
+                // whole register stores skip elements below vstart
+                "csrw vstart, zero",
+                "vs8r.v v0, ({regs})",
+                "add {regs}, {regs}, {group}",
+                "vs8r.v v8, ({regs})",
+                "add {regs}, {regs}, {group}",
+                "vs8r.v v16, ({regs})",
+                "add {regs}, {regs}, {group}",
+                "vs8r.v v24, ({regs})",
+                ".option pop",
+                regs = inout(reg) self.regs.as_mut_ptr() => _,
+                group = in(reg) group,
+                vstart = out(reg) self.vstart,
+                vcsr = out(reg) self.vcsr,
+                vl = out(reg) self.vl,
+                vtype = out(reg) self.vtype,
+            );
+        }
+    }
+
+    fn restore(&self) {
+        let group = self.regs.len() / 4;
+        unsafe {
+            asm!(
+                ".option push",
+                ".option arch, +v",
+                "csrw vstart, zero",
+                "vl8r.v v0, ({regs})",
+                "add {regs}, {regs}, {group}",
+                "vl8r.v v8, ({regs})",
+                "add {regs}, {regs}, {group}",
+                "vl8r.v v16, ({regs})",
+                "add {regs}, {regs}, {group}",
+                "vl8r.v v24, ({regs})",
+                "vsetvl zero, {vl}, {vtype}",
+                "csrw vcsr, {vcsr}",
+                // last, vector instructions reset it
+                "csrw vstart, {vstart}",
+                ".option pop",
+                regs = inout(reg) self.regs.as_ptr() => _,
+                group = in(reg) group,
+                vstart = in(reg) self.vstart,
+                vcsr = in(reg) self.vcsr,
+                vl = in(reg) self.vl,
+                vtype = in(reg) self.vtype,
+            );
+        }
+    }
+}
+
+lazy_static! {
+    /// Vector state of the tasks that returned to user mode, keyed by task id.
+    static ref CONTEXTS: UPSafeCell<BTreeMap<usize, VectorContext>> =
+        unsafe { UPSafeCell::new(BTreeMap::new()) };
+}
+
+/// Detect the V extension and let the kernel execute vector instructions.
+///
+/// VS is read-only zero on harts without V, so a VS that sticks means V is there.
+pub fn init() {
+    unsafe { asm!("csrs sstatus, {}", in(reg) VS_INITIAL) };
+    if sstatus::read().bits() & VS_MASK == 0 {
+        info!("[kernel] no V extension, vector state is not saved");
+        return;
+    }
+    let vlenb: usize;
+    unsafe { asm!("csrr {}, 0xc22", out(reg) vlenb) }; // vlenb
+    VLENB.store(vlenb, Ordering::Relaxed);
+    info!("[kernel] V extension with {vlenb} bytes per vector register");
+}
+
+/// Let a new user task use vector instructions, if the hart has any.
+pub fn set_initial(sstatus: &mut Sstatus) {
+    if VLENB.load(Ordering::Relaxed) != 0 {
+        *sstatus = Sstatus::from_bits(sstatus.bits() & !VS_MASK | VS_INITIAL);
+    }
+}
+
+/// Save the vector registers of `task_id` if it changed them since the last save.
+pub fn save_if_dirty(task_id: usize, cx: &mut TrapContext) {
+    let bits = cx.sstatus.bits();
+    if bits & VS_MASK != VS_DIRTY {
+        return;
+    }
+    if let Some(vcx) = CONTEXTS.exclusive_access().get_mut(&task_id) {
+        vcx.save();
+    }
+    cx.sstatus = Sstatus::from_bits(bits & !VS_MASK | VS_CLEAN);
+}
+
+/// Load the vector state of `task_id`, unless the registers already hold it.
+pub fn restore(task_id: usize) {
+    let vlenb = VLENB.load(Ordering::Relaxed);
+    if vlenb == 0 || VECTOR_OWNER.swap(task_id, Ordering::Relaxed) == task_id {
+        return;
+    }
+    CONTEXTS
+        .exclusive_access()
+        .entry(task_id)
+        .or_insert_with(|| VectorContext::new(vlenb))
+        .restore();
+}
+
+/// Free the vector state of the exited task `task_id`.
+pub fn release(task_id: usize) {
+    CONTEXTS.exclusive_access().remove(&task_id);
+}
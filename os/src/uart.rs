@@ -0,0 +1,49 @@
+//! Polled driver of the 16550 UART of the `virt` machine.
+//!
+//! QEMU's 16550 needs no baud rate setup, so only the line format and the FIFOs are set.
+//! Output busy-waits for room in the transmitter; there is no input or interrupt handling.
+
+use crate::board::UART0;
+
+/// Transmit holding register (write).
+const THR: usize = 0;
+/// Interrupt enable register.
+const IER: usize = 1;
+/// FIFO control register (write).
+const FCR: usize = 2;
+/// Line control register.
+const LCR: usize = 3;
+/// Line status register.
+const LSR: usize = 5;
+
+/// LCR: 8 data bits, no parity, one stop bit.
+const LCR_8N1: u8 = 0x03;
+/// FCR: enable and clear both FIFOs.
+const FCR_ENABLE_CLEAR: u8 = 0x07;
+/// LSR: the transmit holding register is empty.
+const LSR_THR_EMPTY: u8 = 1 << 5;
+
+fn read(reg: usize) -> u8 {
+    unsafe { ((UART0 + reg) as *const u8).read_volatile() }
+}
+
+fn write(reg: usize, value: u8) {
+    unsafe { ((UART0 + reg) as *mut u8).write_volatile(value) }
+}
+
+/// Set the UART up for polled output.
+pub fn init() {
+    write(IER, 0);
+    write(LCR, LCR_8N1);
+    write(FCR, FCR_ENABLE_CLEAR);
+}
+
+/// Write one byte, waiting for room in the transmitter.
+///
+/// Works with or without paging, the device is identity-mapped in the kernel space.
+pub fn putchar(byte: u8) {
+    while read(LSR) & LSR_THR_EMPTY == 0 {
+        core::hint::spin_loop();
+    }
+    write(THR, byte);
+}
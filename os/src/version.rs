@@ -0,0 +1,32 @@
+//! Kernel identification, embedded at build time by build.rs.
+
+use crate::config::{BOARD_NAME, NUM_HARTS};
+
+/// Name of the operating system.
+pub const OS_NAME: &str = "mini-os";
+
+/// Kernel version, from `git describe` of the source tree.
+pub const OS_VERSION: &str = env!("MINI_OS_VERSION");
+
+/// When the kernel was built.
+pub const BUILD_TIME: &str = env!("MINI_OS_BUILD_TIME");
+
+/// Machine architecture.
+pub const MACHINE: &str = "riscv64";
+
+/// Print the boot banner.
+pub fn print_banner() {
+    println!(
+        r#"
+           _       _
+ _ __ ___ (_)_ __ (_)       ___  ___
+| '_ ` _ \| | '_ \| |_____ / _ \/ __|
+| | | | | | | | | | |_____| (_) \__ \
+|_| |_| |_|_|_| |_|_|      \___/|___/
+"#
+    );
+    println!(
+        "{} {} ({}, built {}) on {} with {} hart(s)",
+        OS_NAME, OS_VERSION, MACHINE, BUILD_TIME, BOARD_NAME, NUM_HARTS
+    );
+}
@@ -0,0 +1,249 @@
+//! Output-only driver of a virtio console on the `virt` machine's virtio-mmio transports.
+//!
+//! Only the modern (version 2) MMIO interface is supported; QEMU offers it with
+//! `-global virtio-mmio.force-legacy=false`. The driver uses the transmit queue of port 0 and
+//! no interrupts: a write hands the bytes to the device in place, as a chain of descriptors
+//! over their physical extents, and polls the used ring until the device is done with them.
+
+use crate::board::{VIRTIO_MMIO, VIRTIO_MMIO_COUNT};
+use crate::config::PAGE_SIZE;
+use crate::mm::{FrameTracker, PageTable, PhysAddr, frame_alloc};
+use crate::sync::UPSafeCell;
+use core::sync::atomic::{Ordering, fence};
+use lazy_static::*;
+use log::info;
+use riscv::register::satp;
+
+const MAGIC_VALUE: usize = 0x000;
+const VERSION: usize = 0x004;
+const DEVICE_ID: usize = 0x008;
+const DEVICE_FEATURES: usize = 0x010;
+const DEVICE_FEATURES_SEL: usize = 0x014;
+const DRIVER_FEATURES: usize = 0x020;
+const DRIVER_FEATURES_SEL: usize = 0x024;
+const QUEUE_SEL: usize = 0x030;
+const QUEUE_NUM_MAX: usize = 0x034;
+const QUEUE_NUM: usize = 0x038;
+const QUEUE_READY: usize = 0x044;
+const QUEUE_NOTIFY: usize = 0x050;
+const INTERRUPT_STATUS: usize = 0x060;
+const INTERRUPT_ACK: usize = 0x064;
+const STATUS: usize = 0x070;
+const QUEUE_DESC: usize = 0x080;
+const QUEUE_DRIVER: usize = 0x090;
+const QUEUE_DEVICE: usize = 0x0a0;
+
+/// "virt" in little endian.
+const MAGIC: u32 = 0x7472_6976;
+const DEVICE_ID_CONSOLE: u32 = 3;
+
+const STATUS_ACKNOWLEDGE: u32 = 1;
+const STATUS_DRIVER: u32 = 2;
+const STATUS_DRIVER_OK: u32 = 4;
+const STATUS_FEATURES_OK: u32 = 8;
+
+/// `VIRTIO_F_VERSION_1`, bit 32 of the feature bits.
+const FEATURE_VERSION_1: u32 = 1 << 0;
+
+/// Transmit queue of port 0.
+const TRANSMITQ: u32 = 1;
+/// Entries of the transmit queue; one buffer is in flight at a time, as a chain of up to
+/// this many descriptors.
+const QUEUE_SIZE: u16 = 8;
+
+/// The descriptor continues in the one its `next` names.
+const DESC_F_NEXT: u16 = 1;
+
+/// Offsets of the virtqueue parts in its frame: 16-byte descriptors, then the available
+/// ring, then the used ring, each aligned as the spec requires.
+const DESC_OFFSET: usize = 0;
+const AVAIL_OFFSET: usize = 16 * QUEUE_SIZE as usize;
+const USED_OFFSET: usize = 256;
+
+#[repr(C)]
+struct Descriptor {
+    addr: u64,
+    len: u32,
+    flags: u16,
+    next: u16,
+}
+
+struct VirtioConsole {
+    base: usize,
+    /// Descriptors and rings of the transmit queue.
+    queue: FrameTracker,
+    /// Bounce buffer for writes scattered over more extents than the queue has descriptors.
+    buffer: FrameTracker,
+    /// Next index of the available ring.
+    avail_idx: u16,
+}
+
+lazy_static! {
+    static ref VIRTIO_CONSOLE: UPSafeCell<Option<VirtioConsole>> = unsafe { UPSafeCell::new(None) };
+}
+
+fn read(base: usize, reg: usize) -> u32 {
+    unsafe { ((base + reg) as *const u32).read_volatile() }
+}
+
+fn write(base: usize, reg: usize, value: u32) {
+    unsafe { ((base + reg) as *mut u32).write_volatile(value) }
+}
+
+fn write_addr(base: usize, reg: usize, addr: usize) {
+    write(base, reg, addr as u32);
+    write(base, reg + 4, (addr >> 32) as u32);
+}
+
+impl VirtioConsole {
+    /// Set up the device at `base` and its transmit queue.
+    ///
+    /// # Returns
+    /// `None` if the device lacks modern virtio or the transmit queue, or memory ran out.
+    fn new(base: usize) -> Option<Self> {
+        write(base, STATUS, 0);
+        write(base, STATUS, STATUS_ACKNOWLEDGE | STATUS_DRIVER);
+        write(base, DEVICE_FEATURES_SEL, 1);
+        if read(base, DEVICE_FEATURES) & FEATURE_VERSION_1 == 0 {
+            return None;
+        }
+        // none of the console features, multiport or emergency write, are needed
+        write(base, DRIVER_FEATURES_SEL, 0);
+        write(base, DRIVER_FEATURES, 0);
+        write(base, DRIVER_FEATURES_SEL, 1);
+        write(base, DRIVER_FEATURES, FEATURE_VERSION_1);
+        write(
+            base,
+            STATUS,
+            STATUS_ACKNOWLEDGE | STATUS_DRIVER | STATUS_FEATURES_OK,
+        );
+        if read(base, STATUS) & STATUS_FEATURES_OK == 0 {
+            return None;
+        }
+
+        write(base, QUEUE_SEL, TRANSMITQ);
+        if read(base, QUEUE_READY) != 0 || read(base, QUEUE_NUM_MAX) < QUEUE_SIZE as u32 {
+            return None;
+        }
+        let queue = frame_alloc()?;
+        let buffer = frame_alloc()?;
+        let queue_pa = queue.ppn.get_first_addr().0;
+        write(base, QUEUE_NUM, QUEUE_SIZE as u32);
+        write_addr(base, QUEUE_DESC, queue_pa + DESC_OFFSET);
+        write_addr(base, QUEUE_DRIVER, queue_pa + AVAIL_OFFSET);
+        write_addr(base, QUEUE_DEVICE, queue_pa + USED_OFFSET);
+        write(base, QUEUE_READY, 1);
+        write(
+            base,
+            STATUS,
+            STATUS_ACKNOWLEDGE | STATUS_DRIVER | STATUS_FEATURES_OK | STATUS_DRIVER_OK,
+        );
+        Some(Self {
+            base,
+            queue,
+            buffer,
+            avail_idx: 0,
+        })
+    }
+
+    /// Returns the 16-bit field at `offset` of the queue frame.
+    fn queue_u16(&self, offset: usize) -> *mut u16 {
+        (self.queue.ppn.get_bytes_array_mut().as_mut_ptr() as usize + offset) as *mut u16
+    }
+
+    /// Write `bytes` out in place, one descriptor per physically contiguous extent of them,
+    /// see `PageTable::translate_range`. Bytes scattered over more extents than the queue has
+    /// descriptors are copied through the bounce buffer a page at a time instead.
+    fn write(&mut self, bytes: &[u8]) {
+        if bytes.is_empty() {
+            return;
+        }
+        let page_table = PageTable::from_token(satp::read().bits());
+        match page_table.translate_range(bytes.as_ptr() as usize, bytes.len()) {
+            Some(extents) if extents.len() <= QUEUE_SIZE as usize => self.transmit(&extents),
+            _ => {
+                let buffer_pa = self.buffer.ppn.get_first_addr();
+                for chunk in bytes.chunks(PAGE_SIZE) {
+                    self.buffer.ppn.get_bytes_array_mut()[..chunk.len()].copy_from_slice(chunk);
+                    self.transmit(&[(buffer_pa, chunk.len())]);
+                }
+            }
+        }
+    }
+
+    /// Transmit the physical `extents`, at most `QUEUE_SIZE` of them, as one descriptor chain
+    /// and wait until the device consumed them.
+    fn transmit(&mut self, extents: &[(PhysAddr, usize)]) {
+        let descs = (self.queue.ppn.get_bytes_array_mut().as_mut_ptr() as usize + DESC_OFFSET)
+            as *mut Descriptor;
+        for (i, &(pa, len)) in extents.iter().enumerate() {
+            let last = i + 1 == extents.len();
+            let desc = Descriptor {
+                addr: pa.0 as u64,
+                len: len as u32,
+                flags: if last { 0 } else { DESC_F_NEXT },
+                next: if last { 0 } else { i as u16 + 1 },
+            };
+            unsafe { descs.add(i).write_volatile(desc) };
+        }
+        // avail ring: flags, idx, ring[QUEUE_SIZE]
+        let slot = (self.avail_idx % QUEUE_SIZE) as usize;
+        unsafe {
+            self.queue_u16(AVAIL_OFFSET + 4 + 2 * slot)
+                .write_volatile(0)
+        };
+        self.avail_idx = self.avail_idx.wrapping_add(1);
+        fence(Ordering::SeqCst);
+        unsafe {
+            self.queue_u16(AVAIL_OFFSET + 2)
+                .write_volatile(self.avail_idx)
+        };
+        fence(Ordering::SeqCst);
+        write(self.base, QUEUE_NOTIFY, TRANSMITQ);
+
+        // used ring: flags, idx, ring[QUEUE_SIZE]
+        while unsafe { self.queue_u16(USED_OFFSET + 2).read_volatile() } != self.avail_idx {
+            core::hint::spin_loop();
+        }
+        fence(Ordering::SeqCst);
+        write(self.base, INTERRUPT_ACK, read(self.base, INTERRUPT_STATUS));
+    }
+}
+
+/// Look for a virtio console and set it up.
+///
+/// # Returns
+/// `false` if there is none the driver can use.
+pub fn init() -> bool {
+    for slot in 0..VIRTIO_MMIO_COUNT {
+        let base = VIRTIO_MMIO + slot * 0x1000;
+        if read(base, MAGIC_VALUE) != MAGIC
+            || read(base, VERSION) != 2
+            || read(base, DEVICE_ID) != DEVICE_ID_CONSOLE
+        {
+            continue;
+        }
+        if let Some(console) = VirtioConsole::new(base) {
+            info!("[kernel] virtio console at {base:#x}");
+            *VIRTIO_CONSOLE.exclusive_access() = Some(console);
+            return true;
+        }
+    }
+    false
+}
+
+/// Write `bytes` to the virtio console.
+///
+/// # Returns
+/// `false` if there is no console, or it is busy with a write this one interrupted, e.g.
+/// when panicking while printing.
+pub fn write_bytes(bytes: &[u8]) -> bool {
+    let Some(mut console) = VIRTIO_CONSOLE.try_access() else {
+        return false;
+    };
+    let Some(console) = console.as_mut() else {
+        return false;
+    };
+    console.write(bytes);
+    true
+}
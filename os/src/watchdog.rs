@@ -0,0 +1,39 @@
+//! Watchdog for runaway user tasks.
+//!
+//! Every timer interrupt charges the interrupted task one tick, and any syscall feeds the
+//! watchdog again. A task reaching `WATCHDOG_TIMEOUT_TICKS` is reported together with its
+//! `sepc` and, if `WATCHDOG_KILL` is set, killed.
+//!
+//! Interrupts are disabled in S-mode, so a task stuck inside the kernel is not caught here.
+
+use crate::config::{WATCHDOG_KILL, WATCHDOG_TIMEOUT_TICKS};
+use crate::task::{current_task_label, current_watchdog_feed, current_watchdog_tick};
+use log::warn;
+
+/// Reset the current task's watchdog, called on every syscall.
+pub fn feed() {
+    current_watchdog_feed();
+}
+
+/// Account one timer tick to the current task, interrupted at `sepc`.
+///
+/// # Returns
+/// `true` if the task has to be killed.
+pub fn check(sepc: usize) -> bool {
+    let ticks = current_watchdog_tick();
+    if ticks < WATCHDOG_TIMEOUT_TICKS {
+        return false;
+    }
+
+    warn!(
+        "[kernel] watchdog: {} ran {} ticks without a syscall, sepc = {:#x}",
+        current_task_label(),
+        ticks,
+        sepc
+    );
+    if !WATCHDOG_KILL {
+        // report again after another full period
+        current_watchdog_feed();
+    }
+    WATCHDOG_KILL
+}
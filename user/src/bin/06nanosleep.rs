@@ -0,0 +1,37 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{TIMER_ABSTIME, TimeSpec, get_time, nanosleep, sleep};
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    let mut rem = TimeSpec::default();
+
+    // relative
+    let start = get_time();
+    assert_eq!(nanosleep(&TimeSpec::from_ms(200), &mut rem, 0), 0);
+    assert!(get_time() - start >= 200);
+
+    // absolute
+    let deadline = get_time() + 300;
+    let req = TimeSpec::from_ms(deadline as usize);
+    assert_eq!(nanosleep(&req, &mut rem, TIMER_ABSTIME), 0);
+    assert!(get_time() >= deadline);
+
+    // a deadline in the past returns immediately
+    assert_eq!(nanosleep(&TimeSpec::default(), &mut rem, TIMER_ABSTIME), 0);
+
+    // invalid nsec
+    let bad = TimeSpec {
+        sec: 0,
+        nsec: 1_000_000_000,
+    };
+    assert_eq!(nanosleep(&bad, &mut rem, 0), -1);
+
+    assert_eq!(sleep(100), 0);
+    println!("Test nanosleep OK!");
+    0
+}
@@ -0,0 +1,20 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{dump_syscall_latency, get_time, yield_};
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    for _ in 0..100 {
+        get_time();
+        yield_();
+    }
+    if dump_syscall_latency() == -1 {
+        println!("kernel built without syscall_latency, skipped");
+    }
+    println!("Test syscall_latency OK!");
+    0
+}
@@ -0,0 +1,38 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::ptr::{read_volatile, write_volatile};
+use user_lib::{MADV_DONTNEED, MADV_WILLNEED, madvise};
+
+const PAGE_SIZE: usize = 4096;
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    // main runs in the top page of the user stack, the page below it is still untouched
+    let marker = 0u8;
+    let page = (&marker as *const u8 as usize & !(PAGE_SIZE - 1)) - PAGE_SIZE;
+    let ptr = page as *mut u64;
+
+    assert_eq!(madvise(page, PAGE_SIZE, MADV_WILLNEED), 0);
+    unsafe {
+        write_volatile(ptr, 0xdead_beef);
+        assert_eq!(read_volatile(ptr), 0xdead_beef);
+    }
+    assert_eq!(madvise(page, PAGE_SIZE, MADV_DONTNEED), 0);
+    unsafe {
+        assert_eq!(read_volatile(ptr), 0);
+    }
+
+    // misaligned start, ELF (non-anonymous) memory and unknown advice are rejected
+    assert_eq!(madvise(page + 8, PAGE_SIZE, MADV_DONTNEED), -1);
+    assert_eq!(
+        madvise(main as usize & !(PAGE_SIZE - 1), PAGE_SIZE, MADV_DONTNEED),
+        -1
+    );
+    assert_eq!(madvise(page, PAGE_SIZE, 100), -1);
+    println!("Test madvise OK!");
+    0
+}
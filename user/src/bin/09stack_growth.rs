@@ -0,0 +1,26 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::hint::black_box;
+
+/// Recurse `depth` times with a 512-byte frame each and sum the frames up.
+fn recurse(depth: usize) -> usize {
+    let frame = black_box([depth as u8; 512]);
+    if depth == 0 {
+        return frame[0] as usize;
+    }
+    recurse(depth - 1) + frame[511] as usize
+}
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    // 256 frames of at least 512 bytes need 128 KiB, far more than the initial stack
+    let depth = 256;
+    let expected: usize = (0..=depth).map(|i| i as u8 as usize).sum();
+    assert_eq!(recurse(depth), expected);
+    println!("Test stack growth OK!");
+    0
+}
@@ -0,0 +1,23 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{oops, raw_syscall};
+
+const SYSCALL_NANOSLEEP: usize = 101;
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    println!("Into Test oops, we will pass an unmapped pointer to a syscall...");
+    if raw_syscall(SYSCALL_NANOSLEEP, [0x8, 0, 0, 0, 0, 0]) != -1 {
+        println!("Test oops FAILED: the kernel accepted an unmapped pointer");
+        return -1;
+    }
+    println!("Now we will make the kernel fault in a syscall...");
+    println!("Kernel should oops and kill only this application!");
+    oops();
+    println!("Test oops FAILED: the kernel returned from the syscall");
+    -1
+}
@@ -0,0 +1,41 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec::Vec;
+use user_lib::tokenize::{TokenizeError, tokenize};
+
+fn check(line: &str, expected: &[&str]) {
+    let args = tokenize(line).unwrap();
+    let args: Vec<&str> = args.iter().map(|arg| arg.as_str()).collect();
+    assert_eq!(args, expected, "tokenizing {line:?}");
+}
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    check("", &[]);
+    check("  echo \t hello  world ", &["echo", "hello", "world"]);
+    check("echo 'a  b' \"c d\"", &["echo", "a  b", "c d"]);
+    check("echo a\"b c\"d", &["echo", "ab cd"]);
+    check("echo '' \"\"", &["echo", "", ""]);
+    check("echo 'it\\'s", &["echo", "it\\s"]);
+    check(
+        "echo \"say \\\"hi\\\" \\\\ \\n\"",
+        &["echo", "say \"hi\" \\ \\n"],
+    );
+    check("echo a\\ b \\'c\\'", &["echo", "a b", "'c'"]);
+    assert_eq!(
+        tokenize("echo 'oops"),
+        Err(TokenizeError::UnterminatedQuote('\''))
+    );
+    assert_eq!(
+        tokenize("echo \"oops"),
+        Err(TokenizeError::UnterminatedQuote('"'))
+    );
+    assert_eq!(tokenize("echo \\"), Err(TokenizeError::TrailingBackslash));
+    println!("Test tokenize OK!");
+    0
+}
@@ -0,0 +1,50 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::format;
+use user_lib::parse::{parse_bool, parse_isize, parse_usize};
+use user_lib::rand::{random_range, random_u64, shuffle};
+use user_lib::time::{Millis, uptime};
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    assert_eq!(parse_usize("42"), Some(42));
+    assert_eq!(parse_usize("0x1f"), Some(0x1f));
+    assert_eq!(parse_usize("0b1010"), Some(10));
+    assert_eq!(parse_usize("1_000"), Some(1000));
+    assert_eq!(parse_usize(""), None);
+    assert_eq!(parse_usize("0x"), None);
+    assert_eq!(parse_usize("_1"), None);
+    assert_eq!(parse_usize("12a"), None);
+    assert_eq!(parse_usize("99999999999999999999999"), None);
+    assert_eq!(parse_isize("-17"), Some(-17));
+    assert_eq!(parse_isize("+0x10"), Some(16));
+    assert_eq!(parse_isize("-9223372036854775808"), Some(isize::MIN));
+    assert_eq!(parse_isize("9223372036854775808"), None);
+    assert_eq!(parse_bool("on"), Some(true));
+    assert_eq!(parse_bool("maybe"), None);
+
+    assert_ne!(random_u64(), random_u64());
+    for _ in 0..100 {
+        let value = random_range(10, 20);
+        assert!((10..20).contains(&value));
+    }
+    let mut items = [0, 1, 2, 3, 4, 5, 6, 7];
+    shuffle(&mut items);
+    items.sort();
+    assert_eq!(items, [0, 1, 2, 3, 4, 5, 6, 7]);
+
+    assert_eq!(format!("{}", Millis(0)), "00:00:00.000");
+    assert_eq!(format!("{}", Millis(3_723_004)), "01:02:03.004");
+    assert_eq!(format!("{}", Millis(90_000_000)), "1d 01:00:00.000");
+
+    // stdout is line buffered, this must show up before the stderr line
+    print!("buffered, ");
+    eprintln!("unbuffered");
+    println!("Test ulib OK! uptime {}", uptime());
+    0
+}
@@ -0,0 +1,27 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::console::flush;
+use user_lib::{write, yield_};
+
+const STDOUT: usize = 1;
+const LINES: usize = 50;
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    // write every line in pieces and yield in between: other tasks run while the line is
+    // half written, but the kernel only prints whole lines
+    for i in 0..LINES {
+        write(STDOUT, b"[console_stress] line ");
+        yield_();
+        print!("{:02}", i);
+        flush();
+        yield_();
+        write(STDOUT, b": the quick brown fox jumps over the lazy dog\n");
+    }
+    println!("Test console stress OK!");
+    0
+}
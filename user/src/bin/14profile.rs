@@ -0,0 +1,67 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::hint::black_box;
+use user_lib::{get_profile, get_time};
+
+const MAX_SAMPLES: usize = 256;
+const TOP_ENTRIES: usize = 5;
+
+#[inline(never)]
+fn spin(iterations: usize) -> usize {
+    let mut acc = 0usize;
+    for i in 0..iterations {
+        acc = black_box(acc.wrapping_mul(31).wrapping_add(i));
+    }
+    acc
+}
+
+/// Print the most sampled pcs with their share of all samples.
+fn print_flat_profile(samples: &mut [usize]) {
+    samples.sort_unstable();
+    let mut top = [(0usize, 0usize); TOP_ENTRIES]; // (count, pc)
+    let mut i = 0;
+    while i < samples.len() {
+        let pc = samples[i];
+        let count = samples[i..].iter().take_while(|&&s| s == pc).count();
+        if let Some(min) = top.iter_mut().min_by_key(|entry| entry.0) {
+            if count > min.0 {
+                *min = (count, pc);
+            }
+        }
+        i += count;
+    }
+    top.sort_unstable_by(|a, b| b.0.cmp(&a.0));
+    println!("{:>6} {:>6}  pc", "count", "%");
+    for (count, pc) in top.iter().filter(|entry| entry.0 > 0) {
+        println!(
+            "{:>6} {:>5}%  {:#x}",
+            count,
+            count * 100 / samples.len(),
+            pc
+        );
+    }
+}
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    // stay busy for half a second so some timer ticks land in `spin`
+    let start = get_time();
+    while get_time() - start < 500 {
+        spin(10_000);
+    }
+
+    let mut samples = [0usize; MAX_SAMPLES];
+    let n = get_profile(&mut samples);
+    if n == -1 {
+        println!("kernel built without profiler, skipped");
+    } else {
+        println!("{} samples, spin() at {:#x}", n, spin as usize);
+        print_flat_profile(&mut samples[..n as usize]);
+    }
+    println!("Test profile OK!");
+    0
+}
@@ -0,0 +1,49 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{get_time, kcov_collect, kcov_enable, yield_};
+
+const CAPACITY: usize = 4096;
+
+/// Returns the number of distinct pcs in `pcs`, sorting it.
+fn distinct(pcs: &mut [usize]) -> usize {
+    pcs.sort_unstable();
+    pcs.iter()
+        .enumerate()
+        .filter(|&(i, pc)| i == 0 || pcs[i - 1] != *pc)
+        .count()
+}
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    if kcov_enable(CAPACITY) == -1 {
+        println!("kernel built without kcov, skipped");
+        println!("Test kcov OK!");
+        return 0;
+    }
+
+    let mut pcs = [0usize; CAPACITY];
+    // drop what enabling recorded
+    kcov_collect(&mut pcs);
+
+    get_time();
+    let n = kcov_collect(&mut pcs) as usize;
+    assert!(n > 0, "get_time ran no instrumented kernel code");
+    let get_time_pcs = distinct(&mut pcs[..n]);
+
+    get_time();
+    yield_();
+    let n = kcov_collect(&mut pcs) as usize;
+    let more_pcs = distinct(&mut pcs[..n]);
+    println!(
+        "kcov: get_time reached {} pcs, get_time + yield {} pcs",
+        get_time_pcs, more_pcs
+    );
+
+    assert_eq!(kcov_enable(0), 0);
+    println!("Test kcov OK!");
+    0
+}
@@ -0,0 +1,154 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::ptr::write_volatile;
+use user_lib::rand::{random_range, random_u64};
+use user_lib::{
+    MADV_DONTNEED, MADV_WILLNEED, SysInfo, TimeSpec, UtsName, VmaInfo, fault_inject, get_time,
+    get_vma_info, getrandom, madvise, nanosleep, raw_syscall, sysinfo, uname, write, yield_,
+};
+
+const ITERATIONS: usize = 2000;
+const PAGE_SIZE: usize = 4096;
+const USER_SPACE_END: usize = 1 << 38;
+
+/// Frame allocations fail with this probability (permille) once fault injection is on.
+const FRAME_FAIL_PERMILLE: usize = 200;
+/// Heap allocations fail with this probability (permille) once fault injection is on.
+const HEAP_FAIL_PERMILLE: usize = 200;
+
+const SYSCALL_WRITE: usize = 64;
+const SYSCALL_NANOSLEEP: usize = 101;
+/// Syscalls without side effects that only write to their pointer arguments, called with
+/// random arguments: `capget`, `clock_gettime`, `uname`, `getrusage`, `get_time`, `sysinfo`,
+/// `getrandom`, `get_vma_info`, `group_stat`, `task_info` and `slab_info`.
+const WRITING_SYSCALLS: [usize; 11] = [90, 113, 160, 165, 169, 179, 278, 1001, 1008, 1010, 1012];
+/// Syscall ids from here on are unknown to the kernel.
+const FIRST_UNKNOWN_SYSCALL: u64 = 1014;
+
+/// A random address for `madvise`: mostly in the grown user stack, sometimes anywhere in user
+/// space. Stack addresses stay well below our own frames, `MADV_DONTNEED` would wipe them.
+fn random_addr(stack_page: usize) -> usize {
+    match random_range(0, 4) {
+        0 => random_range(0, 0x40_0000_0000) as usize,
+        1 => stack_page - random_range(8, 16) as usize * PAGE_SIZE + random_range(0, 2) as usize,
+        _ => stack_page - random_range(8, 16) as usize * PAGE_SIZE,
+    }
+}
+
+/// A pointer no syscall may accept: unmapped, outside user space, wrapping around or
+/// straddling its end.
+fn bad_ptr() -> usize {
+    match random_range(0, 5) {
+        0 => random_range(0, PAGE_SIZE as u64) as usize,
+        1 => USER_SPACE_END - random_range(1, 8) as usize,
+        2 => USER_SPACE_END + random_range(0, 1 << 20) as usize,
+        3 => usize::MAX - random_range(0, 8) as usize,
+        // the kernel's half of the address space
+        _ => 0xffff_ffc0_8020_0000 + random_range(0, 1 << 20) as usize,
+    }
+}
+
+/// A random syscall argument that is never the address of memory we can write: small
+/// numbers, bad pointers, our read-only code or random bits.
+fn random_arg() -> usize {
+    match random_range(0, 4) {
+        0 => random_range(0, 16) as usize,
+        1 => bad_ptr(),
+        2 => main as usize + random_range(0, 64) as usize,
+        _ => random_u64() as usize,
+    }
+}
+
+/// Issue one random syscall. Typed calls pass our own buffers, so their only failures are the
+/// kernel's own error paths; raw calls pass bad pointers or unknown ids, which have to fail.
+fn fuzz_one(stack_page: usize) -> isize {
+    match random_range(0, 12) {
+        0 => get_time(),
+        1 => yield_(),
+        2 => {
+            let advice =
+                [MADV_WILLNEED, MADV_DONTNEED, random_u64() as usize][random_range(0, 3) as usize];
+            let len = random_range(0, 6) as usize * PAGE_SIZE + random_range(0, 2) as usize;
+            madvise(random_addr(stack_page), len, advice)
+        }
+        3 => {
+            let req = TimeSpec {
+                sec: 0,
+                // Keep sleeps short; the upper end is an invalid nsec value.
+                nsec: [random_range(0, 2_000_000), 1_000_000_000][random_range(0, 2) as usize]
+                    as usize,
+            };
+            let mut rem = TimeSpec::default();
+            nanosleep(&req, &mut rem, random_range(0, 2) as usize)
+        }
+        4 => {
+            let mut buf = [0u8; 64];
+            let len = random_range(0, 65) as usize;
+            getrandom(&mut buf[..len])
+        }
+        5 => {
+            let mut vmas = [VmaInfo::default(); 8];
+            let len = random_range(0, 9) as usize;
+            get_vma_info(&mut vmas[..len])
+        }
+        6 => sysinfo(&mut SysInfo::default()),
+        7 => uname(&mut UtsName::default()),
+        8 => write(random_range(3, 16) as usize, b"fuzz"),
+        9 => {
+            let id = WRITING_SYSCALLS[random_range(0, WRITING_SYSCALLS.len() as u64) as usize];
+            raw_syscall(id, core::array::from_fn(|_| random_arg()))
+        }
+        10 => {
+            let ret = match random_range(0, 2) {
+                0 => raw_syscall(SYSCALL_NANOSLEEP, [bad_ptr(), 0, 0, 0, 0, 0]),
+                _ => raw_syscall(
+                    SYSCALL_WRITE,
+                    [1, bad_ptr(), random_range(1, 64) as usize, 0, 0, 0],
+                ),
+            };
+            assert_eq!(ret, -1, "a bad pointer was accepted");
+            ret
+        }
+        _ => {
+            let id = random_range(FIRST_UNKNOWN_SYSCALL, 1 << 20) as usize;
+            let ret = raw_syscall(id, core::array::from_fn(|_| random_arg()));
+            assert_eq!(ret, -1, "unknown syscall {id} did not fail");
+            ret
+        }
+    }
+}
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    let marker = 0u8;
+    let stack_page = &marker as *const u8 as usize & !(PAGE_SIZE - 1);
+    // grow the stack so that `random_addr` finds untouched anonymous pages below our frames
+    unsafe { write_volatile((stack_page - 16 * PAGE_SIZE) as *mut u8, 0) };
+
+    for _ in 0..ITERATIONS {
+        fuzz_one(stack_page);
+    }
+    println!("fuzz: {} random syscalls survived", ITERATIONS);
+
+    if fault_inject(FRAME_FAIL_PERMILLE, HEAP_FAIL_PERMILLE) == 0 {
+        let mut failed = 0;
+        for _ in 0..ITERATIONS {
+            if fuzz_one(stack_page) == -1 {
+                failed += 1;
+            }
+        }
+        fault_inject(0, 0);
+        println!(
+            "fuzz: {} random syscalls with fault injection survived, {} failed",
+            ITERATIONS, failed
+        );
+    } else {
+        println!("kernel built without fault_injection, skipped");
+    }
+    println!("Test fuzz OK!");
+    0
+}
@@ -0,0 +1,24 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{SYS_RISCV_FLUSH_ICACHE_LOCAL, riscv_flush_icache};
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    let code = main as usize;
+    assert_eq!(riscv_flush_icache(code, code + 64, 0), 0);
+    assert_eq!(
+        riscv_flush_icache(code, code + 64, SYS_RISCV_FLUSH_ICACHE_LOCAL),
+        0
+    );
+    assert_eq!(riscv_flush_icache(code, code, 0), 0);
+
+    // reversed range and unknown flags are rejected
+    assert_eq!(riscv_flush_icache(code + 64, code, 0), -1);
+    assert_eq!(riscv_flush_icache(code, code + 64, 2), -1);
+    println!("Test flush_icache OK!");
+    0
+}
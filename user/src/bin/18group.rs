@@ -0,0 +1,41 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{GROUP_DEFAULT_WEIGHT, GroupStat, get_time, group_attach, group_create, group_stat};
+
+/// Spin without syscalls other than reading the time for `ms` milliseconds.
+fn spin(ms: isize) {
+    let start = get_time();
+    while get_time() - start < ms {}
+}
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    let mut stat = GroupStat::default();
+    assert_eq!(group_stat(0, &mut stat), 0);
+    assert_eq!(stat.weight, GROUP_DEFAULT_WEIGHT);
+    assert!(stat.tasks >= 1);
+
+    let group = group_create(3 * GROUP_DEFAULT_WEIGHT);
+    assert!(group > 0);
+    let group = group as usize;
+    assert_eq!(group_attach(group), 0);
+    spin(100);
+
+    assert_eq!(group_stat(group, &mut stat), 0);
+    assert_eq!(stat.weight, 3 * GROUP_DEFAULT_WEIGHT);
+    assert_eq!(stat.tasks, 1);
+    assert!(stat.ticks > 0);
+    println!("group {}: {} ticks in 100 ms", group, stat.ticks);
+
+    // bad weights and unknown groups are rejected
+    assert_eq!(group_create(0), -1);
+    assert_eq!(group_create(10_001), -1);
+    assert_eq!(group_attach(1000), -1);
+    assert_eq!(group_stat(1000, &mut stat), -1);
+    println!("Test group OK!");
+    0
+}
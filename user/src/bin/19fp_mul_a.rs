@@ -0,0 +1,32 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::hint::black_box;
+use user_lib::yield_;
+
+const ROUNDS: usize = 100;
+const ITERATIONS: usize = 100_000;
+
+/// Scale by 2 and back, exact in binary, so the values only change if another task's FP
+/// state leaks into ours. 19fp_mul_b runs the same loop with other values.
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    let two = black_box(2.0f64);
+    let half = black_box(0.5f64);
+    let expected: [f64; 4] = [1.25, -3.5, 7.0, 0.1];
+    let mut values = expected;
+    for _ in 0..ROUNDS {
+        for _ in 0..ITERATIONS {
+            for x in values.iter_mut() {
+                *x = *x * two * half;
+            }
+        }
+        yield_();
+    }
+    assert_eq!(values, expected);
+    println!("Test fp_mul_a OK!");
+    0
+}
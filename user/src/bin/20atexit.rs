@@ -0,0 +1,30 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicBool, Ordering};
+use user_lib::atexit;
+
+static SECOND_RAN: AtomicBool = AtomicBool::new(false);
+static MAIN_RETURNED: AtomicBool = AtomicBool::new(false);
+
+/// Registered first, so it runs last.
+fn first() {
+    assert!(MAIN_RETURNED.load(Ordering::Relaxed));
+    assert!(SECOND_RAN.load(Ordering::Relaxed));
+    println!("Test atexit OK!");
+}
+
+fn second() {
+    SECOND_RAN.store(true, Ordering::Relaxed);
+}
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    assert_eq!(atexit(first), 0);
+    assert_eq!(atexit(second), 0);
+    MAIN_RETURNED.store(true, Ordering::Relaxed);
+    0
+}
@@ -0,0 +1,22 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::hint::black_box;
+
+/// Recurse forever with a 4 KiB frame each, one new stack page per call.
+#[allow(unconditional_recursion)]
+fn recurse(depth: usize) -> usize {
+    let frame = black_box([depth as u8; 4096]);
+    recurse(depth + 1) + frame[4095] as usize
+}
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    println!("Into Test stack overflow, we will recurse without end...");
+    println!("Kernel should kill this application once the stack passes its limit!");
+    recurse(0);
+    0
+}
@@ -0,0 +1,43 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::hint::black_box;
+use core::ptr::{read_volatile, write_volatile};
+use user_lib::{MADV_DONTNEED, MADV_WILLNEED, madvise};
+
+const PAGE_SIZE: usize = 4096;
+/// Stack pages to churn, grown once up front.
+const PAGES: usize = 64;
+const ROUNDS: usize = 200;
+
+/// Grow the stack by about `PAGES` pages and return the lowest page touched.
+#[inline(never)]
+fn grow_stack() -> usize {
+    let area = black_box([0u8; PAGES * PAGE_SIZE]);
+    (area.as_ptr() as usize + PAGE_SIZE - 1) & !(PAGE_SIZE - 1)
+}
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    // the pages stay mapped after returning; churn the lower half, clear of the frames of
+    // the functions main calls
+    let start = grow_stack();
+    let len = PAGES / 2 * PAGE_SIZE;
+    for round in 0..ROUNDS {
+        // every round allocates and frees PAGES / 2 frames
+        assert_eq!(madvise(start, len, MADV_WILLNEED), 0);
+        for page in (start..start + len).step_by(PAGE_SIZE) {
+            unsafe { write_volatile(page as *mut usize, round ^ page) };
+        }
+        for page in (start..start + len).step_by(PAGE_SIZE) {
+            assert_eq!(unsafe { read_volatile(page as *const usize) }, round ^ page);
+        }
+        assert_eq!(madvise(start, len, MADV_DONTNEED), 0);
+        assert_eq!(unsafe { read_volatile(start as *const usize) }, 0);
+    }
+    println!("Test memory churn OK!");
+    0
+}
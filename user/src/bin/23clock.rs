@@ -0,0 +1,53 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    CLOCK_MONOTONIC, CLOCK_REALTIME, TIMER_ABSTIME, TimeSpec, clock_gettime, clock_nanosleep,
+    clock_settime, get_time,
+};
+
+/// Mid 2024 in seconds since the Unix epoch, well before any RTC this runs with.
+const SOME_TIME_AGO: usize = 1_720_000_000;
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    let mut rem = TimeSpec::default();
+    let mut mono = TimeSpec::default();
+    let mut real = TimeSpec::default();
+
+    assert_eq!(clock_gettime(CLOCK_MONOTONIC, &mut mono), 0);
+    assert_eq!(clock_gettime(CLOCK_REALTIME, &mut real), 0);
+    assert!(mono.to_ms().abs_diff(get_time() as usize) <= 1);
+    assert!(real.sec > SOME_TIME_AGO);
+
+    // relative sleeps on either clock
+    assert_eq!(
+        clock_nanosleep(CLOCK_REALTIME, 0, &TimeSpec::from_ms(100), &mut rem),
+        0
+    );
+    let mut after = TimeSpec::default();
+    clock_gettime(CLOCK_MONOTONIC, &mut after);
+    assert!(after.to_ms() - mono.to_ms() >= 100);
+
+    // absolute sleep on the wall clock
+    clock_gettime(CLOCK_REALTIME, &mut real);
+    let deadline = TimeSpec::from_ms(real.to_ms() + 200);
+    assert_eq!(
+        clock_nanosleep(CLOCK_REALTIME, TIMER_ABSTIME, &deadline, &mut rem),
+        0
+    );
+    clock_gettime(CLOCK_REALTIME, &mut real);
+    assert!(real.to_ms() >= deadline.to_ms());
+
+    // setting the clock takes CAP_SETTIME, and the monotonic clock is never set
+    assert_eq!(clock_settime(CLOCK_REALTIME, &real), -1);
+    assert_eq!(clock_settime(CLOCK_MONOTONIC, &real), -1);
+    // unknown clock
+    assert_eq!(clock_gettime(42, &mut real), -1);
+    assert_eq!(clock_nanosleep(42, 0, &TimeSpec::default(), &mut rem), -1);
+    println!("Test clock OK!");
+    0
+}
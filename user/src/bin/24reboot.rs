@@ -0,0 +1,17 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{REBOOT_CMD_POWER_OFF, REBOOT_CMD_RESTART, reboot};
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    // rebooting takes CAP_REBOOT, which only initproc holds
+    assert_eq!(reboot(REBOOT_CMD_RESTART), -1);
+    assert_eq!(reboot(REBOOT_CMD_POWER_OFF), -1);
+    assert_eq!(reboot(42), -1);
+    println!("Test reboot OK!");
+    0
+}
@@ -0,0 +1,45 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{SCHED_DEADLINE, SCHED_OTHER, SchedParam, get_time, sched_setscheduler, yield_};
+
+const MS: u64 = 1_000_000;
+/// Period of the control loop in milliseconds.
+const PERIOD_MS: isize = 50;
+const JOBS: isize = 5;
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    let param = SchedParam {
+        runtime_ns: 20 * MS,
+        deadline_ns: 40 * MS,
+        period_ns: PERIOD_MS as u64 * MS,
+    };
+    let start = get_time();
+    assert_eq!(sched_setscheduler(SCHED_DEADLINE, &param), 0);
+    // every yield ends a job and returns at the next release
+    for _ in 0..JOBS {
+        yield_();
+    }
+    let elapsed = get_time() - start;
+    println!("{} jobs in {} ms", JOBS, elapsed);
+    assert!(elapsed >= (JOBS - 1) * PERIOD_MS);
+
+    // runtime above deadline, deadline above period, and more than the CPU can give
+    let bad = |runtime_ms: u64, deadline_ms: u64, period_ms: u64| SchedParam {
+        runtime_ns: runtime_ms * MS,
+        deadline_ns: deadline_ms * MS,
+        period_ns: period_ms * MS,
+    };
+    assert_eq!(sched_setscheduler(SCHED_DEADLINE, &bad(30, 20, 50)), -1);
+    assert_eq!(sched_setscheduler(SCHED_DEADLINE, &bad(10, 60, 50)), -1);
+    assert_eq!(sched_setscheduler(SCHED_DEADLINE, &bad(0, 20, 50)), -1);
+    assert_eq!(sched_setscheduler(SCHED_DEADLINE, &bad(50, 50, 50)), -1);
+    assert_eq!(sched_setscheduler(42, &param), -1);
+    assert_eq!(sched_setscheduler(SCHED_OTHER, &param), 0);
+    println!("Test deadline OK!");
+    0
+}
@@ -0,0 +1,37 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::hint::black_box;
+use user_lib::{RUSAGE_CHILDREN, RUSAGE_SELF, RUsage, getrusage};
+
+/// Recurse `depth` times with a 512-byte frame each, growing the stack on the way down.
+fn recurse(depth: usize) -> usize {
+    let frame = black_box([depth as u8; 512]);
+    if depth == 0 {
+        return frame[0] as usize;
+    }
+    recurse(depth - 1) + frame[511] as usize
+}
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    let mut before = RUsage::default();
+    assert_eq!(getrusage(RUSAGE_SELF, &mut before), 0);
+    black_box(recurse(256));
+    let mut after = RUsage::default();
+    assert_eq!(getrusage(RUSAGE_SELF, &mut after), 0);
+
+    // every page the stack grew by took a minor fault, nothing is ever swapped out
+    assert!(after.minor_faults > before.minor_faults);
+    assert_eq!(after.major_faults, 0);
+    assert!(after.peak_frames > before.peak_frames);
+    assert!(after.user_ns >= before.user_ns);
+
+    // no process has children yet
+    assert_eq!(getrusage(RUSAGE_CHILDREN, &mut after), -1);
+    println!("Test getrusage OK!");
+    0
+}
@@ -0,0 +1,20 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{CAP_REBOOT, CAP_SETTIME, capget, capset};
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    // only initproc starts with capabilities
+    assert_eq!(capget(), 0);
+    assert_eq!(capset(CAP_REBOOT), -1);
+    assert_eq!(capset(CAP_REBOOT | CAP_SETTIME), -1);
+    assert_eq!(capget(), 0);
+    // dropping what one does not hold is fine
+    assert_eq!(capset(0), 0);
+    println!("Test capabilities OK!");
+    0
+}
@@ -0,0 +1,54 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use user_lib::{AcctRecord, acct_collect, sleep};
+
+/// Records fetched per syscall.
+const BATCH: usize = 32;
+
+const PAGE_SIZE_KIB: usize = 4;
+
+/// Print the apps that exited since boot, or since the last run, most recent first.
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    // let the apps started with us finish first
+    sleep(500);
+
+    let mut records = Vec::new();
+    let mut batch = [AcctRecord::default(); BATCH];
+    loop {
+        let n = acct_collect(&mut batch);
+        if n == -1 {
+            println!("lastcomm: kernel built without acct");
+            return 0;
+        }
+        records.extend_from_slice(&batch[..n as usize]);
+        if (n as usize) < BATCH {
+            break;
+        }
+    }
+
+    println!("COMMAND                 TASK  EXIT  USER  SYS  PEAK KiB  EXITED");
+    for record in records.iter().rev() {
+        let time_ms = record.time_ns / 1_000_000;
+        println!(
+            "{:<22} {:>5} {:>5} {:>5} {:>4} {:>9}  {}.{:03} s",
+            record.name(),
+            record.task,
+            record.exit_code,
+            record.user_ticks,
+            record.kernel_ticks,
+            record.peak_frames * PAGE_SIZE_KIB,
+            time_ms / 1000,
+            time_ms % 1000
+        );
+    }
+    println!("lastcomm: {} records", records.len());
+    0
+}
@@ -0,0 +1,57 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    VMA_ELF, VMA_R, VMA_STACK, VMA_TRAP_CONTEXT, VMA_U, VMA_W, VMA_X, VmaInfo, get_vma_info,
+};
+
+const MAX_VMAS: usize = 16;
+
+fn kind_name(kind: usize) -> &'static str {
+    match kind {
+        VMA_ELF => "elf",
+        VMA_STACK => "stack",
+        VMA_TRAP_CONTEXT => "trap-context",
+        _ => "kernel",
+    }
+}
+
+fn perm_char(perm: usize, bit: usize, c: char) -> char {
+    if perm & bit != 0 { c } else { '-' }
+}
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    let mut vmas = [VmaInfo::default(); MAX_VMAS];
+    let total = get_vma_info(&mut vmas);
+    if total < 0 {
+        println!("pmap: get_vma_info failed");
+        return -1;
+    }
+    let total = total as usize;
+
+    println!(
+        "{:<18} {:<18} {:<4} {:>6} kind",
+        "start", "end", "perm", "size"
+    );
+    for vma in &vmas[..total.min(MAX_VMAS)] {
+        println!(
+            "{:#018x} {:#018x} {}{}{}{} {:>5}K {}",
+            vma.start,
+            vma.end,
+            perm_char(vma.perm, VMA_R, 'r'),
+            perm_char(vma.perm, VMA_W, 'w'),
+            perm_char(vma.perm, VMA_X, 'x'),
+            perm_char(vma.perm, VMA_U, 'u'),
+            (vma.end - vma.start) / 1024,
+            kind_name(vma.kind),
+        );
+    }
+    if total > MAX_VMAS {
+        println!("... {} more", total - MAX_VMAS);
+    }
+    0
+}
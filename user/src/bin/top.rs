@@ -0,0 +1,101 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::time::Millis;
+use user_lib::{
+    SlabInfo, SysInfo, TASK_EXITED, TASK_RUNNING, TaskInfo, slab_info, sleep, sysinfo, task_info,
+};
+
+/// Number of refreshes before exiting.
+const ROUNDS: usize = 3;
+/// Most slab caches shown.
+const MAX_SLABS: usize = 8;
+/// Delay between refreshes in milliseconds.
+const INTERVAL_MS: usize = 1000;
+
+const PAGE_SIZE_KIB: usize = 4;
+
+fn show(info: &SysInfo, prev: &SysInfo) {
+    let interval = info.uptime_ms - prev.uptime_ms;
+    println!(
+        "top - up {}, {} hart(s), {} switches/s",
+        Millis(info.uptime_ms),
+        info.harts,
+        (info.context_switches - prev.context_switches) * 1000 / interval.max(1)
+    );
+    println!(
+        "Tasks: {} running, {} ready, {} sleeping, {} exited ({} kthreads)",
+        info.tasks_running, info.tasks_ready, info.tasks_blocked, info.tasks_exited, info.kthreads
+    );
+    println!(
+        "Mem:  {} KiB total, {} KiB free",
+        info.total_frames * PAGE_SIZE_KIB,
+        info.free_frames * PAGE_SIZE_KIB
+    );
+    println!(
+        "Heap: {} KiB total, {} KiB used",
+        info.heap_total / 1024,
+        info.heap_used / 1024
+    );
+    let mut slabs = [SlabInfo::default(); MAX_SLABS];
+    let n = (slab_info(&mut slabs).max(0) as usize).min(MAX_SLABS);
+    print!("Slab:");
+    for slab in &slabs[..n] {
+        print!(
+            " {}B {}/{}",
+            slab.size,
+            slab.in_use,
+            slab.slabs * PAGE_SIZE_KIB * 1024 / slab.size
+        );
+    }
+    println!("");
+    println!(
+        "Ticks: {} ({} during syscalls)",
+        info.timer_ticks, info.nested_interrupts
+    );
+    println!(
+        "Switches: {} voluntary, {} involuntary",
+        info.voluntary_switches, info.involuntary_switches
+    );
+    println!("  ID  STATE    VOL  INVOL  NAME");
+    let mut task = TaskInfo::default();
+    let mut id = 0;
+    while task_info(id, &mut task) == 0 {
+        let state = match task.status {
+            TASK_RUNNING => "R",
+            TASK_EXITED => "X",
+            _ => "S",
+        };
+        println!(
+            "{:>4}  {:<5} {:>5} {:>6}  {}{}",
+            task.id,
+            state,
+            task.voluntary_switches,
+            task.involuntary_switches,
+            task.name(),
+            if task.kthread != 0 { " [k]" } else { "" }
+        );
+        id += 1;
+    }
+}
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    let mut prev = SysInfo::default();
+    for round in 0..ROUNDS {
+        let mut info = SysInfo::default();
+        if sysinfo(&mut info) != 0 {
+            eprintln!("top: sysinfo failed");
+            return -1;
+        }
+        show(&info, &prev);
+        prev = info;
+        if round + 1 < ROUNDS {
+            sleep(INTERVAL_MS);
+        }
+    }
+    0
+}
@@ -0,0 +1,69 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    SCHED_OUT_EXITED, SCHED_OUT_PREEMPTED, SCHED_OUT_YIELDED, TRACE_BLOCK, TRACE_EXIT,
+    TRACE_SCHED_IN, TRACE_SCHED_OUT, TRACE_SPAWN, TRACE_WAKEUP, TraceRecord, sleep, trace_collect,
+    yield_,
+};
+
+/// Events fetched per syscall.
+const BATCH: usize = 64;
+
+fn print_record(record: &TraceRecord) {
+    let time_us = record.time_ns / 1000;
+    print!(
+        "{:>6}.{:03} ms  task {:>3}  ",
+        time_us / 1000,
+        time_us % 1000,
+        record.task
+    );
+    match record.event {
+        TRACE_SCHED_OUT => {
+            let reason = match record.arg {
+                SCHED_OUT_PREEMPTED => "preempted",
+                SCHED_OUT_YIELDED => "yielded",
+                SCHED_OUT_EXITED => "exited",
+                _ => "?",
+            };
+            println!("sched-out ({})", reason);
+        }
+        TRACE_SCHED_IN => println!("sched-in"),
+        TRACE_BLOCK => println!("block until {} ms", record.arg),
+        TRACE_WAKEUP => println!("wakeup"),
+        TRACE_SPAWN if record.arg == 1 => println!("spawn kthread"),
+        TRACE_SPAWN => println!("spawn"),
+        TRACE_EXIT => println!("exit {}", record.arg as i32),
+        event => println!("unknown event {}", event),
+    }
+}
+
+/// Print the scheduler timeline recorded since boot, or since the last run.
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    // give the trace something about ourselves
+    yield_();
+    sleep(10);
+
+    let mut records = [TraceRecord::default(); BATCH];
+    let mut total = 0;
+    loop {
+        let n = trace_collect(&mut records);
+        if n == -1 {
+            println!("trace: kernel built without sched_trace");
+            return 0;
+        }
+        for record in &records[..n as usize] {
+            print_record(record);
+        }
+        total += n as usize;
+        if (n as usize) < BATCH {
+            break;
+        }
+    }
+    println!("trace: {} events", total);
+    0
+}
@@ -0,0 +1,26 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{UtsName, uname};
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    let mut uts = UtsName::default();
+    if uname(&mut uts) != 0 {
+        println!("uname: failed");
+        return -1;
+    }
+    println!(
+        "{} {} {} {} {} harts={}",
+        UtsName::field_str(&uts.sysname),
+        UtsName::field_str(&uts.nodename),
+        UtsName::field_str(&uts.release),
+        UtsName::field_str(&uts.version),
+        UtsName::field_str(&uts.machine),
+        uts.harts
+    );
+    0
+}
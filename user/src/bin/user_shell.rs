@@ -1,70 +1,80 @@
 #![no_std]
 #![no_main]
 
-use alloc::string::String;
-use user_lib::console::getchar;
-use user_lib::{exec, fork, waitpid};
+use user_lib::tokenize::tokenize;
+use user_lib::{EXIT_PANIC, RUSAGE_CHILDREN, RUsage, exec, fork, getrusage, waitpid};
 
 extern crate alloc;
 
 #[macro_use]
 extern crate user_lib;
 
-/// Line Feed (LF) ASCII control character (0x0A).
-const LF: u8 = 0x0au8;
-/// Carriage Return (CR) ASCII control character (0x0D).
-const CR: u8 = 0x0du8;
-/// Delete (DEL) ASCII control character (0x7F).
-const DL: u8 = 0x7fu8;
-/// Backspace (BS) ASCII control character (0x08).
-const BS: u8 = 0x08u8;
+/// Print what the jobs that exited between `before` and `after` used, for the `verbose`
+/// builtin.
+fn print_usage(before: &RUsage, after: &RUsage) {
+    println!(
+        "Shell: {}ms user {}ms sys, {} KiB peak, {} minor {} major faults, {} voluntary {} involuntary switches",
+        (after.user_ns - before.user_ns) / 1_000_000,
+        (after.kernel_ns - before.kernel_ns) / 1_000_000,
+        after.peak_frames * 4,
+        after.minor_faults - before.minor_faults,
+        after.major_faults - before.major_faults,
+        after.voluntary_switches - before.voluntary_switches,
+        after.involuntary_switches - before.involuntary_switches
+    );
+}
 
 #[unsafe(no_mangle)]
 pub fn main() -> i32 {
     println!("Rust user shell");
-    let mut line: String = String::new();
-    print!(">> ");
+    let mut verbose = false;
     loop {
-        let c = getchar();
-        match c {
-            CR | LF => {
-                println!("");
-                if line.is_empty() {
-                    print!(">> ");
-                    continue;
-                }
+        let line = get_line!(">> ");
+        if line.is_empty() {
+            continue;
+        }
+
+        let mut args = match tokenize(&line) {
+            Ok(args) => args,
+            Err(err) => {
+                eprintln!("Shell: {}", err);
+                continue;
+            }
+        };
+        if args.is_empty() {
+            continue;
+        }
+        // `verbose` toggles the resource usage summary after each job
+        if args[0] == "verbose" {
+            verbose = !verbose;
+            continue;
+        }
 
-                line.push('\0');
-                let pid = fork();
-                // child process
-                if pid == 0 {
-                    if exec(line.as_str()) == -1 {
-                        println!("Error when executing!");
-                        return -4;
-                    }
-                    unreachable!();
-                } else {
-                    let mut exit_code: i32 = 0;
-                    let exit_pid = waitpid(pid as usize, &mut exit_code);
-                    assert_eq!(pid, exit_pid);
-                    println!("Shell: Process {} exited with code {}", pid, exit_code);
-                }
-                line.clear();
+        // only the program name is passed on, exec has no argv yet
+        let mut path = args.swap_remove(0);
+        path.push('\0');
+        let pid = fork();
+        // child process
+        if pid == 0 {
+            if exec(path.as_str()) == -1 {
+                eprintln!("Error when executing!");
+                return -4;
             }
-            BS | DL => {
-                if !line.is_empty() {
-                    // move cursor back
-                    print!("{}", BS as char);
-                    // print the space to overwrite the last character
-                    print!(" ");
-                    // move cursor back again
-                    print!("{}", BS as char);
-                    line.pop();
-                }
+            unreachable!();
+        } else {
+            let mut before = RUsage::default();
+            let usage = verbose && getrusage(RUSAGE_CHILDREN, &mut before) == 0;
+            let mut exit_code: i32 = 0;
+            let exit_pid = waitpid(pid as usize, &mut exit_code);
+            assert_eq!(pid, exit_pid);
+            if exit_code == EXIT_PANIC {
+                println!("Shell: Process {} panicked", pid);
+            } else {
+                println!("Shell: Process {} exited with code {}", pid, exit_code);
             }
-            _ => {
-                print!("{}", c as char);
-                line.push(c as char);
+            let mut after = RUsage::default();
+            if usage && getrusage(RUSAGE_CHILDREN, &mut after) == 0 {
+                print_usage(&before, &after);
             }
         }
     }
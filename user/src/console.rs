@@ -1,14 +1,72 @@
 use super::{read, write};
+use crate::parse::{parse_isize, parse_usize};
+use alloc::string::String;
 use core::fmt::{self, Write};
 
 struct Stdout;
+struct Stderr;
 
 const STDIN: usize = 0;
 const STDOUT: usize = 1;
+const STDERR: usize = 2;
+
+/// Size of the stdout buffer in bytes.
+const STDOUT_BUFFER_SIZE: usize = 256;
+
+/// Line buffer of stdout, flushed on newline, when full, before reading stdin and on exit.
+struct StdoutBuffer {
+    buf: [u8; STDOUT_BUFFER_SIZE],
+    len: usize,
+}
+
+static mut STDOUT_BUFFER: StdoutBuffer = StdoutBuffer {
+    buf: [0; STDOUT_BUFFER_SIZE],
+    len: 0,
+};
+
+/// Returns the stdout buffer. User programs are single threaded, so it may be dereferenced
+/// while no other reference to it is alive.
+fn stdout_buffer() -> *mut StdoutBuffer {
+    &raw mut STDOUT_BUFFER
+}
+
+impl StdoutBuffer {
+    fn flush(&mut self) {
+        if self.len > 0 {
+            write(STDOUT, &self.buf[..self.len]);
+            self.len = 0;
+        }
+    }
+}
+
+/// Write out everything buffered for stdout.
+pub fn flush() {
+    // SAFETY: no other reference to the buffer is alive
+    unsafe { &mut *stdout_buffer() }.flush();
+}
 
 impl Write for Stdout {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        write(STDOUT, s.as_bytes());
+        // SAFETY: nothing below takes another reference to the buffer
+        let buffer = unsafe { &mut *stdout_buffer() };
+        for c in s.chars() {
+            // never split a character, the kernel expects whole UTF-8 sequences
+            if buffer.len + c.len_utf8() > STDOUT_BUFFER_SIZE {
+                buffer.flush();
+            }
+            let len = c.encode_utf8(&mut buffer.buf[buffer.len..]).len();
+            buffer.len += len;
+            if c == '\n' {
+                buffer.flush();
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Write for Stderr {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        write(STDERR, s.as_bytes());
         Ok(())
     }
 }
@@ -17,6 +75,12 @@ pub fn print(args: fmt::Arguments) {
     Stdout.write_fmt(args).unwrap();
 }
 
+/// Print to stderr, unbuffered. Pending stdout output goes first to keep the order.
+pub fn eprint(args: fmt::Arguments) {
+    flush();
+    Stderr.write_fmt(args).unwrap();
+}
+
 #[macro_export]
 macro_rules! print {
     ($fmt: literal $(, $($arg: tt)+)?) => {
@@ -31,8 +95,157 @@ macro_rules! println {
     }
 }
 
+#[macro_export]
+macro_rules! eprint {
+    ($fmt: literal $(, $($arg: tt)+)?) => {
+        $crate::console::eprint(format_args!($fmt $(, $($arg)+)?));
+    }
+}
+
+#[macro_export]
+macro_rules! eprintln {
+    ($fmt: literal $(, $($arg: tt)+)?) => {
+        $crate::console::eprint(format_args!(concat!($fmt, "\n") $(, $($arg)+)?));
+    }
+}
+
+/// Size of the stdin buffer in bytes.
+const STDIN_BUFFER_SIZE: usize = 256;
+
+/// Line Feed (LF) ASCII control character (0x0A).
+const LF: u8 = 0x0a;
+/// Carriage Return (CR) ASCII control character (0x0D).
+const CR: u8 = 0x0d;
+/// Delete (DEL) ASCII control character (0x7F).
+const DL: u8 = 0x7f;
+/// Backspace (BS) ASCII control character (0x08).
+const BS: u8 = 0x08;
+
+/// Input of stdin: bytes read but not consumed yet, and the rest of the line `read_word`
+/// is taking words from.
+struct StdinBuffer {
+    buf: [u8; STDIN_BUFFER_SIZE],
+    start: usize,
+    end: usize,
+    line: String,
+    /// The last read of stdin failed or returned nothing.
+    eof: bool,
+}
+
+static mut STDIN_BUFFER: StdinBuffer = StdinBuffer {
+    buf: [0; STDIN_BUFFER_SIZE],
+    start: 0,
+    end: 0,
+    line: String::new(),
+    eof: false,
+};
+
+/// Returns the stdin buffer, see `stdout_buffer`.
+fn stdin_buffer() -> *mut StdinBuffer {
+    &raw mut STDIN_BUFFER
+}
+
+/// Read one byte of stdin, without echoing it.
+///
+/// Returns 0 at the end of input or if stdin can't be read.
 pub fn getchar() -> u8 {
-    let mut ch = [0u8; 1];
-    read(STDIN, &mut ch);
-    ch[0]
+    // SAFETY: nothing below takes another reference to the buffer
+    let buffer = unsafe { &mut *stdin_buffer() };
+    if buffer.start == buffer.end {
+        // show a pending prompt before blocking
+        flush();
+        let len = read(STDIN, &mut buffer.buf);
+        if len <= 0 {
+            buffer.eof = true;
+            return 0;
+        }
+        buffer.start = 0;
+        buffer.end = len as usize;
+    }
+    let c = buffer.buf[buffer.start];
+    buffer.start += 1;
+    c
+}
+
+/// Read a line of stdin, without the line ending.
+///
+/// The console is raw, so this echoes what is typed and handles backspace itself. If
+/// `read_word` left part of a line, that part is returned instead.
+pub fn read_line() -> String {
+    // SAFETY: the reference is gone before `getchar` takes another one
+    let pending = unsafe { &mut (*stdin_buffer()).line };
+    if !pending.is_empty() {
+        return core::mem::take(pending);
+    }
+    let mut line = String::new();
+    loop {
+        match getchar() {
+            // 0 is end of input
+            CR | LF | 0 => {
+                println!("");
+                return line;
+            }
+            BS | DL => {
+                if line.pop().is_some() {
+                    // step back, blank the character out and step back again
+                    print!("{} {}", BS as char, BS as char);
+                }
+            }
+            c => {
+                print!("{}", c as char);
+                line.push(c as char);
+            }
+        }
+    }
+}
+
+/// Read the next whitespace separated word of stdin, reading further lines as needed.
+///
+/// Returns `None` at the end of input.
+pub fn read_word() -> Option<String> {
+    loop {
+        // SAFETY: the reference is gone before `read_line` takes another one
+        let pending = unsafe { &mut (*stdin_buffer()).line };
+        let rest = pending.trim_start();
+        if !rest.is_empty() {
+            let len = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            let word = String::from(&rest[..len]);
+            *pending = String::from(&rest[len..]);
+            return Some(word);
+        }
+        pending.clear();
+        let line = read_line();
+        // SAFETY: no other reference to the buffer is alive
+        let buffer = unsafe { &mut *stdin_buffer() };
+        if line.is_empty() && buffer.eof {
+            return None;
+        }
+        buffer.line = line;
+    }
+}
+
+/// Read the next word of stdin as an unsigned integer, see `parse_usize`.
+///
+/// Returns `None` at the end of input or if the word is not a number.
+pub fn read_usize() -> Option<usize> {
+    parse_usize(&read_word()?)
+}
+
+/// Read the next word of stdin as a signed integer, see `parse_isize`.
+///
+/// Returns `None` at the end of input or if the word is not a number.
+pub fn read_isize() -> Option<isize> {
+    parse_isize(&read_word()?)
+}
+
+/// Print an optional prompt and read a line of stdin, see `read_line`.
+#[macro_export]
+macro_rules! get_line {
+    () => {
+        $crate::console::read_line()
+    };
+    ($fmt: literal $(, $($arg: tt)+)?) => {{
+        $crate::console::print(format_args!($fmt $(, $($arg)+)?));
+        $crate::console::read_line()
+    }};
 }
@@ -1,15 +1,59 @@
+use crate::syscall::sys_exit;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// Exit code of a process that panicked, the same as Rust's std uses.
+pub const EXIT_PANIC: i32 = 101;
+
+/// Maximum number of frames printed by a panic.
+const MAX_FRAMES: usize = 32;
+
+/// Stack pointer `_start` was entered with, the top of the user stack.
+pub(crate) static STACK_TOP: AtomicUsize = AtomicUsize::new(0);
+
+static PANICKING: AtomicBool = AtomicBool::new(false);
+
 #[panic_handler]
 fn panic_handler(panic_info: &core::panic::PanicInfo) -> ! {
-    let err = panic_info.message();
-    if let Some(location) = panic_info.location() {
-        println!(
-            "Panicked at {}:{}, {}",
-            location.file(),
-            location.line(),
-            err
-        );
-    } else {
-        println!("Panicked: {}", err);
+    // a panic while reporting one only exits
+    if !PANICKING.swap(true, Ordering::Relaxed) {
+        let err = panic_info.message();
+        if let Some(location) = panic_info.location() {
+            eprintln!(
+                "Panicked at {}:{}, {}",
+                location.file(),
+                location.line(),
+                err
+            );
+        } else {
+            eprintln!("Panicked: {}", err);
+        }
+        print_backtrace();
+    }
+    // no atexit callbacks, they may be what panicked
+    sys_exit(EXIT_PANIC);
+    unreachable!("panicked process returned from sys_exit");
+}
+
+/// Print the return addresses of the frames on the stack, the user crate is built with frame
+/// pointers. See `stack_trace.rs` of the kernel for the frame layout.
+fn print_backtrace() {
+    let top = STACK_TOP.load(Ordering::Relaxed);
+    let mut fp: usize;
+    unsafe { core::arch::asm!("mv {}, fp", out(reg) fp) };
+    eprintln!("Backtrace:");
+    for _ in 0..MAX_FRAMES {
+        // frames are only ever found further up the stack, anything else is garbage
+        if fp % size_of::<usize>() != 0 || fp > top || fp < 2 * size_of::<usize>() {
+            break;
+        }
+        let (ra, saved_fp) = unsafe {
+            let fp = fp as *const usize;
+            (*fp.sub(1), *fp.sub(2))
+        };
+        eprintln!("  0x{:x}", ra);
+        if saved_fp <= fp {
+            break;
+        }
+        fp = saved_fp;
     }
-    loop {}
 }
@@ -2,13 +2,21 @@
 #![feature(linkage)]
 #![feature(alloc_error_handler)]
 
+extern crate alloc;
+
 use buddy_system_allocator::LockedHeap;
 use syscall::*;
 
 #[macro_use]
 pub mod console;
 mod lang_items;
+pub mod parse;
+pub mod rand;
 mod syscall;
+pub mod time;
+pub mod tokenize;
+
+pub use lang_items::EXIT_PANIC;
 
 const USER_HEAP_SIZE: usize = 16384;
 
@@ -25,6 +33,10 @@ pub fn handle_alloc_error(layout: core::alloc::Layout) -> ! {
 #[unsafe(no_mangle)]
 #[unsafe(link_section = ".text.entry")]
 pub extern "C" fn _start() -> ! {
+    // with frame pointers, fp is the sp `_start` was entered with
+    let fp: usize;
+    unsafe { core::arch::asm!("mv {}, fp", out(reg) fp) };
+    lang_items::STACK_TOP.store(fp, core::sync::atomic::Ordering::Relaxed);
     unsafe {
         HEAP.lock()
             .init(&raw mut HEAP_SPACE as usize, USER_HEAP_SIZE);
@@ -46,9 +58,79 @@ pub fn read(fd: usize, buf: &mut [u8]) -> isize {
 pub fn write(fd: usize, buf: &[u8]) -> isize {
     sys_write(fd, buf)
 }
+/// Runs the `atexit` callbacks, flushes stdout and terminates the process with `exit_code`.
 pub fn exit(exit_code: i32) -> isize {
+    run_atexit();
+    console::flush();
     sys_exit(exit_code)
 }
+
+/// Exit code of a process that called `abort`, as a shell would report death by `SIGABRT`.
+pub const EXIT_ABORT: i32 = 128 + 6;
+
+/// Terminates the process right away with `EXIT_ABORT`, skipping the `atexit` callbacks.
+///
+/// There are no signals yet, so this cannot be caught.
+pub fn abort() -> ! {
+    console::flush();
+    sys_exit(EXIT_ABORT);
+    unreachable!("aborted process returned from sys_exit");
+}
+
+/// Maximum number of `atexit` callbacks of a process.
+pub const ATEXIT_MAX: usize = 32;
+
+/// Callbacks registered with `atexit`, in registration order.
+struct AtExitList {
+    fns: [Option<fn()>; ATEXIT_MAX],
+    len: usize,
+}
+
+static mut ATEXIT_LIST: AtExitList = AtExitList {
+    fns: [None; ATEXIT_MAX],
+    len: 0,
+};
+
+/// Returns the `atexit` callbacks. User programs are single threaded, so the list may be
+/// dereferenced while no other reference to it is alive.
+fn atexit_list() -> *mut AtExitList {
+    &raw mut ATEXIT_LIST
+}
+
+/// Registers `f` to run when the process exits, through `exit` or by returning from `main`.
+///
+/// Callbacks run in the reverse order of registration, before stdout is flushed for the last
+/// time.
+///
+/// # Returns
+///
+/// 0 on success, or -1 if `ATEXIT_MAX` callbacks are registered already.
+pub fn atexit(f: fn()) -> isize {
+    // SAFETY: no other reference to the list is alive
+    let list = unsafe { &mut *atexit_list() };
+    if list.len == ATEXIT_MAX {
+        return -1;
+    }
+    list.fns[list.len] = Some(f);
+    list.len += 1;
+    0
+}
+
+fn run_atexit() {
+    // each callback is taken off the list before it runs, so one calling `exit` goes on with
+    // the rest instead of running again
+    loop {
+        // SAFETY: the reference is gone before the callback, which may call `atexit`, runs
+        let list = unsafe { &mut *atexit_list() };
+        if list.len == 0 {
+            break;
+        }
+        list.len -= 1;
+        if let Some(f) = list.fns[list.len].take() {
+            f();
+        }
+    }
+}
 pub fn yield_() -> isize {
     sys_yield()
 }
@@ -56,6 +138,121 @@ pub fn get_time() -> isize {
     sys_get_time()
 }
 
+/// `nanosleep` flag: the request is an absolute deadline on the boot clock.
+pub const TIMER_ABSTIME: usize = 1;
+
+/// Wall clock time since the Unix epoch, settable with `CAP_SETTIME`.
+pub const CLOCK_REALTIME: usize = 0;
+/// Time since boot, never set and never going backwards.
+pub const CLOCK_MONOTONIC: usize = 1;
+
+/// A time value with nanosecond resolution, layout compatible with the kernel's.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TimeSpec {
+    pub sec: usize,
+    pub nsec: usize,
+}
+
+impl TimeSpec {
+    pub fn from_ms(ms: usize) -> Self {
+        Self {
+            sec: ms / 1000,
+            nsec: ms % 1000 * 1_000_000,
+        }
+    }
+
+    pub fn to_ms(&self) -> usize {
+        self.sec * 1000 + self.nsec / 1_000_000
+    }
+}
+
+/// Sleeps for `req`, or until the boot clock reaches `req` if `flags` is `TIMER_ABSTIME`.
+///
+/// # Arguments
+///
+/// * `req` - Duration or absolute deadline.
+/// * `rem` - Receives the time left when the sleep is cut short.
+/// * `flags` - `0` or `TIMER_ABSTIME`.
+///
+/// # Returns
+///
+/// 0 on success, or -1 if `req` is invalid.
+pub fn nanosleep(req: &TimeSpec, rem: &mut TimeSpec, flags: usize) -> isize {
+    sys_nanosleep(req, rem, flags)
+}
+
+/// Sleeps like `nanosleep`, on clock `clock_id`.
+///
+/// An absolute sleep on `CLOCK_REALTIME` ends when the clock reads `req`, even if it is set
+/// meanwhile. A relative sleep lasts `req` on any clock.
+///
+/// # Returns
+///
+/// 0 on success, or -1 for an unknown clock or an invalid `req`.
+pub fn clock_nanosleep(clock_id: usize, flags: usize, req: &TimeSpec, rem: &mut TimeSpec) -> isize {
+    sys_clock_nanosleep(clock_id, flags, req, rem)
+}
+
+/// Gets the time of clock `clock_id`.
+///
+/// # Returns
+///
+/// 0 on success, or -1 for an unknown clock.
+pub fn clock_gettime(clock_id: usize, tp: &mut TimeSpec) -> isize {
+    sys_clock_gettime(clock_id, tp)
+}
+
+/// `reboot` commands.
+pub const REBOOT_CMD_RESTART: usize = 0x0123_4567;
+pub const REBOOT_CMD_POWER_OFF: usize = 0x4321_fedc;
+
+/// Restarts the machine with a warm reboot, the next boot prints this one's kernel log, or
+/// powers it off; the caller needs `CAP_REBOOT`.
+///
+/// Returns -1 for an unknown `cmd` or a caller without the capability, does not return
+/// otherwise.
+pub fn reboot(cmd: usize) -> isize {
+    sys_reboot(cmd)
+}
+
+/// Sets `CLOCK_REALTIME` to `tp`; the caller needs `CAP_SETTIME`.
+///
+/// # Returns
+///
+/// 0 on success, or -1 for another clock or a caller without the capability.
+pub fn clock_settime(clock_id: usize, tp: &TimeSpec) -> isize {
+    sys_clock_settime(clock_id, tp)
+}
+
+/// Capabilities, bits of privilege a process holds. initproc starts with all of them, every
+/// other app with none.
+pub const CAP_REBOOT: usize = 1 << 0;
+pub const CAP_MOUNT: usize = 1 << 1;
+pub const CAP_RAWIO: usize = 1 << 2;
+pub const CAP_SETTIME: usize = 1 << 3;
+pub const CAP_KILL_ANY: usize = 1 << 4;
+
+/// Gets the capabilities of the calling process, `CAP_*` bits.
+pub fn capget() -> usize {
+    sys_capget() as usize
+}
+
+/// Drops the capabilities of the calling process to `caps`.
+///
+/// # Returns
+///
+/// 0 on success, or -1 if `caps` holds one the process does not; dropped capabilities
+/// cannot be regained.
+pub fn capset(caps: usize) -> isize {
+    sys_capset(caps)
+}
+
+/// Sleeps for `ms` milliseconds.
+pub fn sleep(ms: usize) -> isize {
+    sys_nanosleep(&TimeSpec::from_ms(ms), core::ptr::null_mut(), 0)
+}
+
 /// Creates a new process by duplicating current process.
 ///
 /// # Returns
@@ -127,3 +324,426 @@ pub fn waitpid(pid: usize, exit_code: &mut i32) -> isize {
         }
     }
 }
+
+/// `madvise` advice: the range will be accessed soon.
+pub const MADV_WILLNEED: usize = 3;
+/// `madvise` advice: the range is not needed anymore, its contents may be dropped.
+pub const MADV_DONTNEED: usize = 4;
+
+/// Gives the kernel advice about the use of the anonymous memory `[addr, addr + len)`.
+///
+/// After `MADV_DONTNEED` the range reads as zeros.
+///
+/// # Returns
+///
+/// 0 on success, or -1 if `addr` isn't page aligned or the range isn't anonymous memory.
+pub fn madvise(addr: usize, len: usize, advice: usize) -> isize {
+    sys_madvise(addr, len, advice)
+}
+
+/// `riscv_flush_icache` flag: only the calling hart will run the new code.
+pub const SYS_RISCV_FLUSH_ICACHE_LOCAL: usize = 1;
+
+/// Makes instruction fetches see code written to `[start, end)`, call it after generating or
+/// patching code and before running it.
+///
+/// # Returns
+///
+/// 0 on success, or -1 if `start > end` or `flags` is unknown.
+pub fn riscv_flush_icache(start: usize, end: usize, flags: usize) -> isize {
+    sys_riscv_flush_icache(start, end, flags)
+}
+
+/// Prints the kernel's per-syscall latency histograms to the console.
+///
+/// Returns -1 if the kernel was built without the `syscall_latency` feature.
+pub fn dump_syscall_latency() -> isize {
+    sys_dump_syscall_latency()
+}
+
+/// `VmaInfo::kind` of kernel memory.
+pub const VMA_KERNEL: usize = 0;
+/// `VmaInfo::kind` of a segment loaded from the ELF file.
+pub const VMA_ELF: usize = 1;
+/// `VmaInfo::kind` of the user stack.
+pub const VMA_STACK: usize = 2;
+/// `VmaInfo::kind` of the trap context page.
+pub const VMA_TRAP_CONTEXT: usize = 3;
+
+/// `VmaInfo::perm` bits.
+pub const VMA_R: usize = 1 << 1;
+pub const VMA_W: usize = 1 << 2;
+pub const VMA_X: usize = 1 << 3;
+pub const VMA_U: usize = 1 << 4;
+
+/// One memory area of the current process, layout compatible with the kernel's.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct VmaInfo {
+    pub start: usize,
+    pub end: usize,
+    pub perm: usize,
+    pub kind: usize,
+}
+
+/// Fills `buf` with the memory areas of the current process.
+///
+/// # Returns
+///
+/// The total number of memory areas, which may be larger than `buf.len()`.
+pub fn get_vma_info(buf: &mut [VmaInfo]) -> isize {
+    sys_get_vma_info(buf)
+}
+
+/// Length of each `UtsName` string, including the terminating NUL.
+const UTS_LEN: usize = 65;
+
+/// System identification, layout compatible with the kernel's.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct UtsName {
+    pub sysname: [u8; UTS_LEN],
+    pub nodename: [u8; UTS_LEN],
+    pub release: [u8; UTS_LEN],
+    pub version: [u8; UTS_LEN],
+    pub machine: [u8; UTS_LEN],
+    pub harts: usize,
+}
+
+impl Default for UtsName {
+    fn default() -> Self {
+        Self {
+            sysname: [0; UTS_LEN],
+            nodename: [0; UTS_LEN],
+            release: [0; UTS_LEN],
+            version: [0; UTS_LEN],
+            machine: [0; UTS_LEN],
+            harts: 0,
+        }
+    }
+}
+
+impl UtsName {
+    /// Returns the NUL terminated string in `field`.
+    pub fn field_str(field: &[u8; UTS_LEN]) -> &str {
+        let len = field.iter().position(|&b| b == 0).unwrap_or(UTS_LEN);
+        core::str::from_utf8(&field[..len]).unwrap_or("?")
+    }
+}
+
+/// Gets the name, version, build time, board and hart count of the system.
+pub fn uname(buf: &mut UtsName) -> isize {
+    sys_uname(buf)
+}
+
+/// `getrusage` targets.
+pub const RUSAGE_SELF: isize = 0;
+pub const RUSAGE_CHILDREN: isize = -1;
+
+/// Resource usage statistics, layout compatible with the kernel's.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct RUsage {
+    /// Time spent in user mode and in syscalls, with timer tick resolution.
+    pub user_ns: u64,
+    pub kernel_ns: u64,
+    pub peak_frames: usize,
+    /// Page faults resolved without I/O, e.g. stack growth or first writes to zero-filled pages.
+    pub minor_faults: usize,
+    /// Page faults that had to read a page back in.
+    pub major_faults: usize,
+    pub voluntary_switches: usize,
+    pub involuntary_switches: usize,
+}
+
+/// Gets resource usage statistics of the calling process (`RUSAGE_SELF`) or of its exited
+/// children (`RUSAGE_CHILDREN`).
+///
+/// # Returns
+///
+/// 0 on success, -1 if the kernel does not support `who`; no process has children yet.
+pub fn getrusage(who: isize, usage: &mut RUsage) -> isize {
+    sys_getrusage(who, usage)
+}
+
+/// Fills `buf` with random bytes from the kernel, see `rand` for convenience wrappers.
+///
+/// # Returns
+///
+/// The number of bytes written.
+pub fn getrandom(buf: &mut [u8]) -> isize {
+    sys_getrandom(buf, 0)
+}
+
+/// System status, layout compatible with the kernel's.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SysInfo {
+    pub uptime_ms: usize,
+    pub total_frames: usize,
+    pub free_frames: usize,
+    pub heap_total: usize,
+    pub heap_used: usize,
+    pub tasks_ready: usize,
+    pub tasks_running: usize,
+    pub tasks_exited: usize,
+    pub tasks_blocked: usize,
+    pub kthreads: usize,
+    pub harts: usize,
+    pub timer_ticks: usize,
+    pub context_switches: usize,
+    pub voluntary_switches: usize,
+    pub involuntary_switches: usize,
+    pub nested_interrupts: usize,
+}
+
+/// Gets uptime, memory, task and scheduler statistics of the system.
+pub fn sysinfo(info: &mut SysInfo) -> isize {
+    sys_sysinfo(info)
+}
+
+/// A slab cache of the kernel heap, layout compatible with the kernel's.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SlabInfo {
+    /// Size of the objects in bytes.
+    pub size: usize,
+    /// Pages the cache took from the heap.
+    pub slabs: usize,
+    /// Objects allocated right now.
+    pub in_use: usize,
+    /// Objects allocated since boot.
+    pub allocs: usize,
+}
+
+/// Fills `buf` with the statistics of the kernel heap's slab caches, smallest objects first.
+///
+/// # Returns
+///
+/// The total number of caches, which may be larger than `buf.len()`.
+pub fn slab_info(buf: &mut [SlabInfo]) -> isize {
+    sys_slab_info(buf)
+}
+
+/// Copies the profiler samples (pcs interrupted by timer ticks) of the current process to
+/// `buf`, oldest first.
+///
+/// Returns the number of samples, or -1 if the kernel was built without the `profiler`
+/// feature.
+pub fn get_profile(buf: &mut [usize]) -> isize {
+    sys_get_profile(buf)
+}
+
+/// Starts recording which kernel code this process's syscalls run, keeping up to `capacity`
+/// pcs; 0 stops recording.
+///
+/// Returns -1 if the kernel was built without the `kcov` feature.
+pub fn kcov_enable(capacity: usize) -> isize {
+    sys_kcov_enable(capacity)
+}
+
+/// Moves the kernel pcs recorded since the last call to `buf`.
+///
+/// Returns the number of pcs, or -1 if the kernel was built without the `kcov` feature.
+pub fn kcov_collect(buf: &mut [usize]) -> isize {
+    sys_kcov_collect(buf)
+}
+
+/// Makes frame and heap allocations in this process's later syscalls fail with the given
+/// probabilities in permille; `0, 0` turns it off.
+///
+/// Returns -1 if the kernel was built without the `fault_injection` feature.
+pub fn fault_inject(frame_permille: usize, heap_permille: usize) -> isize {
+    sys_fault_inject(frame_permille, heap_permille)
+}
+
+/// Weight of task group 0, which every process starts in.
+pub const GROUP_DEFAULT_WEIGHT: usize = 100;
+
+/// Usage of a task group, layout compatible with the kernel's.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct GroupStat {
+    pub weight: usize,
+    /// Processes in the group that have not exited.
+    pub tasks: usize,
+    /// Timer ticks its processes ran.
+    pub ticks: usize,
+}
+
+/// `sched_setscheduler` policies.
+pub const SCHED_OTHER: usize = 0;
+pub const SCHED_DEADLINE: usize = 6;
+
+/// Parameters of a `SCHED_DEADLINE` task, layout compatible with the kernel's. Times in ns.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SchedParam {
+    /// CPU time each job needs, charged per 10 ms timer tick.
+    pub runtime_ns: u64,
+    /// Time after its release by which a job must be done.
+    pub deadline_ns: u64,
+    /// Time between job releases.
+    pub period_ns: u64,
+}
+
+/// Moves the current process to scheduling class `policy`.
+///
+/// `SCHED_DEADLINE` processes run ahead of all others, earliest deadline first. A job ends
+/// with `yield_`, which returns at the next release; a job running longer than its runtime
+/// waits for the next release as well. `param` is ignored for `SCHED_OTHER`.
+///
+/// Returns -1 for an unknown policy, invalid parameters, or if the deadline processes would
+/// reserve more than 90% of the CPU.
+pub fn sched_setscheduler(policy: usize, param: &SchedParam) -> isize {
+    sys_sched_setscheduler(0, policy, param)
+}
+
+/// Creates a task group; groups share the CPU in proportion to their weights.
+///
+/// Returns the group id, or -1 if `weight` is 0 or above 10000, or there are too many groups.
+pub fn group_create(weight: usize) -> isize {
+    sys_group_create(weight)
+}
+
+/// Moves the calling process to task group `group`.
+pub fn group_attach(group: usize) -> isize {
+    sys_group_attach(group)
+}
+
+/// Gets the weight, process count and CPU ticks of task group `group`.
+pub fn group_stat(group: usize, stat: &mut GroupStat) -> isize {
+    sys_group_stat(group, stat)
+}
+
+/// `TraceRecord::event`: `task` stopped running, `arg` is a `SCHED_OUT_*` reason.
+pub const TRACE_SCHED_OUT: usize = 1;
+/// `TraceRecord::event`: `task` started running.
+pub const TRACE_SCHED_IN: usize = 2;
+/// `TraceRecord::event`: `task` went to sleep, `arg` is the deadline in ms on the clock it sleeps on.
+pub const TRACE_BLOCK: usize = 3;
+/// `TraceRecord::event`: `task` woke up from a sleep.
+pub const TRACE_WAKEUP: usize = 4;
+/// `TraceRecord::event`: `task` was created, `arg` is 1 for a kthread and 0 for an app.
+pub const TRACE_SPAWN: usize = 5;
+/// `TraceRecord::event`: `task` exited, `arg` is its exit code.
+pub const TRACE_EXIT: usize = 6;
+
+/// `TRACE_SCHED_OUT` reasons.
+pub const SCHED_OUT_PREEMPTED: usize = 0;
+pub const SCHED_OUT_YIELDED: usize = 1;
+pub const SCHED_OUT_EXITED: usize = 2;
+
+/// One scheduler event, layout compatible with the kernel's.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TraceRecord {
+    /// Time since boot in nanoseconds.
+    pub time_ns: u64,
+    pub event: usize,
+    pub task: usize,
+    pub arg: usize,
+}
+
+/// Moves the oldest scheduler events recorded by the kernel to `buf`; later calls return the
+/// events that followed.
+///
+/// Returns the number of events, or -1 if the kernel was built without the `sched_trace`
+/// feature.
+pub fn trace_collect(buf: &mut [TraceRecord]) -> isize {
+    sys_trace_collect(buf)
+}
+
+/// `TaskInfo::status` values.
+pub const TASK_READY: usize = 0;
+pub const TASK_RUNNING: usize = 1;
+pub const TASK_EXITED: usize = 2;
+pub const TASK_BLOCKED: usize = 3;
+
+/// Length of `TaskInfo::name`, including the terminating NUL.
+const TASK_NAME_LEN: usize = 32;
+
+/// A task as seen by `task_info`, layout compatible with the kernel's.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TaskInfo {
+    pub id: usize,
+    /// `TASK_READY`, `TASK_RUNNING`, `TASK_EXITED` or `TASK_BLOCKED`.
+    pub status: usize,
+    /// 1 for a kthread.
+    pub kthread: usize,
+    pub group: usize,
+    pub voluntary_switches: usize,
+    pub involuntary_switches: usize,
+    pub name: [u8; TASK_NAME_LEN],
+}
+
+impl TaskInfo {
+    /// Returns the name of the app or kthread.
+    pub fn name(&self) -> &str {
+        name_str(&self.name)
+    }
+}
+
+/// Returns the NUL terminated name in `bytes`.
+fn name_str(bytes: &[u8; TASK_NAME_LEN]) -> &str {
+    let len = bytes.iter().position(|&b| b == 0).unwrap_or(TASK_NAME_LEN);
+    core::str::from_utf8(&bytes[..len]).unwrap_or("?")
+}
+
+/// Gets the name, state and scheduler counters of task `task_id`.
+///
+/// Returns -1 if there is no such task; task ids are dense and start at 0.
+pub fn task_info(task_id: usize, info: &mut TaskInfo) -> isize {
+    sys_task_info(task_id, info)
+}
+
+/// The accounting record of an exited app, layout compatible with the kernel's.
+///
+/// `task` is the task id of the app; there are no processes, so no parent either.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct AcctRecord {
+    /// Time of the exit since boot in nanoseconds.
+    pub time_ns: u64,
+    pub task: usize,
+    pub exit_code: isize,
+    /// Timer ticks the app ran in user mode.
+    pub user_ticks: usize,
+    /// Timer ticks the kernel ran syscalls for the app.
+    pub kernel_ticks: usize,
+    /// Most frames the address space of the app owned at once.
+    pub peak_frames: usize,
+    pub name: [u8; TASK_NAME_LEN],
+}
+
+impl AcctRecord {
+    /// Returns the name of the app.
+    pub fn name(&self) -> &str {
+        name_str(&self.name)
+    }
+}
+
+/// Moves the oldest accounting records of exited apps to `buf`; later calls return the
+/// records that followed.
+///
+/// Returns the number of records, or -1 if the kernel was built without the `acct` feature.
+pub fn acct_collect(buf: &mut [AcctRecord]) -> isize {
+    sys_acct_collect(buf)
+}
+
+/// Makes the kernel fault on purpose while servicing the call, to test that a kernel oops
+/// kills only the calling process.
+///
+/// Does not return, unless the kernel cannot recover from the fault.
+pub fn oops() -> isize {
+    sys_oops()
+}
+
+/// Issues syscall `id` with the raw arguments a0-a5.
+///
+/// For tests passing what the typed wrappers cannot express, e.g. bad pointers or unknown
+/// syscall ids.
+pub fn raw_syscall(id: usize, args: [usize; 6]) -> isize {
+    syscall6(id, args)
+}
@@ -0,0 +1,52 @@
+//! Number parsing helpers for command line arguments.
+
+/// Parse an unsigned integer, decimal or with a `0x`, `0o` or `0b` prefix. `_` separators
+/// are allowed between digits.
+///
+/// Returns `None` on empty input, invalid digits or overflow.
+pub fn parse_usize(s: &str) -> Option<usize> {
+    let (digits, radix) = if let Some(hex) = s.strip_prefix("0x") {
+        (hex, 16)
+    } else if let Some(oct) = s.strip_prefix("0o") {
+        (oct, 8)
+    } else if let Some(bin) = s.strip_prefix("0b") {
+        (bin, 2)
+    } else {
+        (s, 10)
+    };
+
+    let mut value: usize = 0;
+    let mut any_digit = false;
+    for c in digits.chars() {
+        if c == '_' && any_digit {
+            continue;
+        }
+        let digit = c.to_digit(radix)? as usize;
+        value = value.checked_mul(radix as usize)?.checked_add(digit)?;
+        any_digit = true;
+    }
+    any_digit.then_some(value)
+}
+
+/// Parse a signed integer: an optional `-` or `+` followed by anything `parse_usize` accepts.
+pub fn parse_isize(s: &str) -> Option<isize> {
+    if let Some(magnitude) = s.strip_prefix('-') {
+        let magnitude = parse_usize(magnitude)?;
+        if magnitude == isize::MIN.unsigned_abs() {
+            Some(isize::MIN)
+        } else {
+            isize::try_from(magnitude).ok().map(|m| -m)
+        }
+    } else {
+        isize::try_from(parse_usize(s.strip_prefix('+').unwrap_or(s))?).ok()
+    }
+}
+
+/// Parse `yes`/`no`, `true`/`false`, `on`/`off` or `1`/`0`.
+pub fn parse_bool(s: &str) -> Option<bool> {
+    match s {
+        "1" | "yes" | "true" | "on" => Some(true),
+        "0" | "no" | "false" | "off" => Some(false),
+        _ => None,
+    }
+}
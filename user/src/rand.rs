@@ -0,0 +1,35 @@
+//! Random numbers from the kernel's `getrandom`, which is not cryptographically secure.
+
+use crate::getrandom;
+
+/// Returns a random `u64`.
+pub fn random_u64() -> u64 {
+    let mut bytes = [0u8; 8];
+    getrandom(&mut bytes);
+    u64::from_le_bytes(bytes)
+}
+
+/// Returns a random number in `[start, end)`.
+///
+/// # Panics
+/// Panics if the range is empty.
+pub fn random_range(start: u64, end: u64) -> u64 {
+    assert!(start < end, "empty range {start}..{end}");
+    // reject the tail of the u64 range that would bias the modulo
+    let span = end - start;
+    let zone = u64::MAX - u64::MAX % span;
+    loop {
+        let value = random_u64();
+        if value < zone {
+            return start + value % span;
+        }
+    }
+}
+
+/// Shuffle `items` in place (Fisher-Yates).
+pub fn shuffle<T>(items: &mut [T]) {
+    for i in (1..items.len()).rev() {
+        let j = random_range(0, i as u64 + 1) as usize;
+        items.swap(i, j);
+    }
+}
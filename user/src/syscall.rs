@@ -1,13 +1,49 @@
+use crate::{
+    AcctRecord, GroupStat, RUsage, SchedParam, SlabInfo, SysInfo, TaskInfo, TimeSpec, TraceRecord,
+    UtsName, VmaInfo,
+};
 use core::arch::asm;
 
 const SYSCALL_READ: usize = 63;
 const SYSCALL_WRITE: usize = 64;
+const SYSCALL_CAPGET: usize = 90;
+const SYSCALL_CAPSET: usize = 91;
 const SYSCALL_EXIT: usize = 93;
+const SYSCALL_NANOSLEEP: usize = 101;
+const SYSCALL_CLOCK_SETTIME: usize = 112;
+const SYSCALL_CLOCK_GETTIME: usize = 113;
+const SYSCALL_CLOCK_NANOSLEEP: usize = 115;
+const SYSCALL_SCHED_SETSCHEDULER: usize = 119;
 const SYSCALL_YIELD: usize = 124;
+const SYSCALL_REBOOT: usize = 142;
+const SYSCALL_UNAME: usize = 160;
+const SYSCALL_GETRUSAGE: usize = 165;
 const SYSCALL_GET_TIME: usize = 169;
+const SYSCALL_SYSINFO: usize = 179;
 const SYSCALL_FORK: usize = 220;
 const SYSCALL_EXEC: usize = 221;
+const SYSCALL_MADVISE: usize = 233;
+const SYSCALL_RISCV_FLUSH_ICACHE: usize = 259;
+const SYSCALL_GETRANDOM: usize = 278;
 const SYSCALL_WAITPID: usize = 260;
+const SYSCALL_DUMP_SYSCALL_LATENCY: usize = 1000;
+const SYSCALL_GET_VMA_INFO: usize = 1001;
+const SYSCALL_GET_PROFILE: usize = 1002;
+const SYSCALL_KCOV_ENABLE: usize = 1003;
+const SYSCALL_KCOV_COLLECT: usize = 1004;
+const SYSCALL_FAULT_INJECT: usize = 1005;
+const SYSCALL_GROUP_CREATE: usize = 1006;
+const SYSCALL_GROUP_ATTACH: usize = 1007;
+const SYSCALL_GROUP_STAT: usize = 1008;
+const SYSCALL_TRACE_COLLECT: usize = 1009;
+const SYSCALL_TASK_INFO: usize = 1010;
+const SYSCALL_ACCT_COLLECT: usize = 1011;
+const SYSCALL_SLAB_INFO: usize = 1012;
+const SYSCALL_OOPS: usize = 1013;
+
+/// `sys_reboot` magic numbers.
+const REBOOT_MAGIC1: usize = 0xfee1_dead;
+const REBOOT_MAGIC2: usize = 672_274_793;
 
 /// Performs a system call with the given ID and arguments.
 ///
@@ -16,6 +52,16 @@ const SYSCALL_WAITPID: usize = 260;
 /// * `id` - The system call number.
 /// * `args` - An array of up to three arguments for the system call.
 fn syscall(id: usize, args: [usize; 3]) -> isize {
+    syscall6(id, [args[0], args[1], args[2], 0, 0, 0])
+}
+
+/// Performs a system call with up to six arguments, passed in a0-a5.
+///
+/// # Arguments
+///
+/// * `id` - The system call number.
+/// * `args` - The six arguments, unused ones should be 0.
+pub fn syscall6(id: usize, args: [usize; 6]) -> isize {
     let mut ret: isize;
     unsafe {
         asm!(
@@ -23,6 +69,9 @@ fn syscall(id: usize, args: [usize; 3]) -> isize {
             inlateout("x10") args[0] => ret,
             in("x11") args[1],
             in("x12") args[2],
+            in("x13") args[3],
+            in("x14") args[4],
+            in("x15") args[5],
             in("x17") id
         );
     }
@@ -73,6 +122,129 @@ pub fn sys_exit(exit_code: i32) -> isize {
     syscall(SYSCALL_EXIT, [exit_code as usize, 0, 0])
 }
 
+/// Suspends the calling process until the time described by `req` has passed.
+///
+/// # Arguments
+///
+/// * `req` - Duration to sleep, or an absolute deadline on the boot clock with `TIMER_ABSTIME`.
+/// * `rem` - Receives the remaining time if the sleep is interrupted early. May be null.
+/// * `flags` - `0` or `TIMER_ABSTIME`.
+///
+/// # Returns
+///
+/// 0 on success, or -1 if `req` is invalid.
+pub fn sys_nanosleep(req: *const TimeSpec, rem: *mut TimeSpec, flags: usize) -> isize {
+    syscall(SYSCALL_NANOSLEEP, [req as usize, rem as usize, flags])
+}
+
+/// Suspends the calling process until the time described by `req` has passed on a clock.
+///
+/// # Arguments
+///
+/// * `clock_id` - `CLOCK_REALTIME` or `CLOCK_MONOTONIC`.
+/// * `flags` - `0` or `TIMER_ABSTIME`.
+/// * `req` - Duration to sleep, or an absolute deadline on the clock with `TIMER_ABSTIME`.
+/// * `rem` - Receives the remaining time if the sleep is interrupted early. May be null.
+///
+/// # Returns
+///
+/// 0 on success, or -1 for an unknown clock or an invalid `req`.
+pub fn sys_clock_nanosleep(
+    clock_id: usize,
+    flags: usize,
+    req: *const TimeSpec,
+    rem: *mut TimeSpec,
+) -> isize {
+    syscall6(
+        SYSCALL_CLOCK_NANOSLEEP,
+        [clock_id, flags, req as usize, rem as usize, 0, 0],
+    )
+}
+
+/// Gets the time of a clock.
+///
+/// # Arguments
+///
+/// * `clock_id` - `CLOCK_REALTIME` or `CLOCK_MONOTONIC`.
+/// * `tp` - Receives the time.
+///
+/// # Returns
+///
+/// 0 on success, or -1 for an unknown clock.
+pub fn sys_clock_gettime(clock_id: usize, tp: &mut TimeSpec) -> isize {
+    syscall(SYSCALL_CLOCK_GETTIME, [clock_id, tp as *mut _ as usize, 0])
+}
+
+/// Sets the time of a clock. Only `CLOCK_REALTIME` can be set, and only with `CAP_SETTIME`.
+///
+/// # Arguments
+///
+/// * `clock_id` - `CLOCK_REALTIME`.
+/// * `tp` - The new time.
+///
+/// # Returns
+///
+/// 0 on success, or -1 otherwise.
+pub fn sys_clock_settime(clock_id: usize, tp: &TimeSpec) -> isize {
+    syscall(
+        SYSCALL_CLOCK_SETTIME,
+        [clock_id, tp as *const _ as usize, 0],
+    )
+}
+
+/// Gets the capabilities of the calling process.
+///
+/// # Returns
+///
+/// The `CAP_*` bits the process holds.
+pub fn sys_capget() -> isize {
+    syscall(SYSCALL_CAPGET, [0, 0, 0])
+}
+
+/// Sets the capabilities of the calling process.
+///
+/// # Arguments
+///
+/// * `caps` - The `CAP_*` bits to keep, a subset of those held.
+///
+/// # Returns
+///
+/// 0 on success, or -1 if `caps` adds a capability.
+pub fn sys_capset(caps: usize) -> isize {
+    syscall(SYSCALL_CAPSET, [caps, 0, 0])
+}
+
+/// Restarts or powers off the machine.
+///
+/// # Arguments
+///
+/// * `cmd` - `REBOOT_CMD_RESTART` or `REBOOT_CMD_POWER_OFF`.
+///
+/// # Returns
+///
+/// Does not return on success, or -1 otherwise.
+pub fn sys_reboot(cmd: usize) -> isize {
+    syscall(SYSCALL_REBOOT, [REBOOT_MAGIC1, REBOOT_MAGIC2, cmd])
+}
+
+/// Sets the scheduling class of a task.
+///
+/// # Arguments
+///
+/// * `pid` - 0 or the caller's task id.
+/// * `policy` - `SCHED_OTHER` or `SCHED_DEADLINE`.
+/// * `param` - Period, deadline and runtime for `SCHED_DEADLINE`.
+///
+/// # Returns
+///
+/// 0 on success, or -1 otherwise.
+pub fn sys_sched_setscheduler(pid: usize, policy: usize, param: &SchedParam) -> isize {
+    syscall(
+        SYSCALL_SCHED_SETSCHEDULER,
+        [pid, policy, param as *const _ as usize],
+    )
+}
+
 /// Yields the CPU to another process.
 ///
 /// # Returns
@@ -127,3 +299,286 @@ pub fn sys_waitpid(pid: isize, exit_code: *mut i32) -> isize {
 pub fn sys_exec(path: &str) -> isize {
     syscall(SYSCALL_EXEC, [path.as_ptr() as usize, 0, 0])
 }
+
+/// Gives the kernel advice about the use of the memory range `[addr, addr + len)`.
+///
+/// # Arguments
+///
+/// * `addr` - Page aligned start of the range.
+/// * `len` - Length of the range in bytes.
+/// * `advice` - `MADV_WILLNEED` or `MADV_DONTNEED`.
+///
+/// # Returns
+///
+/// 0 on success, or -1 on error.
+pub fn sys_madvise(addr: usize, len: usize, advice: usize) -> isize {
+    syscall(SYSCALL_MADVISE, [addr, len, advice])
+}
+
+/// Synchronizes the instruction cache with code written to memory.
+///
+/// # Arguments
+///
+/// * `start` - Start of the modified range.
+/// * `end` - End of the modified range.
+/// * `flags` - `0`, or `SYS_RISCV_FLUSH_ICACHE_LOCAL` if only this hart runs the code.
+///
+/// # Returns
+///
+/// 0 on success, or -1 on error.
+pub fn sys_riscv_flush_icache(start: usize, end: usize, flags: usize) -> isize {
+    syscall(SYSCALL_RISCV_FLUSH_ICACHE, [start, end, flags])
+}
+
+/// Asks the kernel to print its per-syscall latency histograms (debug syscall).
+///
+/// # Returns
+///
+/// 0 on success, or -1 if the kernel was built without syscall latency tracking.
+pub fn sys_dump_syscall_latency() -> isize {
+    syscall(SYSCALL_DUMP_SYSCALL_LATENCY, [0, 0, 0])
+}
+
+/// Copies the descriptions of the current process's memory areas to `buf` (debug syscall).
+///
+/// # Arguments
+///
+/// * `buf` - Receives at most `buf.len()` memory areas.
+///
+/// # Returns
+///
+/// The total number of memory areas, which may be larger than `buf.len()`.
+pub fn sys_get_vma_info(buf: &mut [VmaInfo]) -> isize {
+    syscall(
+        SYSCALL_GET_VMA_INFO,
+        [buf.as_mut_ptr() as usize, buf.len(), 0],
+    )
+}
+
+/// Gets the name, version, build time, board and hart count of the system.
+///
+/// # Arguments
+///
+/// * `buf` - Receives the system identification.
+///
+/// # Returns
+///
+/// 0 on success.
+pub fn sys_uname(buf: &mut UtsName) -> isize {
+    syscall(SYSCALL_UNAME, [buf as *mut _ as usize, 0, 0])
+}
+
+/// Gets resource usage statistics.
+///
+/// # Arguments
+///
+/// * `who` - `RUSAGE_SELF` for the calling process, or `RUSAGE_CHILDREN`.
+/// * `usage` - Receives the statistics.
+///
+/// # Returns
+///
+/// 0 on success, -1 for an unsupported `who`.
+pub fn sys_getrusage(who: isize, usage: &mut RUsage) -> isize {
+    syscall(
+        SYSCALL_GETRUSAGE,
+        [who as usize, usage as *mut _ as usize, 0],
+    )
+}
+
+/// Fills `buf` with random bytes from the kernel.
+///
+/// # Arguments
+///
+/// * `buf` - Buffer to fill.
+/// * `flags` - Ignored, the kernel's generator never blocks.
+///
+/// # Returns
+///
+/// The number of bytes written.
+pub fn sys_getrandom(buf: &mut [u8], flags: usize) -> isize {
+    syscall(
+        SYSCALL_GETRANDOM,
+        [buf.as_mut_ptr() as usize, buf.len(), flags],
+    )
+}
+
+/// Gets uptime, memory, task and scheduler statistics of the system.
+///
+/// # Arguments
+///
+/// * `info` - Receives the statistics.
+///
+/// # Returns
+///
+/// 0 on success.
+pub fn sys_sysinfo(info: &mut SysInfo) -> isize {
+    syscall(SYSCALL_SYSINFO, [info as *mut _ as usize, 0, 0])
+}
+
+/// Copies the profiler samples (interrupted pcs) of the current process to `buf` (debug
+/// syscall).
+///
+/// # Arguments
+///
+/// * `buf` - Receives at most `buf.len()` samples, oldest first.
+///
+/// # Returns
+///
+/// The number of samples copied, or -1 if the kernel was built without the profiler.
+pub fn sys_get_profile(buf: &mut [usize]) -> isize {
+    syscall(
+        SYSCALL_GET_PROFILE,
+        [buf.as_mut_ptr() as usize, buf.len(), 0],
+    )
+}
+
+/// Starts recording the kernel coverage of this process's syscalls (debug syscall).
+///
+/// # Arguments
+///
+/// * `capacity` - Number of pcs to keep, or 0 to stop recording.
+///
+/// # Returns
+///
+/// 0 on success, or -1 if the kernel was built without kcov.
+pub fn sys_kcov_enable(capacity: usize) -> isize {
+    syscall(SYSCALL_KCOV_ENABLE, [capacity, 0, 0])
+}
+
+/// Moves the kernel pcs recorded since the last call to `buf` (debug syscall).
+///
+/// # Arguments
+///
+/// * `buf` - Receives at most `buf.len()` pcs.
+///
+/// # Returns
+///
+/// The number of pcs copied, or -1 if the kernel was built without kcov.
+pub fn sys_kcov_collect(buf: &mut [usize]) -> isize {
+    syscall(
+        SYSCALL_KCOV_COLLECT,
+        [buf.as_mut_ptr() as usize, buf.len(), 0],
+    )
+}
+
+/// Makes allocations in this process's later syscalls fail at random (debug syscall).
+///
+/// # Arguments
+///
+/// * `frame_permille` - Probability in permille that a frame allocation fails.
+/// * `heap_permille` - Probability in permille that a kernel heap allocation fails.
+///
+/// # Returns
+///
+/// 0 on success, or -1 if the kernel was built without fault injection.
+pub fn sys_fault_inject(frame_permille: usize, heap_permille: usize) -> isize {
+    syscall(SYSCALL_FAULT_INJECT, [frame_permille, heap_permille, 0])
+}
+
+/// Creates a task group getting CPU time in proportion to `weight`.
+///
+/// # Arguments
+///
+/// * `weight` - CPU weight of the group, group 0 has 100.
+///
+/// # Returns
+///
+/// The group id, or -1 on error.
+pub fn sys_group_create(weight: usize) -> isize {
+    syscall(SYSCALL_GROUP_CREATE, [weight, 0, 0])
+}
+
+/// Moves the calling process to a task group.
+///
+/// # Arguments
+///
+/// * `group` - Id of the group.
+///
+/// # Returns
+///
+/// 0 on success, or -1 if there is no such group.
+pub fn sys_group_attach(group: usize) -> isize {
+    syscall(SYSCALL_GROUP_ATTACH, [group, 0, 0])
+}
+
+/// Gets the weight, task count and CPU usage of a task group.
+///
+/// # Arguments
+///
+/// * `group` - Id of the group.
+/// * `stat` - Receives the statistics.
+///
+/// # Returns
+///
+/// 0 on success, or -1 if there is no such group.
+pub fn sys_group_stat(group: usize, stat: &mut GroupStat) -> isize {
+    syscall(SYSCALL_GROUP_STAT, [group, stat as *mut _ as usize, 0])
+}
+
+/// Moves the oldest scheduler trace events out of the kernel.
+///
+/// # Arguments
+///
+/// * `buf` - Receives the events, oldest first.
+///
+/// # Returns
+///
+/// The number of events, or -1 if the kernel has no scheduler tracing.
+pub fn sys_trace_collect(buf: &mut [TraceRecord]) -> isize {
+    syscall(
+        SYSCALL_TRACE_COLLECT,
+        [buf.as_mut_ptr() as usize, buf.len(), 0],
+    )
+}
+
+/// Gets the name, state and scheduler counters of a task.
+///
+/// # Arguments
+///
+/// * `task_id` - Id of the task.
+/// * `info` - Receives the information.
+///
+/// # Returns
+///
+/// 0 on success, or -1 if there is no such task.
+pub fn sys_task_info(task_id: usize, info: &mut TaskInfo) -> isize {
+    syscall(SYSCALL_TASK_INFO, [task_id, info as *mut _ as usize, 0])
+}
+
+/// Moves the oldest accounting records of exited apps out of the kernel.
+///
+/// # Arguments
+///
+/// * `buf` - Receives the records, oldest first.
+///
+/// # Returns
+///
+/// The number of records, or -1 if the kernel has no process accounting.
+pub fn sys_acct_collect(buf: &mut [AcctRecord]) -> isize {
+    syscall(
+        SYSCALL_ACCT_COLLECT,
+        [buf.as_mut_ptr() as usize, buf.len(), 0],
+    )
+}
+
+/// Copies the statistics of the kernel heap's slab caches to `buf`.
+///
+/// # Arguments
+///
+/// * `buf` - Receives at most `buf.len()` caches, smallest objects first.
+///
+/// # Returns
+///
+/// The total number of caches, which may be larger than `buf.len()`.
+pub fn sys_slab_info(buf: &mut [SlabInfo]) -> isize {
+    syscall(SYSCALL_SLAB_INFO, [buf.as_mut_ptr() as usize, buf.len(), 0])
+}
+
+/// Makes the kernel fault while servicing the syscall, which kills the calling process.
+///
+/// # Returns
+///
+/// Does not return, unless the kernel cannot recover from the fault.
+pub fn sys_oops() -> isize {
+    syscall(SYSCALL_OOPS, [0, 0, 0])
+}
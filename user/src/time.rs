@@ -0,0 +1,26 @@
+//! Time formatting helpers.
+
+use crate::get_time;
+use core::fmt;
+
+/// A span of milliseconds, displayed as `[Dd ]HH:MM:SS.mmm`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Millis(pub usize);
+
+impl fmt::Display for Millis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ms = self.0 % 1000;
+        let secs = self.0 / 1000;
+        let (days, hours) = (secs / 86400, secs / 3600 % 24);
+        let (mins, secs) = (secs / 60 % 60, secs % 60);
+        if days > 0 {
+            write!(f, "{days}d ")?;
+        }
+        write!(f, "{hours:02}:{mins:02}:{secs:02}.{ms:03}")
+    }
+}
+
+/// Returns the time since boot.
+pub fn uptime() -> Millis {
+    Millis(get_time() as usize)
+}
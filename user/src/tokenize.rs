@@ -0,0 +1,86 @@
+//! Split a command line into arguments, shell style.
+//!
+//! - Whitespace separates arguments.
+//! - `'...'` keeps everything up to the next `'` literally.
+//! - `"..."` keeps everything literally except `\"` and `\\`.
+//! - Outside quotes, `\` takes the next character literally.
+//!
+//! Quotes may appear inside an argument (`a"b c"d` is one argument `ab cd`) and `""` is an
+//! empty argument.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// Why a command line could not be split.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TokenizeError {
+    /// A quote of this kind was not closed.
+    UnterminatedQuote(char),
+    /// The line ends with an unescaped `\`.
+    TrailingBackslash,
+}
+
+impl fmt::Display for TokenizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnterminatedQuote(quote) => write!(f, "unterminated {quote} quote"),
+            Self::TrailingBackslash => write!(f, "trailing backslash"),
+        }
+    }
+}
+
+/// Split `line` into arguments, see the module docs for the rules.
+pub fn tokenize(line: &str) -> Result<Vec<String>, TokenizeError> {
+    let mut args = Vec::new();
+    // the argument being built, `None` between arguments
+    let mut arg: Option<String> = None;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' | '\t' | '\n' | '\r' => {
+                if let Some(arg) = arg.take() {
+                    args.push(arg);
+                }
+            }
+            '\'' => {
+                let arg = arg.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => arg.push(c),
+                        None => return Err(TokenizeError::UnterminatedQuote('\'')),
+                    }
+                }
+            }
+            '"' => {
+                let arg = arg.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\')) => arg.push(c),
+                            Some(c) => {
+                                arg.push('\\');
+                                arg.push(c);
+                            }
+                            None => return Err(TokenizeError::UnterminatedQuote('"')),
+                        },
+                        Some(c) => arg.push(c),
+                        None => return Err(TokenizeError::UnterminatedQuote('"')),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some(c) => arg.get_or_insert_with(String::new).push(c),
+                None => return Err(TokenizeError::TrailingBackslash),
+            },
+            c => arg.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if let Some(arg) = arg {
+        args.push(arg);
+    }
+    Ok(args)
+}